@@ -0,0 +1,250 @@
+//! # `DBFast` Macros
+//!
+//! Provides `#[dbfast::test]`, which turns an async function into a tokio test
+//! running against its own database cloned from the configured template.
+//! Use it through the re-export in the `dbfast` crate rather than directly.
+
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, rust_2018_idioms)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::{parse::Parser, FnArg, ItemFn, LitBool, LitStr, ReturnType, Token, Type};
+
+/// Run an async test against a fresh database cloned from the template
+///
+/// The test may take no argument or one argument of type `DatabasePool`,
+/// `&DatabasePool`, `&TestDatabase`, `String` or `&str` (the connection URL,
+/// password included, so keep it out of test output).
+/// The database is dropped after the test, also when it panics.
+///
+/// ```rust,ignore
+/// #[dbfast::test(env = "local", fixtures("fixtures/users.sql"))]
+/// async fn counts_users(pool: DatabasePool) {
+///     let rows = pool.query("SELECT count(*) FROM tb_user", &[]).await.unwrap();
+///     assert_eq!(rows[0].get::<_, i64>(0), 2);
+/// }
+/// ```
+///
+/// Attributes:
+/// - `config = "path"`: config file relative to the crate root (default `dbfast.toml`)
+/// - `env = "name"`: environment whose file filters build the template
+/// - `seeds = false`: build the template without seed files
+/// - `fixtures("a.sql", ...)`: SQL files applied to the clone, relative to the config file
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Options parsed from the attribute arguments
+#[derive(Default)]
+struct TestArgs {
+    config: Option<LitStr>,
+    env: Option<LitStr>,
+    seeds: Option<LitBool>,
+    fixtures: Vec<LitStr>,
+}
+
+impl TestArgs {
+    fn parse(attr: TokenStream2) -> syn::Result<Self> {
+        let mut args = Self::default();
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("config") {
+                args.config = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("env") {
+                args.env = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("seeds") {
+                args.seeds = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("fixtures") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                args.fixtures
+                    .extend(Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?);
+            } else {
+                return Err(meta.error("expected `config`, `env`, `seeds` or `fixtures`"));
+            }
+            Ok(())
+        });
+        parser.parse2(attr)?;
+        Ok(args)
+    }
+
+    /// Expression building the `TestDatabaseOptions`
+    fn options(&self) -> TokenStream2 {
+        let config = self.config.as_ref().map_or_else(
+            || quote!(concat!(env!("CARGO_MANIFEST_DIR"), "/dbfast.toml")),
+            |config| quote!(concat!(env!("CARGO_MANIFEST_DIR"), "/", #config)),
+        );
+        let environment = self.env.as_ref().map_or_else(
+            || quote!(::std::option::Option::None),
+            |env| quote!(::std::option::Option::Some(::std::string::String::from(#env))),
+        );
+        let with_seeds = self
+            .seeds
+            .as_ref()
+            .map_or_else(|| quote!(true), ToTokens::to_token_stream);
+        let fixtures = &self.fixtures;
+
+        quote! {
+            ::dbfast::testing::TestDatabaseOptions {
+                config_path: ::std::option::Option::Some(::std::path::PathBuf::from(#config)),
+                environment: #environment,
+                with_seeds: #with_seeds,
+                fixtures: ::std::vec![#(::std::path::PathBuf::from(#fixtures)),*],
+                ..::std::default::Default::default()
+            }
+        }
+    }
+}
+
+/// Expand `#[dbfast::test]` on `item`
+fn expand(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let args = TestArgs::parse(attr)?;
+    let function: ItemFn = syn::parse2(item)?;
+
+    if function.sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            function.sig.fn_token,
+            "#[dbfast::test] functions must be async",
+        ));
+    }
+    if function.sig.inputs.len() > 1 {
+        return Err(syn::Error::new_spanned(
+            &function.sig.inputs,
+            "#[dbfast::test] functions take at most one argument",
+        ));
+    }
+
+    let database_binding = match function.sig.inputs.first() {
+        None => quote!(),
+        Some(FnArg::Receiver(receiver)) => {
+            return Err(syn::Error::new_spanned(
+                receiver,
+                "#[dbfast::test] functions cannot take self",
+            ))
+        }
+        Some(FnArg::Typed(arg)) => {
+            let value = injected_value(&arg.ty)?;
+            let pat = &arg.pat;
+            let ty = &arg.ty;
+            quote!(let #pat: #ty = #value;)
+        }
+    };
+
+    let options = args.options();
+    let attrs = &function.attrs;
+    let vis = &function.vis;
+    let name = &function.sig.ident;
+    let output = &function.sig.output;
+    let output_type = match output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => ty.to_token_stream(),
+    };
+    let body = &function.block;
+
+    Ok(quote! {
+        #(#attrs)*
+        #[::tokio::test]
+        #vis async fn #name() #output {
+            let __dbfast_db = ::dbfast::testing::TestDatabase::with_options(&#options)
+                .await
+                .unwrap_or_else(|e| panic!("dbfast: failed to create test database: {e}"));
+            let __dbfast_result = {
+                #database_binding
+                let __dbfast_body: ::std::pin::Pin<
+                    &mut dyn ::std::future::Future<Output = #output_type>,
+                > = ::std::pin::pin!(async move #body);
+                __dbfast_body.await
+            };
+            __dbfast_db
+                .cleanup()
+                .await
+                .unwrap_or_else(|e| panic!("dbfast: failed to drop test database: {e}"));
+            __dbfast_result
+        }
+    })
+}
+
+/// Value injected for a test argument of type `ty`
+fn injected_value(ty: &Type) -> syn::Result<TokenStream2> {
+    let (is_ref, inner) = match ty {
+        Type::Reference(reference) => (true, &*reference.elem),
+        other => (false, other),
+    };
+    let type_name = match inner {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    };
+
+    match (is_ref, type_name.as_deref()) {
+        (false, Some("DatabasePool")) => Ok(quote!(__dbfast_db.pool().clone())),
+        (true, Some("DatabasePool")) => Ok(quote!(__dbfast_db.pool())),
+        (true, Some("TestDatabase")) => Ok(quote!(&__dbfast_db)),
+        (false, Some("String")) => Ok(quote!(__dbfast_db.url().to_string())),
+        (true, Some("str")) => Ok(quote!(__dbfast_db.url())),
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "expected `DatabasePool`, `&DatabasePool`, `&TestDatabase`, `String` or `&str`",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, TokenStream2};
+    use quote::quote;
+
+    fn expand_error(attr: TokenStream2, item: TokenStream2) -> String {
+        expand(attr, item).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_expands_with_injected_pool() {
+        let expanded = expand(
+            quote!(env = "local", seeds = false, fixtures("users.sql")),
+            quote!(
+                async fn counts_users(pool: DatabasePool) {}
+            ),
+        )
+        .unwrap()
+        .to_string();
+
+        assert!(expanded.contains(":: tokio :: test"));
+        assert!(expanded.contains("__dbfast_db . pool () . clone ()"));
+        assert!(expanded.contains("with_seeds : false"));
+        assert!(expanded.contains("\"users.sql\""));
+        assert!(expanded.contains("\"local\""));
+    }
+
+    #[test]
+    fn test_rejects_invalid_tests() {
+        assert!(expand_error(
+            quote!(),
+            quote!(
+                fn not_async() {}
+            )
+        )
+        .contains("must be async"));
+        assert!(expand_error(
+            quote!(),
+            quote!(
+                async fn wrong(count: u32) {}
+            )
+        )
+        .contains("expected"));
+        assert!(expand_error(
+            quote!(database = "x"),
+            quote!(
+                async fn unknown() {}
+            )
+        )
+        .contains("expected `config`"));
+    }
+}
@@ -99,7 +99,13 @@ impl BackupManager {
         remote_config: &RemoteConfig,
         file_path: &PathBuf,
     ) -> anyhow::Result<bool> {
-        let output = Command::new("pg_dump")
+        let mut command = Command::new("pg_dump");
+        if let Some(password) = remote_config.get_password()? {
+            password.apply_to(&mut command);
+        }
+        // TLS query parameters of the URL take precedence, as in `RemoteConfig::tls`
+        remote_config.tls.apply_to(&mut command);
+        let output = command
             .arg("--compress=9")
             .arg("--format=custom")
             .arg("--file")
@@ -190,7 +196,12 @@ impl BackupManager {
         backup_info: &BackupInfo,
         target_config: &RemoteConfig,
     ) -> anyhow::Result<()> {
-        let output = Command::new("pg_restore")
+        let mut command = Command::new("pg_restore");
+        if let Some(password) = target_config.get_password()? {
+            password.apply_to(&mut command);
+        }
+        target_config.tls.apply_to(&mut command);
+        let output = command
             .arg("--clean")
             .arg("--if-exists")
             .arg("--create")
@@ -1,3 +1,5 @@
+use crate::fingerprint::{fingerprint_files, FingerprintRegistry};
+use crate::profile::BuildReport;
 use crate::scanner::ScannedFile;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -26,6 +28,12 @@ pub struct TemplateMetadata {
     pub created_at: String,
     /// File hashes at time of template creation
     pub file_hashes: HashMap<PathBuf, String>,
+    /// Layer this template was cloned from (layered templates only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_template: Option<String>,
+    /// `created_at` of the base layer when this layer was built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_created_at: Option<String>,
 }
 
 /// Change detector for identifying when SQL files have changed and templates need rebuilding
@@ -57,6 +65,30 @@ impl ChangeDetector {
         &self.root_path
     }
 
+    /// Fingerprint of a set of files, relative to the monitored root path
+    #[must_use]
+    pub fn fingerprint(&self, files: &[ScannedFile]) -> String {
+        fingerprint_files(&self.root_path, files)
+    }
+
+    /// Registry of fingerprinted templates kept next to the template metadata
+    #[must_use]
+    pub fn fingerprint_registry(&self) -> FingerprintRegistry {
+        FingerprintRegistry::new(&self.metadata_dir)
+    }
+
+    /// Remove the stored metadata for a template, if any
+    pub async fn remove_template_metadata(
+        &self,
+        template_name: &str,
+    ) -> Result<(), ChangeDetectionError> {
+        let metadata_file = self.metadata_dir.join(format!("{template_name}.json"));
+        if metadata_file.exists() {
+            fs::remove_file(metadata_file).await?;
+        }
+        Ok(())
+    }
+
     /// Check if a template needs rebuilding based on file changes
     ///
     /// # Arguments
@@ -87,6 +119,63 @@ impl ChangeDetector {
         Ok(Self::compare_files(&current_files, &stored_metadata))
     }
 
+    /// Check if a template built from a specific set of files needs rebuilding
+    ///
+    /// Unlike [`template_needs_rebuild`](Self::template_needs_rebuild), which compares
+    /// against every SQL file under the root path, this compares only the given files.
+    ///
+    /// # Arguments
+    /// * `template_name` - Name of the template to check
+    /// * `current_files` - Files (with current hashes) the template is built from
+    pub async fn template_needs_rebuild_for_files(
+        &self,
+        template_name: &str,
+        current_files: &[ScannedFile],
+    ) -> Result<bool, ChangeDetectionError> {
+        let Some(stored_metadata) = self.get_template_metadata(template_name).await? else {
+            return Ok(true);
+        };
+
+        Ok(Self::compare_files(current_files, &stored_metadata))
+    }
+
+    /// Check if one layer of a layered template needs rebuilding
+    ///
+    /// A layer is stale when its own files changed or when the layer below it
+    /// has been rebuilt since this layer was cloned from it.
+    ///
+    /// # Arguments
+    /// * `layer_name` - Database name of the layer
+    /// * `current_files` - Files (with current hashes) applied in this layer
+    /// * `base_layer` - Database name of the layer below, `None` for the bottom layer
+    pub async fn layer_needs_rebuild(
+        &self,
+        layer_name: &str,
+        current_files: &[ScannedFile],
+        base_layer: Option<&str>,
+    ) -> Result<bool, ChangeDetectionError> {
+        let Some(metadata) = self.read_metadata(layer_name).await? else {
+            return Ok(true);
+        };
+
+        let base_created_at = match base_layer {
+            Some(base_layer) => match self.read_metadata(base_layer).await? {
+                Some(base_metadata) => Some(base_metadata.created_at),
+                None => return Ok(true),
+            },
+            None => None,
+        };
+
+        if metadata.base_template.as_deref() != base_layer
+            || metadata.base_created_at != base_created_at
+        {
+            return Ok(true);
+        }
+
+        let stored_files = Self::metadata_to_scanned_files(metadata);
+        Ok(Self::compare_files(current_files, &stored_files))
+    }
+
     /// Store template metadata for change detection
     ///
     /// # Arguments
@@ -96,6 +185,22 @@ impl ChangeDetector {
         &self,
         template_name: &str,
         scanned_files: &[ScannedFile],
+    ) -> Result<(), ChangeDetectionError> {
+        self.store_layer_metadata(template_name, scanned_files, None)
+            .await
+    }
+
+    /// Store metadata for one layer of a layered template
+    ///
+    /// # Arguments
+    /// * `layer_name` - Database name of the layer
+    /// * `scanned_files` - Files applied in this layer
+    /// * `base_layer` - Database name of the layer it was cloned from, if any
+    pub async fn store_layer_metadata(
+        &self,
+        layer_name: &str,
+        scanned_files: &[ScannedFile],
+        base_layer: Option<&str>,
     ) -> Result<(), ChangeDetectionError> {
         // Ensure metadata directory exists
         fs::create_dir_all(&self.metadata_dir).await?;
@@ -106,20 +211,49 @@ impl ChangeDetector {
             file_hashes.insert(file.path.clone(), file.hash.clone());
         }
 
+        let base_created_at = match base_layer {
+            Some(base_layer) => self
+                .read_metadata(base_layer)
+                .await?
+                .map(|metadata| metadata.created_at),
+            None => None,
+        };
+
         let metadata = TemplateMetadata {
-            name: template_name.to_string(),
+            name: layer_name.to_string(),
             created_at: chrono::Utc::now().to_rfc3339(),
             file_hashes,
+            base_template: base_layer.map(str::to_string),
+            base_created_at,
         };
 
         // Write metadata to file
-        let metadata_file = self.metadata_dir.join(format!("{template_name}.json"));
+        let metadata_file = self.metadata_dir.join(format!("{layer_name}.json"));
         let json_content = serde_json::to_string_pretty(&metadata)?;
         fs::write(metadata_file, json_content).await?;
 
         Ok(())
     }
 
+    /// Store the timings of a build under `.dbfast/builds/`
+    ///
+    /// Every build gets its own file, see [`BuildReport::file_name`].
+    ///
+    /// # Returns
+    /// Path of the written report
+    pub async fn store_build_report(
+        &self,
+        report: &BuildReport,
+    ) -> Result<PathBuf, ChangeDetectionError> {
+        let builds_dir = self.metadata_dir.join("builds");
+        fs::create_dir_all(&builds_dir).await?;
+
+        let report_file = builds_dir.join(report.file_name());
+        fs::write(&report_file, serde_json::to_string_pretty(report)?).await?;
+
+        Ok(report_file)
+    }
+
     /// Get stored template metadata
     ///
     /// # Arguments
@@ -131,6 +265,17 @@ impl ChangeDetector {
         &self,
         template_name: &str,
     ) -> Result<Option<Vec<ScannedFile>>, ChangeDetectionError> {
+        Ok(self
+            .read_metadata(template_name)
+            .await?
+            .map(Self::metadata_to_scanned_files))
+    }
+
+    /// Read the raw metadata file for a template, if it exists
+    async fn read_metadata(
+        &self,
+        template_name: &str,
+    ) -> Result<Option<TemplateMetadata>, ChangeDetectionError> {
         let metadata_file = self.metadata_dir.join(format!("{template_name}.json"));
 
         if !metadata_file.exists() {
@@ -139,8 +284,11 @@ impl ChangeDetector {
 
         let json_content = fs::read_to_string(metadata_file).await?;
         let metadata: TemplateMetadata = serde_json::from_str(&json_content)?;
+        Ok(Some(metadata))
+    }
 
-        // Convert back to ScannedFile format
+    /// Convert stored metadata back to `ScannedFile` format
+    fn metadata_to_scanned_files(metadata: TemplateMetadata) -> Vec<ScannedFile> {
         let mut scanned_files = Vec::new();
         for (path, hash) in metadata.file_hashes {
             scanned_files.push(ScannedFile { path, hash });
@@ -149,7 +297,7 @@ impl ChangeDetector {
         // Sort by path for consistent ordering
         scanned_files.sort_by(|a, b| a.path.cmp(&b.path));
 
-        Ok(Some(scanned_files))
+        scanned_files
     }
 
     /// Compare current files with stored metadata to determine if rebuild is needed
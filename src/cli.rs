@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use crate::clone_registry::{parse_duration, parse_label};
+use crate::config::PerformanceOverrides;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// Main CLI interface for `DBFast`
 #[derive(Parser)]
@@ -34,6 +38,58 @@ pub enum Commands {
         /// Include seed data
         #[arg(long)]
         with_seeds: bool,
+        /// Environment whose file filters are used to build the template
+        #[arg(long, value_name = "ENV")]
+        env: Option<String>,
+        /// Number of databases to create (named <OUTPUT>_1..N when greater than 1)
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        count: usize,
+        /// Time-to-live recorded for the clone(s), e.g. 30m or 2h
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        ttl: Option<Duration>,
+        /// Label recorded for the clone(s), can be repeated
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+        /// Time every statement of a template build, not only every file
+        #[arg(long)]
+        profile_statements: bool,
+        /// Overrides of the `[performance]` settings
+        #[command(flatten)]
+        performance: PerformanceArgs,
+    },
+    /// Manage databases created by seed
+    Clones {
+        /// Clones subcommand
+        #[command(subcommand)]
+        command: ClonesCommands,
+    },
+    /// Force-drop a clone and re-clone it from its recorded template
+    Reset {
+        /// Name of the registered clone to reset
+        #[arg(value_name = "DATABASE")]
+        database: String,
+        /// Overrides of the `[performance]` settings
+        #[command(flatten)]
+        performance: PerformanceArgs,
+    },
+    /// Keep databases warm and lease them to test processes over a socket or HTTP
+    #[command(group(ArgGroup::new("listen").args(["socket", "http"])))]
+    Serve {
+        /// Unix socket to listen on (default: .dbfast/dbfast.sock)
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+        /// Listen for HTTP on this port on 127.0.0.1 instead
+        #[arg(long, value_name = "PORT")]
+        http: Option<u16>,
+        /// Databases kept cloned ahead of time per template
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        warm: usize,
+        /// Lease lifetime without a heartbeat, e.g. 60s or 5m
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "60s")]
+        lease_ttl: Duration,
+        /// Overrides of the `[performance]` settings
+        #[command(flatten)]
+        performance: PerformanceArgs,
     },
     /// Show template and database status
     Status {
@@ -47,12 +103,27 @@ pub enum Commands {
         #[arg(long)]
         verbose: bool,
     },
+    /// Show the order SQL files run in
+    Order {
+        /// Environment whose file filters are used
+        #[arg(long, value_name = "ENV")]
+        env: Option<String>,
+        /// Explain why files run after others
+        #[arg(long)]
+        explain: bool,
+    },
     /// Validate environment configuration
     ValidateEnv {
         /// Environment name to validate
         #[arg(long, value_name = "NAME")]
         env: String,
     },
+    /// Template maintenance
+    Template {
+        /// Template subcommand
+        #[command(subcommand)]
+        command: TemplateCommands,
+    },
     /// Remote database management
     Remote {
         /// Remote subcommand
@@ -79,6 +150,107 @@ pub enum Commands {
     },
 }
 
+/// Command-line overrides of the `[performance]` settings in dbfast.toml
+#[derive(Args, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerformanceArgs {
+    /// Clones created at the same time
+    #[arg(long, value_name = "N")]
+    pub max_concurrent_clones: Option<usize>,
+    /// Connections per database connection pool
+    #[arg(long, value_name = "N")]
+    pub connection_pool_size: Option<u32>,
+    /// Milliseconds to wait for a database connection
+    #[arg(long, value_name = "MS")]
+    pub connection_timeout_ms: Option<u64>,
+    /// Milliseconds a clone may take
+    #[arg(long, value_name = "MS")]
+    pub clone_timeout_ms: Option<u64>,
+    /// Milliseconds a template build may take before it is cancelled
+    #[arg(long, value_name = "MS")]
+    pub build_timeout_ms: Option<u64>,
+}
+
+impl From<PerformanceArgs> for PerformanceOverrides {
+    fn from(args: PerformanceArgs) -> Self {
+        Self {
+            max_concurrent_clones: args.max_concurrent_clones,
+            connection_pool_size: args.connection_pool_size,
+            connection_timeout_ms: args.connection_timeout_ms,
+            clone_timeout_ms: args.clone_timeout_ms,
+            build_timeout_ms: args.build_timeout_ms,
+        }
+    }
+}
+
+/// Clone registry commands
+#[derive(Subcommand)]
+pub enum ClonesCommands {
+    /// List registered clones
+    List,
+    /// Drop registered clones matching a glob pattern
+    Drop {
+        /// Clone name or glob pattern, e.g. `test_db_*`
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+        /// Only list what would be dropped
+        #[arg(long)]
+        dry_run: bool,
+        /// Terminate sessions still connected to the clones
+        #[arg(long)]
+        force: bool,
+    },
+    /// Drop clones past their TTL or older than a given age
+    Gc {
+        /// Also drop clones older than this, e.g. 2h or 7d
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        older_than: Option<Duration>,
+        /// Only list what would be dropped
+        #[arg(long)]
+        dry_run: bool,
+        /// Terminate sessions still connected to the clones
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Template maintenance commands
+#[derive(Subcommand)]
+pub enum TemplateCommands {
+    /// Build or refresh a template and report its slowest files
+    Build {
+        /// Environment whose file filters are used to build the template
+        #[arg(long, value_name = "ENV")]
+        env: Option<String>,
+        /// Include seed data
+        #[arg(long)]
+        with_seeds: bool,
+        /// Time every statement, not only every file
+        #[arg(long)]
+        profile_statements: bool,
+        /// Overrides of the `[performance]` settings
+        #[command(flatten)]
+        performance: PerformanceArgs,
+    },
+    /// Drop fingerprinted templates that are no longer used
+    #[command(group(
+        ArgGroup::new("limit")
+            .required(true)
+            .multiple(true)
+            .args(["older_than_days", "keep"])
+    ))]
+    Gc {
+        /// Drop fingerprints not used for this many days
+        #[arg(long, value_name = "DAYS")]
+        older_than_days: Option<u64>,
+        /// Keep at most this many fingerprints per template
+        #[arg(long, value_name = "COUNT")]
+        keep: Option<usize>,
+        /// Only list what would be dropped
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
 /// Remote database management commands
 #[derive(Subcommand)]
 pub enum RemoteCommands {
@@ -1,10 +1,17 @@
 /// Simple database cloning functionality using `PostgreSQL`'s CREATE DATABASE WITH TEMPLATE
+use crate::clone_registry::CloneRegistry;
 use crate::database::DatabasePool;
+use crate::psql_script::quote_identifier;
+use futures::future::join_all;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::warn;
 
 /// Errors that can occur during database cloning
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum CloneError {
     #[error("Invalid database name: {name}. {reason}")]
     InvalidDatabaseName { name: String, reason: String },
@@ -27,17 +34,35 @@ pub enum CloneError {
     #[error("Database error: {details}")]
     DatabaseError { details: String },
 
+    #[error("Template database '{template}' is being accessed by other users")]
+    SourceDatabaseBusy { template: String },
+
+    #[error("Database '{clone}' is not a registered clone")]
+    CloneNotRegistered { clone: String },
+
     #[error("Clone verification failed: {reason}")]
     CloneVerificationFailed { reason: String },
 }
 
 /// Configuration for clone operations
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)] // Independent feature toggles
 pub struct CloneConfig {
     pub max_concurrent_clones: usize,
     pub clone_timeout: Duration,
+    /// Compare catalog object counts per schema between clone and template
     pub enable_verification: bool,
+    /// Also compare per-table row counts (requires `enable_verification`)
     pub verify_data_integrity: bool,
+    /// Also compare per-table content checksums (requires `verify_data_integrity`)
+    pub verify_checksums: bool,
+    /// Record clones in the clone registry (see [`CloneRegistry`]), which
+    /// creates a `dbfast` schema on the database the manager's pool connects to
+    pub register_clones: bool,
+    /// Time-to-live recorded for new clones, used by `clones gc`
+    pub ttl: Option<Duration>,
+    /// Labels recorded for new clones
+    pub labels: BTreeMap<String, String>,
 }
 
 impl Default for CloneConfig {
@@ -47,38 +72,389 @@ impl Default for CloneConfig {
             clone_timeout: Duration::from_secs(30),
             enable_verification: false,
             verify_data_integrity: false,
+            verify_checksums: false,
+            register_clones: false,
+            ttl: None,
+            labels: BTreeMap::new(),
+        }
+    }
+}
+
+/// Catalog and data summary of a database used to verify clones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseSnapshot {
+    /// Number of objects per (schema, object kind)
+    pub object_counts: BTreeMap<(String, String), i64>,
+    /// Row count per table (`schema.table`), when integrity checking is on
+    pub row_counts: BTreeMap<String, i64>,
+    /// Content checksum per table (`schema.table`), when checksums are on
+    pub checksums: BTreeMap<String, String>,
+}
+
+impl DatabaseSnapshot {
+    /// Describe every difference between a template snapshot and a clone snapshot
+    #[must_use]
+    pub fn differences(template: &Self, clone: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        let object_keys: BTreeSet<_> = template
+            .object_counts
+            .keys()
+            .chain(clone.object_counts.keys())
+            .collect();
+        for key @ (schema, kind) in object_keys {
+            let expected = template.object_counts.get(key).copied().unwrap_or(0);
+            let actual = clone.object_counts.get(key).copied().unwrap_or(0);
+            if expected != actual {
+                differences.push(format!(
+                    "schema {schema}: {expected} {kind}(s) in template, {actual} in clone"
+                ));
+            }
+        }
+
+        let tables: BTreeSet<_> = template
+            .row_counts
+            .keys()
+            .chain(clone.row_counts.keys())
+            .collect();
+        for table in tables {
+            match (template.row_counts.get(table), clone.row_counts.get(table)) {
+                (Some(expected), Some(actual)) if expected != actual => differences.push(format!(
+                    "table {table}: {expected} row(s) in template, {actual} in clone"
+                )),
+                (Some(_), None) => differences.push(format!("table {table}: missing in clone")),
+                (None, Some(_)) => {
+                    differences.push(format!("table {table}: not present in template"));
+                }
+                _ => {}
+            }
+        }
+
+        for (table, expected) in &template.checksums {
+            if let Some(actual) = clone.checksums.get(table) {
+                if expected != actual {
+                    differences.push(format!("table {table}: content checksum differs"));
+                }
+            }
         }
+
+        differences
     }
 }
 
+/// Outcome of one clone in a batch
+#[derive(Debug)]
+pub struct CloneReport {
+    /// Name of the cloned database
+    pub clone_name: String,
+    /// Time spent on this clone, including retries (excluding the wait for a slot)
+    pub duration: Duration,
+    /// Number of attempts made
+    pub attempts: u32,
+    /// Result of the last attempt
+    pub result: Result<(), CloneError>,
+}
+
+/// Objects per schema and kind, excluding system schemas
+const OBJECT_COUNTS_SQL: &str = "
+    SELECT n.nspname::text, CASE c.relkind
+            WHEN 'r' THEN 'table' WHEN 'p' THEN 'partitioned table'
+            WHEN 'i' THEN 'index' WHEN 'I' THEN 'partitioned index'
+            WHEN 'S' THEN 'sequence' WHEN 'v' THEN 'view'
+            WHEN 'm' THEN 'materialized view' WHEN 'c' THEN 'composite type'
+            WHEN 'f' THEN 'foreign table' ELSE c.relkind::text END,
+        count(*)
+    FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace
+    WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+      AND n.nspname NOT LIKE 'pg_toast%' AND n.nspname NOT LIKE 'pg_temp%'
+    GROUP BY 1, 2
+    UNION ALL
+    SELECT n.nspname::text, 'function', count(*)
+    FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace
+    WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+    GROUP BY 1";
+
+/// User tables, excluding system schemas
+const USER_TABLES_SQL: &str = "
+    SELECT schemaname::text, tablename::text FROM pg_tables
+    WHERE schemaname NOT IN ('pg_catalog', 'information_schema')
+    ORDER BY 1, 2";
+
 /// Simple database clone manager
 #[derive(Clone)]
 pub struct CloneManager {
     pool: DatabasePool,
     config: CloneConfig,
+    registry: CloneRegistry,
 }
 
 impl CloneManager {
     /// Create a new clone manager with default configuration
+    ///
+    /// Clones are not registered; see [`CloneConfig::register_clones`].
     #[must_use]
     pub fn new(pool: DatabasePool) -> Self {
-        Self {
-            pool,
-            config: CloneConfig::default(),
-        }
+        Self::new_with_config(pool, CloneConfig::default())
     }
 
     /// Create a new clone manager with custom configuration
     #[must_use]
-    pub const fn new_with_config(pool: DatabasePool, config: CloneConfig) -> Self {
-        Self { pool, config }
+    pub fn new_with_config(pool: DatabasePool, config: CloneConfig) -> Self {
+        let registry = CloneRegistry::new(pool.clone());
+        Self {
+            pool,
+            config,
+            registry,
+        }
     }
 
     /// Clone a database using `PostgreSQL`'s template functionality
+    ///
+    /// Clones that fail because the template is briefly in use by another
+    /// session are retried until `clone_timeout` has passed. With
+    /// `enable_verification`, a clone that fails verification is dropped again.
     pub async fn clone_database(
         &self,
         template_name: &str,
         clone_name: &str,
+    ) -> Result<(), CloneError> {
+        let template_snapshot = self.verification_snapshot(template_name).await?;
+        self.clone_database_with_retry(template_name, clone_name, template_snapshot.as_ref())
+            .await
+            .0
+    }
+
+    /// Clone several databases from one template concurrently
+    ///
+    /// At most `max_concurrent_clones` clones run at the same time. Every clone
+    /// is attempted; the reports are returned in the order of `clone_names`.
+    /// With `enable_verification`, the template is snapshotted once before the
+    /// clones start and every clone is compared against that snapshot.
+    pub async fn clone_databases(
+        &self,
+        template_name: &str,
+        clone_names: &[String],
+    ) -> Vec<CloneReport> {
+        let template_snapshot = match self.verification_snapshot(template_name).await {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                return clone_names
+                    .iter()
+                    .map(|clone_name| CloneReport {
+                        clone_name: clone_name.clone(),
+                        duration: Duration::ZERO,
+                        attempts: 0,
+                        result: Err(e.clone()),
+                    })
+                    .collect();
+            }
+        };
+        let semaphore = Arc::new(Semaphore::new(self.config.max_concurrent_clones.max(1)));
+
+        let clones = clone_names.iter().map(|clone_name| {
+            let semaphore = Arc::clone(&semaphore);
+            let template_snapshot = template_snapshot.as_ref();
+            async move {
+                let _permit = semaphore
+                    .acquire()
+                    .await
+                    .expect("clone semaphore is never closed");
+                let start = Instant::now();
+                let (result, attempts) = self
+                    .clone_database_with_retry(template_name, clone_name, template_snapshot)
+                    .await;
+                CloneReport {
+                    clone_name: clone_name.clone(),
+                    duration: start.elapsed(),
+                    attempts,
+                    result,
+                }
+            }
+        });
+
+        join_all(clones).await
+    }
+
+    /// Snapshot of the template to verify clones against, if verification is on
+    async fn verification_snapshot(
+        &self,
+        template_name: &str,
+    ) -> Result<Option<DatabaseSnapshot>, CloneError> {
+        if !self.config.enable_verification {
+            return Ok(None);
+        }
+        Self::validate_database_name(template_name)?;
+        let exists = self
+            .pool
+            .database_exists(template_name)
+            .await
+            .map_err(|e| CloneError::DatabaseError {
+                details: e.to_string(),
+            })?;
+        if !exists {
+            return Err(CloneError::TemplateNotFound {
+                template: template_name.to_string(),
+            });
+        }
+        self.snapshot_database(template_name).await.map(Some)
+    }
+
+    /// Clone a database, retrying while the template is busy
+    ///
+    /// A clone is verified against `template_snapshot` when one is given.
+    /// Returns the result of the last attempt and the number of attempts made.
+    async fn clone_database_with_retry(
+        &self,
+        template_name: &str,
+        clone_name: &str,
+        template_snapshot: Option<&DatabaseSnapshot>,
+    ) -> (Result<(), CloneError>, u32) {
+        let start = Instant::now();
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+            match self.try_clone_database(template_name, clone_name).await {
+                Err(CloneError::SourceDatabaseBusy { .. })
+                    if start.elapsed() < self.config.clone_timeout =>
+                {
+                    let backoff = Duration::from_millis(25 * u64::from(attempts.min(20)));
+                    tokio::time::sleep(backoff).await;
+                }
+                Ok(()) => {
+                    self.register_clone(template_name, clone_name).await;
+                    if let Some(template_snapshot) = template_snapshot {
+                        let verification = self
+                            .verify_clone_against(template_name, template_snapshot, clone_name)
+                            .await;
+                        if verification.is_err() {
+                            // Nobody gets a clone that failed verification, so don't keep it
+                            if let Err(e) = self.force_drop_database(clone_name).await {
+                                warn!(
+                                    "Failed to drop clone '{}' after failed verification: {}",
+                                    clone_name, e
+                                );
+                            }
+                        }
+                        return (verification, attempts);
+                    }
+                    return (Ok(()), attempts);
+                }
+                result => return (result, attempts),
+            }
+        }
+    }
+
+    /// Verify a clone against its template
+    ///
+    /// Compares catalog object counts per schema and, with `verify_data_integrity`,
+    /// per-table row counts (plus content checksums with `verify_checksums`).
+    ///
+    /// Note that this connects to the template, so clones of the same template
+    /// started meanwhile may have to retry.
+    pub async fn verify_clone(
+        &self,
+        template_name: &str,
+        clone_name: &str,
+    ) -> Result<(), CloneError> {
+        let template = self.snapshot_database(template_name).await?;
+        self.verify_clone_against(template_name, &template, clone_name)
+            .await
+    }
+
+    /// Verify a clone against an earlier snapshot of its template
+    pub async fn verify_clone_against(
+        &self,
+        template_name: &str,
+        template: &DatabaseSnapshot,
+        clone_name: &str,
+    ) -> Result<(), CloneError> {
+        let clone = self.snapshot_database(clone_name).await?;
+
+        let differences = DatabaseSnapshot::differences(template, &clone);
+        if differences.is_empty() {
+            return Ok(());
+        }
+
+        Err(CloneError::CloneVerificationFailed {
+            reason: format!(
+                "clone '{clone_name}' differs from template '{template_name}':\n  - {}",
+                differences.join("\n  - ")
+            ),
+        })
+    }
+
+    /// Collect the verification snapshot of one database
+    pub async fn snapshot_database(
+        &self,
+        database_name: &str,
+    ) -> Result<DatabaseSnapshot, CloneError> {
+        let database_error = |e: crate::database::DatabaseError| CloneError::DatabaseError {
+            details: format!("Failed to inspect database '{database_name}': {e}"),
+        };
+        let pool = self
+            .pool
+            .for_database(database_name)
+            .await
+            .map_err(database_error)?;
+
+        let mut snapshot = DatabaseSnapshot::default();
+        for row in pool
+            .query(OBJECT_COUNTS_SQL, &[])
+            .await
+            .map_err(database_error)?
+        {
+            snapshot
+                .object_counts
+                .insert((row.get(0), row.get(1)), row.get(2));
+        }
+
+        if !self.config.verify_data_integrity {
+            return Ok(snapshot);
+        }
+
+        let tables = pool
+            .query(USER_TABLES_SQL, &[])
+            .await
+            .map_err(database_error)?;
+        for row in tables {
+            let schema: String = row.get(0);
+            let table: String = row.get(1);
+            let qualified = format!("{}.{}", quote_identifier(&schema), quote_identifier(&table));
+            let key = format!("{schema}.{table}");
+
+            let count_rows = pool
+                .query(&format!("SELECT count(*) FROM {qualified}"), &[])
+                .await
+                .map_err(database_error)?;
+            snapshot
+                .row_counts
+                .insert(key.clone(), count_rows[0].get(0));
+
+            if self.config.verify_checksums {
+                // Order-independent: hash every row, then hash the sorted row hashes
+                let checksum_rows = pool
+                    .query(
+                        &format!(
+                            "SELECT coalesce(md5(string_agg(h, '' ORDER BY h)), '') \
+                             FROM (SELECT md5(t::text) AS h FROM {qualified} t) rows"
+                        ),
+                        &[],
+                    )
+                    .await
+                    .map_err(database_error)?;
+                snapshot.checksums.insert(key, checksum_rows[0].get(0));
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Make a single clone attempt
+    async fn try_clone_database(
+        &self,
+        template_name: &str,
+        clone_name: &str,
     ) -> Result<(), CloneError> {
         // Validate database names
         Self::validate_database_name(template_name)?;
@@ -89,8 +465,8 @@ impl CloneManager {
         // Execute the clone operation
         let query = format!(
             "CREATE DATABASE {} WITH TEMPLATE {}",
-            Self::escape_identifier(clone_name),
-            Self::escape_identifier(template_name)
+            quote_identifier(clone_name),
+            quote_identifier(template_name)
         );
 
         let result = self.pool.execute_non_transactional(&query, &[]).await;
@@ -105,7 +481,11 @@ impl CloneManager {
         // Handle database errors
         result.map_err(|e| {
             let error_msg = e.to_string();
-            if error_msg.contains("already exists") {
+            if error_msg.contains("is being accessed by other users") {
+                CloneError::SourceDatabaseBusy {
+                    template: template_name.to_string(),
+                }
+            } else if error_msg.contains("already exists") {
                 CloneError::CloneAlreadyExists {
                     clone: clone_name.to_string(),
                 }
@@ -131,7 +511,7 @@ impl CloneManager {
 
         let query = format!(
             "DROP DATABASE IF EXISTS {}",
-            Self::escape_identifier(database_name)
+            quote_identifier(database_name)
         );
 
         self.pool
@@ -141,9 +521,103 @@ impl CloneManager {
                 details: e.to_string(),
             })?;
 
+        if self.config.register_clones {
+            if let Err(e) = self.registry().remove(database_name).await {
+                warn!(
+                    "Failed to remove clone '{}' from registry: {}",
+                    database_name, e
+                );
+            }
+        }
+
         Ok(())
     }
 
+    /// Drop a database, terminating any sessions still connected to it
+    ///
+    /// Use this for clones left behind by crashed test processes, where a
+    /// plain [`drop_database`](Self::drop_database) fails because of open connections.
+    pub async fn force_drop_database(&self, database_name: &str) -> Result<(), CloneError> {
+        Self::validate_database_name(database_name)?;
+
+        self.pool
+            .force_drop_database(database_name)
+            .await
+            .map_err(|e| CloneError::DatabaseError {
+                details: e.to_string(),
+            })?;
+
+        if self.config.register_clones {
+            if let Err(e) = self.registry().remove(database_name).await {
+                warn!(
+                    "Failed to remove clone '{}' from registry: {}",
+                    database_name, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reset a clone to a clean state
+    ///
+    /// Force-drops the clone and re-clones it from the template recorded in the
+    /// registry, keeping its TTL and labels.
+    ///
+    /// # Returns
+    /// Name of the template the clone was recreated from
+    pub async fn reset_database(&self, clone_name: &str) -> Result<String, CloneError> {
+        Self::validate_database_name(clone_name)?;
+
+        let registry = self.registry();
+        let record = registry
+            .get(clone_name)
+            .await
+            .map_err(|e| CloneError::DatabaseError {
+                details: e.to_string(),
+            })?
+            .ok_or_else(|| CloneError::CloneNotRegistered {
+                clone: clone_name.to_string(),
+            })?;
+
+        self.force_drop_database(clone_name).await?;
+        self.clone_database(&record.template, clone_name).await?;
+
+        registry
+            .record(clone_name, &record.template, record.ttl, &record.labels)
+            .await
+            .map_err(|e| CloneError::DatabaseError {
+                details: e.to_string(),
+            })?;
+
+        Ok(record.template)
+    }
+
+    /// Registry of clones on the admin database this manager connects to
+    #[must_use]
+    pub fn registry(&self) -> CloneRegistry {
+        self.registry.clone()
+    }
+
+    /// Record a new clone in the registry; failures are logged, not fatal
+    async fn register_clone(&self, template_name: &str, clone_name: &str) {
+        if !self.config.register_clones {
+            return;
+        }
+        if let Err(e) = self
+            .registry()
+            .record(
+                clone_name,
+                template_name,
+                self.config.ttl,
+                &self.config.labels,
+            )
+            .await
+        {
+            warn!("Failed to record clone '{}' in registry: {}", clone_name, e);
+        }
+    }
+
     /// Validate a database name for security and `PostgreSQL` compatibility
     pub fn validate_database_name(name: &str) -> Result<(), CloneError> {
         if name.is_empty() {
@@ -188,11 +662,6 @@ impl CloneManager {
 
         Ok(())
     }
-
-    /// Escape a database identifier for safe SQL usage
-    fn escape_identifier(name: &str) -> String {
-        format!("\"{}\"", name.replace('"', "\"\""))
-    }
 }
 
 #[cfg(test)]
@@ -214,6 +683,38 @@ mod tests {
         assert!(CloneManager::validate_database_name("test-db").is_err());
     }
 
+    #[test]
+    fn test_snapshot_differences() {
+        let mut template = DatabaseSnapshot::default();
+        template
+            .object_counts
+            .insert(("public".to_string(), "table".to_string()), 2);
+        template.row_counts.insert("public.tb_user".to_string(), 10);
+        template
+            .checksums
+            .insert("public.tb_user".to_string(), "abc".to_string());
+
+        assert!(DatabaseSnapshot::differences(&template, &template.clone()).is_empty());
+
+        let mut clone = template.clone();
+        clone
+            .object_counts
+            .insert(("public".to_string(), "table".to_string()), 1);
+        clone.row_counts.insert("public.tb_user".to_string(), 8);
+        clone
+            .checksums
+            .insert("public.tb_user".to_string(), "def".to_string());
+
+        assert_eq!(
+            DatabaseSnapshot::differences(&template, &clone),
+            [
+                "schema public: 2 table(s) in template, 1 in clone",
+                "table public.tb_user: 10 row(s) in template, 8 in clone",
+                "table public.tb_user: content checksum differs",
+            ]
+        );
+    }
+
     #[test]
     fn test_clone_config_default() {
         let config = CloneConfig::default();
@@ -0,0 +1,312 @@
+//! # Clone Registry
+//!
+//! Records the databases created by a [`CloneManager`](crate::clone::CloneManager)
+//! with `register_clones` on (as the CLI, the lease server and
+//! [`TestDatabase`](crate::testing::TestDatabase) do) in a `dbfast.clones`
+//! table on the admin database, so clones can be listed, dropped by pattern and
+//! garbage collected by age or TTL.
+
+use crate::database::{DatabaseError, DatabasePool};
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio_postgres::error::SqlState;
+
+/// Registry table setup; safe to run repeatedly
+const CREATE_REGISTRY_SQL: [&str; 2] = [
+    "CREATE SCHEMA IF NOT EXISTS dbfast",
+    "CREATE TABLE IF NOT EXISTS dbfast.clones (
+        name        TEXT PRIMARY KEY,
+        template    TEXT NOT NULL,
+        created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+        created_by  TEXT NOT NULL,
+        ttl_seconds BIGINT,
+        labels      TEXT NOT NULL DEFAULT '{}'
+    )",
+];
+
+/// One registered clone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloneRecord {
+    /// Database name of the clone
+    pub name: String,
+    /// Template the clone was created from
+    pub template: String,
+    /// When the clone was created
+    pub created_at: DateTime<Utc>,
+    /// Who created the clone (`user@host`)
+    pub created_by: String,
+    /// How long the clone may live before `clones gc` drops it
+    pub ttl: Option<Duration>,
+    /// Free-form labels (e.g. `ci_job=1234`)
+    pub labels: BTreeMap<String, String>,
+}
+
+impl CloneRecord {
+    /// Age of the clone at `now`
+    #[must_use]
+    pub fn age(&self, now: DateTime<Utc>) -> Duration {
+        (now - self.created_at).to_std().unwrap_or_default()
+    }
+
+    /// Whether the clone has outlived its TTL at `now`
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.ttl.is_some_and(|ttl| self.age(now) > ttl)
+    }
+}
+
+/// Registry of clones stored on the admin database
+///
+/// Clones of a registry share the knowledge that its table exists.
+#[derive(Clone)]
+pub struct CloneRegistry {
+    pool: DatabasePool,
+    table_ready: Arc<OnceCell<()>>,
+}
+
+impl CloneRegistry {
+    /// Create a registry on the database the pool connects to
+    #[must_use]
+    pub fn new(pool: DatabasePool) -> Self {
+        Self {
+            pool,
+            table_ready: Arc::default(),
+        }
+    }
+
+    /// Create the registry table if it does not exist yet
+    ///
+    /// Only the first successful call per registry runs any SQL.
+    pub async fn ensure_table(&self) -> Result<(), DatabaseError> {
+        self.table_ready
+            .get_or_try_init(|| async {
+                for statement in CREATE_REGISTRY_SQL {
+                    match self.pool.execute_non_transactional(statement, &[]).await {
+                        // Another process created it concurrently
+                        Err(DatabaseError::Query(e))
+                            if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {}
+                        result => result?,
+                    }
+                }
+                Ok::<(), DatabaseError>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Record a newly created clone, replacing any previous record of the same name
+    pub async fn record(
+        &self,
+        name: &str,
+        template: &str,
+        ttl: Option<Duration>,
+        labels: &BTreeMap<String, String>,
+    ) -> Result<(), DatabaseError> {
+        self.ensure_table().await?;
+
+        let ttl_seconds = ttl.map(|ttl| i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX));
+        let labels = serde_json::to_string(labels)
+            .map_err(|e| DatabaseError::Config(format!("Failed to encode labels: {e}")))?;
+
+        self.pool
+            .query(
+                "INSERT INTO dbfast.clones (name, template, created_by, ttl_seconds, labels)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (name) DO UPDATE SET
+                     template = EXCLUDED.template, created_at = now(),
+                     created_by = EXCLUDED.created_by, ttl_seconds = EXCLUDED.ttl_seconds,
+                     labels = EXCLUDED.labels",
+                &[&name, &template, &current_creator(), &ttl_seconds, &labels],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Forget a clone
+    pub async fn remove(&self, name: &str) -> Result<(), DatabaseError> {
+        self.ensure_table().await?;
+        self.pool
+            .query("DELETE FROM dbfast.clones WHERE name = $1", &[&name])
+            .await?;
+        Ok(())
+    }
+
+    /// Forget clones whose database no longer exists
+    ///
+    /// # Returns
+    /// Number of records removed
+    pub async fn prune_missing(&self) -> Result<usize, DatabaseError> {
+        self.ensure_table().await?;
+        let rows = self
+            .pool
+            .query(
+                "DELETE FROM dbfast.clones c
+                 WHERE NOT EXISTS (SELECT 1 FROM pg_database d WHERE d.datname = c.name)
+                 RETURNING c.name",
+                &[],
+            )
+            .await?;
+        Ok(rows.len())
+    }
+
+    /// List registered clones whose database still exists, oldest first
+    pub async fn list(&self) -> Result<Vec<CloneRecord>, DatabaseError> {
+        self.ensure_table().await?;
+        let rows = self
+            .pool
+            .query(
+                "SELECT c.name, c.template, extract(epoch FROM c.created_at)::bigint,
+                        c.created_by, c.ttl_seconds, c.labels
+                 FROM dbfast.clones c
+                 JOIN pg_database d ON d.datname = c.name
+                 ORDER BY c.created_at, c.name",
+                &[],
+            )
+            .await?;
+
+        rows.iter().map(record_from_row).collect()
+    }
+
+    /// Look up one clone by name, whether or not its database still exists
+    pub async fn get(&self, name: &str) -> Result<Option<CloneRecord>, DatabaseError> {
+        self.ensure_table().await?;
+        let rows = self
+            .pool
+            .query(
+                "SELECT c.name, c.template, extract(epoch FROM c.created_at)::bigint,
+                        c.created_by, c.ttl_seconds, c.labels
+                 FROM dbfast.clones c
+                 WHERE c.name = $1",
+                &[&name],
+            )
+            .await?;
+
+        rows.first().map(record_from_row).transpose()
+    }
+}
+
+/// Convert a registry row (name, template, epoch, creator, ttl, labels) into a record
+fn record_from_row(row: &tokio_postgres::Row) -> Result<CloneRecord, DatabaseError> {
+    let created_at: i64 = row.get(2);
+    let ttl_seconds: Option<i64> = row.get(4);
+    let labels: String = row.get(5);
+    Ok(CloneRecord {
+        name: row.get(0),
+        template: row.get(1),
+        created_at: Utc
+            .timestamp_opt(created_at, 0)
+            .single()
+            .unwrap_or_else(Utc::now),
+        created_by: row.get(3),
+        ttl: ttl_seconds.map(|seconds| Duration::from_secs(u64::try_from(seconds).unwrap_or(0))),
+        labels: serde_json::from_str(&labels)
+            .map_err(|e| DatabaseError::Config(format!("Invalid labels for clone: {e}")))?,
+    })
+}
+
+/// Identify who is creating a clone (`user@host`)
+#[must_use]
+pub fn current_creator() -> String {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    match std::env::var("HOSTNAME") {
+        Ok(host) if !host.is_empty() => format!("{user}@{host}"),
+        _ => user,
+    }
+}
+
+/// Parse a duration such as `90s`, `30m`, `2h` or `7d`
+///
+/// A bare number is taken as seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let invalid = || format!("Invalid duration '{value}', expected e.g. 30m, 2h or 7d");
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let multiplier = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        other => return Err(format!("Unknown duration unit '{other}', use s, m, h or d")),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// Format a duration compactly (`2h5m`, `45s`)
+#[must_use]
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        seconds / 86_400,
+        seconds % 86_400 / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60,
+    );
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{seconds}s"),
+        (0, 0, _) => format!("{minutes}m{seconds}s"),
+        (0, _, _) => format!("{hours}h{minutes}m"),
+        _ => format!("{days}d{hours}h"),
+    }
+}
+
+/// Parse a `KEY=VALUE` label
+pub fn parse_label(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, label)) if !key.is_empty() => Ok((key.to_string(), label.to_string())),
+        _ => Err(format!("Invalid label '{value}', expected KEY=VALUE")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1_800)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7_200)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(604_800)));
+        assert!(parse_duration("2w").is_err());
+        assert!(parse_duration("h").is_err());
+        assert_eq!(
+            parse_duration("999999999999999999d"),
+            Err("Invalid duration '999999999999999999d', expected e.g. 30m, 2h or 7d".to_string())
+        );
+    }
+
+    #[test]
+    fn test_record_expiry() {
+        let now = Utc::now();
+        let record = CloneRecord {
+            name: "test_db_1".to_string(),
+            template: "app_template".to_string(),
+            created_at: now - chrono::Duration::hours(3),
+            created_by: "ci".to_string(),
+            ttl: Some(Duration::from_secs(7_200)),
+            labels: BTreeMap::new(),
+        };
+
+        assert!(record.is_expired(now));
+        assert!(!CloneRecord {
+            ttl: None,
+            ..record.clone()
+        }
+        .is_expired(now));
+        assert_eq!(format_duration(record.age(now)), "3h0m");
+    }
+}
@@ -0,0 +1,264 @@
+use crate::clone::CloneManager;
+use crate::clone_registry::{format_duration, CloneRecord};
+use crate::config::{Config, PerformanceOverrides};
+use crate::database::DatabasePool;
+use crate::error::{DbFastError, Result};
+use globset::Glob;
+use std::path::Path;
+use std::time::Duration;
+
+/// Run an async clones subcommand on a fresh runtime
+fn block_on<F: std::future::Future<Output = Result<()>>>(future: F) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to create async runtime: {e}"),
+    })?;
+    rt.block_on(future)
+}
+
+#[allow(clippy::disallowed_methods)]
+/// Handle `clones list`
+pub fn handle_clones_list() -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+    block_on(handle_clones_list_in_dir(&current_dir))
+}
+
+#[allow(clippy::disallowed_methods)]
+/// Handle `clones drop <pattern>`
+pub fn handle_clones_drop(pattern: &str, dry_run: bool, force: bool) -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+    block_on(handle_clones_drop_in_dir(
+        &current_dir,
+        pattern,
+        dry_run,
+        force,
+    ))
+}
+
+#[allow(clippy::disallowed_methods)]
+/// Handle `clones gc`
+pub fn handle_clones_gc(older_than: Option<Duration>, dry_run: bool, force: bool) -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+    block_on(handle_clones_gc_in_dir(
+        &current_dir,
+        older_than,
+        dry_run,
+        force,
+    ))
+}
+
+#[allow(clippy::disallowed_methods)]
+/// Handle `reset <database>`
+pub fn handle_reset(database: &str, performance: &PerformanceOverrides) -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+    block_on(handle_reset_in_dir(&current_dir, database, performance))
+}
+
+/// List registered clones
+pub async fn handle_clones_list_in_dir(dir: &Path) -> Result<()> {
+    let clone_manager = connect(dir, &PerformanceOverrides::default()).await?;
+    let registry = clone_manager.registry();
+    registry.prune_missing().await.map_err(registry_error)?;
+    let clones = registry.list().await.map_err(registry_error)?;
+
+    if clones.is_empty() {
+        println!("📭 No registered clones");
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now();
+    println!("📋 Registered clones ({}):", clones.len());
+    for clone in &clones {
+        let ttl = clone.ttl.map_or_else(
+            || "-".to_string(),
+            |ttl| {
+                if clone.is_expired(now) {
+                    format!("{} (expired)", format_duration(ttl))
+                } else {
+                    format_duration(ttl)
+                }
+            },
+        );
+        let labels = clone
+            .labels
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        println!(
+            "   {:<30} template={} age={} ttl={} by={}{}",
+            clone.name,
+            clone.template,
+            format_duration(clone.age(now)),
+            ttl,
+            clone.created_by,
+            if labels.is_empty() {
+                String::new()
+            } else {
+                format!(" labels={labels}")
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Drop registered clones whose name matches a glob pattern (e.g. `test_db_*`)
+///
+/// Only clones recorded in the registry are considered, so unrelated
+/// databases on the server can never match. With `force`, sessions still
+/// connected to a clone are terminated instead of failing the drop.
+pub async fn handle_clones_drop_in_dir(
+    dir: &Path,
+    pattern: &str,
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let matcher = Glob::new(pattern)
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Invalid pattern '{pattern}': {e}"),
+        })?
+        .compile_matcher();
+
+    let clone_manager = connect(dir, &PerformanceOverrides::default()).await?;
+    let clones = clone_manager
+        .registry()
+        .list()
+        .await
+        .map_err(registry_error)?;
+    let matching: Vec<CloneRecord> = clones
+        .into_iter()
+        .filter(|clone| matcher.is_match(&clone.name))
+        .collect();
+
+    drop_clones(&clone_manager, &matching, dry_run, force).await
+}
+
+/// Drop clones older than `older_than` and clones whose TTL has expired
+pub async fn handle_clones_gc_in_dir(
+    dir: &Path,
+    older_than: Option<Duration>,
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let clone_manager = connect(dir, &PerformanceOverrides::default()).await?;
+    let registry = clone_manager.registry();
+    let pruned = registry.prune_missing().await.map_err(registry_error)?;
+    if pruned > 0 {
+        println!("🧹 Forgot {pruned} clone(s) that no longer exist");
+    }
+
+    let now = chrono::Utc::now();
+    let expired: Vec<CloneRecord> = registry
+        .list()
+        .await
+        .map_err(registry_error)?
+        .into_iter()
+        .filter(|clone| clone.is_expired(now) || older_than.is_some_and(|age| clone.age(now) > age))
+        .collect();
+
+    drop_clones(&clone_manager, &expired, dry_run, force).await
+}
+
+/// Force-drop a registered clone and re-clone it from its recorded template
+pub async fn handle_reset_in_dir(
+    dir: &Path,
+    database: &str,
+    performance: &PerformanceOverrides,
+) -> Result<()> {
+    let clone_manager = connect(dir, performance).await?;
+    let start = std::time::Instant::now();
+
+    let template = clone_manager.reset_database(database).await.map_err(|e| {
+        DbFastError::ConfigCreationFailed {
+            message: format!("Failed to reset '{database}': {e}"),
+        }
+    })?;
+
+    println!(
+        "🔄 Reset {database} from {template} in {}ms",
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// Drop the given clones (or only list them with `dry_run`)
+async fn drop_clones(
+    clone_manager: &CloneManager,
+    clones: &[CloneRecord],
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    if clones.is_empty() {
+        println!("✅ No matching clones");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("🔍 Would drop {} clone(s):", clones.len());
+        for clone in clones {
+            println!("   - {}", clone.name);
+        }
+        return Ok(());
+    }
+
+    let mut failures = Vec::new();
+    for clone in clones {
+        let result = if force {
+            clone_manager.force_drop_database(&clone.name).await
+        } else {
+            clone_manager.drop_database(&clone.name).await
+        };
+        match result {
+            Ok(()) => println!("🗑️  Dropped {}", clone.name),
+            Err(e) => {
+                println!("❌ Failed to drop {}: {e}", clone.name);
+                failures.push(clone.name.clone());
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(DbFastError::ConfigCreationFailed {
+            message: format!("Failed to drop clone(s): {}", failures.join(", ")),
+        })
+    }
+}
+
+/// Load the config in `dir` and connect to the admin database
+///
+/// The pool and clones follow `[performance]`, with `performance` overriding it.
+async fn connect(dir: &Path, performance: &PerformanceOverrides) -> Result<CloneManager> {
+    let config_path = dir.join("dbfast.toml");
+    if !config_path.exists() {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: "No dbfast.toml config file found. Run 'dbfast init' first.".to_string(),
+        });
+    }
+
+    let config =
+        Config::from_file(&config_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to load config: {e}"),
+        })?;
+
+    let performance = config.performance.with_overrides(performance);
+    let pool =
+        DatabasePool::from_config_with_pool_config(&config.database, performance.pool_config())
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to connect to database: {e}"),
+            })?;
+
+    Ok(CloneManager::new_with_config(
+        pool,
+        performance.clone_config(),
+    ))
+}
+
+/// Map a registry error to a command error
+fn registry_error(e: crate::database::DatabaseError) -> DbFastError {
+    DbFastError::ConfigCreationFailed {
+        message: format!("Failed to read clone registry: {e}"),
+    }
+}
@@ -201,15 +201,13 @@ fn validate_deployment(
         .parse_connection_url()
         .map_err(|e| anyhow::anyhow!("Invalid remote URL: {}", e))?;
 
-    // Check password environment variable
-    if let Some(password_env) = &remote_config.password_env {
-        if std::env::var(password_env).is_err() {
-            return Err(anyhow::anyhow!(
-                "Password environment variable '{}' not set",
-                password_env
-            ));
-        }
-    }
+    // Check the configured password source can produce a password
+    remote_config
+        .get_password()
+        .map_err(|e| anyhow::anyhow!("Cannot resolve remote password: {}", e))?;
+    remote_config
+        .tls()
+        .map_err(|e| anyhow::anyhow!("Invalid remote TLS settings: {}", e))?;
 
     // Environment-specific validations
     if target_env == "production" {
@@ -1,12 +1,24 @@
-use crate::config::Config;
+use crate::config::{Config, Environment};
 use crate::error::{DbFastError, Result};
-use crate::scanner::FileScanner;
-use std::path::PathBuf;
+use crate::sql_repository::SqlRepository;
+use std::path::Path;
 
-/// Handle the environments command synchronously
+#[allow(clippy::disallowed_methods)]
+/// Handle the environments command synchronously (wrapper for async implementation)
 pub fn handle_environments(verbose: bool) -> Result<()> {
-    // Try to load config from current directory
-    let config_path = std::env::current_dir()?.join("dbfast.toml");
+    let rt = tokio::runtime::Runtime::new().map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to create async runtime: {e}"),
+    })?;
+
+    let current_dir = std::env::current_dir()?;
+    rt.block_on(handle_environments_in_dir(&current_dir, verbose))
+}
+
+/// List the configured environments with the files each one runs
+///
+/// Files are selected exactly as `dbfast seed --env <name>` selects them.
+pub async fn handle_environments_in_dir(dir: &Path, verbose: bool) -> Result<()> {
+    let config_path = dir.join("dbfast.toml");
     if !config_path.exists() {
         return Err(DbFastError::ConfigCreationFailed {
             message: "No dbfast.toml config file found. Run 'dbfast init' first.".to_string(),
@@ -21,36 +33,38 @@ pub fn handle_environments(verbose: bool) -> Result<()> {
     println!("🌍 Configured Environments:");
     println!();
 
-    // Get repository path for file scanning
-    let repo_path = PathBuf::from(&config.repository.path);
-    let scanner = FileScanner::new(&repo_path);
-
-    for (env_name, environment) in &config.environments {
-        // Count files that would be included in this environment
-        let file_count = count_environment_files(&scanner, environment, verbose)?;
-
-        println!("• {env_name} ({file_count} files)");
-
-        // Always show basic directory info (includes/excludes summary)
-        let include_summary = environment.include_directories.join(", ");
-        println!("  Includes: {include_summary}");
-
-        if !environment.exclude_directories.is_empty() {
-            let exclude_summary = environment.exclude_directories.join(", ");
-            println!("  Excludes: {exclude_summary}");
-        }
-
-        if verbose {
-            println!("  Detailed configuration:");
-            println!("    Include directories:");
-            for dir in &environment.include_directories {
-                println!("      + {dir}");
-            }
+    let repo_path = dir.join(&config.repository.path);
+    let repository =
+        SqlRepository::new(&repo_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to open SQL repository: {e}"),
+        })?;
 
-            if !environment.exclude_directories.is_empty() {
-                println!("    Exclude directories:");
-                for dir in &environment.exclude_directories {
-                    println!("      - {dir}");
+    let mut environments: Vec<_> = config.environments.iter().collect();
+    environments.sort_by_key(|(env_name, _)| env_name.as_str());
+    for (env_name, environment) in environments {
+        let files = repository
+            .discover_sql_files_for_environment(&environment.to_environment_config(env_name))
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to discover SQL files for '{env_name}': {e}"),
+            })?;
+
+        println!("• {env_name} ({} files)", files.len());
+        print_directories(environment, verbose);
+
+        if verbose && !files.is_empty() {
+            println!("  Files:");
+            for file in &files {
+                let directives = repository.file_directives(file).map_err(|e| {
+                    DbFastError::ConfigCreationFailed {
+                        message: format!("Failed to read directives: {e}"),
+                    }
+                })?;
+                let path = file.strip_prefix(&repo_path).unwrap_or(file).display();
+                if directives.is_empty() {
+                    println!("    - {path}");
+                } else {
+                    println!("    - {path} [{directives}]");
                 }
             }
         }
@@ -65,55 +79,32 @@ pub fn handle_environments(verbose: bool) -> Result<()> {
     Ok(())
 }
 
-/// Filter files based on environment include/exclude patterns
-fn file_matches_environment(file_path_str: &str, environment: &crate::config::Environment) -> bool {
-    // Check if file is in any include directory
-    let included = environment
-        .include_directories
-        .iter()
-        .any(|include_dir| file_path_str.contains(include_dir));
-
-    if !included {
-        return false;
+fn print_directories(environment: &Environment, verbose: bool) {
+    // An empty include list places no directory restriction
+    let include_summary = if environment.include_directories.is_empty() {
+        "(all directories)".to_string()
+    } else {
+        environment.include_directories.join(", ")
+    };
+    println!("  Includes: {include_summary}");
+
+    if !environment.exclude_directories.is_empty() {
+        let exclude_summary = environment.exclude_directories.join(", ");
+        println!("  Excludes: {exclude_summary}");
     }
 
-    // Check if file is excluded
-    let excluded = environment
-        .exclude_directories
-        .iter()
-        .any(|exclude_dir| file_path_str.contains(exclude_dir));
-
-    !excluded
-}
-
-fn count_environment_files(
-    scanner: &FileScanner,
-    environment: &crate::config::Environment,
-    verbose: bool,
-) -> Result<usize> {
-    // Scan all files first
-    let all_files = scanner
-        .scan()
-        .map_err(|e| DbFastError::ConfigCreationFailed {
-            message: format!("Failed to scan files: {e}"),
-        })?;
-
-    // Filter based on environment configuration
-    let filtered_files: Vec<_> = all_files
-        .iter()
-        .filter(|file| {
-            let file_path_str = file.path.to_string_lossy();
-            file_matches_environment(&file_path_str, environment)
-        })
-        .collect();
+    if verbose {
+        println!("  Detailed configuration:");
+        println!("    Include directories:");
+        for dir in &environment.include_directories {
+            println!("      + {dir}");
+        }
 
-    if verbose && !filtered_files.is_empty() {
-        println!("  Files:");
-        for file in &filtered_files {
-            let path = file.path.display();
-            println!("    - {path}");
+        if !environment.exclude_directories.is_empty() {
+            println!("    Exclude directories:");
+            for dir in &environment.exclude_directories {
+                println!("      - {dir}");
+            }
         }
     }
-
-    Ok(filtered_files.len())
 }
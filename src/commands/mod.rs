@@ -1,11 +1,19 @@
+/// Clone registry commands
+pub mod clones;
 /// Environments command functionality
 pub mod environments;
 /// Init command functionality
 pub mod init;
+/// Order command functionality
+pub mod order;
 /// Seed command functionality
 pub mod seed;
+/// Clone-leasing server command
+pub mod serve;
 /// Status command functionality
 pub mod status;
+/// Template maintenance commands
+pub mod template;
 /// Validate environment command functionality
 pub mod validate_env;
 
@@ -0,0 +1,92 @@
+use crate::config::Config;
+use crate::error::{DbFastError, Result};
+use crate::ordering::DependencyOrder;
+use crate::sql_repository::SqlRepository;
+use std::path::{Path, PathBuf};
+
+#[allow(clippy::disallowed_methods)]
+/// Handle the order command synchronously (wrapper for async implementation)
+pub fn handle_order(env_name: Option<&str>, explain: bool) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to create async runtime: {e}"),
+    })?;
+
+    let current_dir = std::env::current_dir()?;
+    rt.block_on(handle_order_in_dir(&current_dir, env_name, explain))
+}
+
+/// Print the order SQL files run in, and with `explain` why
+///
+/// Configured environments are filtered with their `[environments.<name>]`
+/// rules, and unknown names are rejected, like `dbfast seed --env` does.
+pub async fn handle_order_in_dir(dir: &Path, env_name: Option<&str>, explain: bool) -> Result<()> {
+    let config_path = dir.join("dbfast.toml");
+    if !config_path.exists() {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: "No dbfast.toml config file found. Run 'dbfast init' first.".to_string(),
+        });
+    }
+
+    let config =
+        Config::from_file(&config_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to load config: {e}"),
+        })?;
+
+    let repo_path = dir.join(&config.repository.path);
+    let repository =
+        SqlRepository::new(&repo_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to open SQL repository: {e}"),
+        })?;
+
+    let order = match env_name {
+        Some(name) => match config
+            .environment(name)
+            .map_err(|message| DbFastError::ConfigCreationFailed { message })?
+        {
+            Some(environment) => {
+                repository
+                    .order_sql_files_for_environment(&environment.to_environment_config(name))
+                    .await
+            }
+            None => repository.order_sql_files(&[name]).await,
+        },
+        None => repository.order_sql_files(&[]).await,
+    }
+    .map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to order SQL files: {e}"),
+    })?;
+
+    println!("📋 Execution order ({} files):", order.files.len());
+    print_order(&order, &repo_path, explain);
+
+    Ok(())
+}
+
+fn print_order(order: &DependencyOrder, repo_path: &Path, explain: bool) {
+    let relative =
+        |path: &Path| -> PathBuf { path.strip_prefix(repo_path).unwrap_or(path).to_path_buf() };
+
+    for (position, file) in order.files.iter().enumerate() {
+        let path = relative(&file.path);
+        if explain && file.discovered_at != position {
+            println!(
+                "{:>4}. {} (path order: {})",
+                position + 1,
+                path.display(),
+                file.discovered_at + 1
+            );
+        } else {
+            println!("{:>4}. {}", position + 1, path.display());
+        }
+
+        if explain {
+            for dependency in &file.dependencies {
+                println!(
+                    "        after {}: {}",
+                    relative(&dependency.path).display(),
+                    dependency.reason
+                );
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
 //! Remote database management commands
 
 use crate::config::Config;
+use crate::credentials::redact_url;
+use crate::database::DatabasePool;
 use crate::remote::RemoteConfig;
+use crate::tls::TlsConfig;
 use anyhow::Result;
 use std::fs;
 use std::path::Path;
@@ -19,7 +22,11 @@ pub fn handle_remote_add(
     info!("Adding remote database configuration: {}", name);
     debug!(
         "Remote config details: url={}, env={}, password_env={:?}, destructive={}, skip_backup={}",
-        url, env, password_env, allow_destructive, skip_backup
+        redact_url(url),
+        env,
+        password_env,
+        allow_destructive,
+        skip_backup
     );
 
     let config_path = "dbfast.toml";
@@ -59,10 +66,13 @@ pub fn handle_remote_add(
         name: Some(name.to_string()),
         url: url.to_string(),
         password_env,
+        password_file: None,
+        password_command: None,
         environment: env.to_string(),
         allow_destructive,
         backup_before_deploy: !skip_backup,
         require_confirmation: false,
+        tls: TlsConfig::default(),
     };
 
     // Validate the URL can be parsed
@@ -122,7 +132,7 @@ pub fn handle_remote_list(verbose: bool) -> Result<()> {
             println!("   Database:    {}", params.database);
             println!("   User:        {}", params.user);
         } else {
-            println!("   URL:         {} (invalid)", remote.url);
+            println!("   URL:         {} (invalid)", redact_url(&remote.url));
         }
 
         println!("   Environment: {}", remote.environment);
@@ -153,7 +163,10 @@ pub fn handle_remote_list(verbose: bool) -> Result<()> {
                 }
             );
 
-            if let Some(password_env) = &remote.password_env {
+            let credentials = remote.credentials();
+            if let (Some(password_env), None, None) =
+                (credentials.env, credentials.file, credentials.command)
+            {
                 let has_password = std::env::var(password_env).is_ok();
                 // Don't log actual environment variable name for security
                 println!(
@@ -165,7 +178,12 @@ pub fn handle_remote_list(verbose: bool) -> Result<()> {
                     }
                 );
             } else {
-                println!("   Password:    None");
+                println!("   Password:    {}", credentials.kind());
+            }
+
+            match remote.tls() {
+                Ok(tls) => println!("   TLS:         sslmode={}", tls.mode()),
+                Err(e) => println!("   TLS:         ❌ {e}"),
             }
         }
 
@@ -204,34 +222,50 @@ pub async fn handle_remote_test(name: &str) -> Result<()> {
     println!("   User:        {}", params.user);
 
     // Check password
-    let password_result = remote.get_password();
-    match password_result {
-        Ok(password) => {
-            if password.is_empty() && remote.password_env.is_some() {
-                println!(
-                    "   Password:    ❌ Environment variable '{}' not set",
-                    remote.password_env.as_ref().unwrap()
-                );
-                return Err(anyhow::anyhow!("Password environment variable not set"));
-            }
-            println!("   Password:    ✅ Available");
-        }
+    let source = remote.credentials().kind();
+    match remote.get_password() {
+        Ok(Some(_)) => println!("   Password:    ✅ Available ({source})"),
+        Ok(None) => println!("   Password:    None (no matching .pgpass entry)"),
         Err(e) => {
             println!("   Password:    ❌ {e}");
             return Err(anyhow::anyhow!("Password error: {}", e));
         }
     }
 
-    // Test connection (simplified - would normally create a connection pool)
-    println!("   Connection:  🔄 Testing...");
+    let tls = remote
+        .tls()
+        .map_err(|e| anyhow::anyhow!("Invalid TLS settings: {}", e))?;
+    println!("   TLS:         sslmode={}", tls.mode());
 
-    // For now, just validate the configuration is complete
-    // In a full implementation, this would create an actual database connection
-    println!("   Connection:  ✅ Configuration valid (actual connection test not implemented yet)");
+    println!("   Connection:  🔄 Testing...");
+    let pool = DatabasePool::from_remote(remote).await.map_err(|e| {
+        println!("   Connection:  ❌ {e}");
+        anyhow::anyhow!("Connection to remote '{}' failed: {}", name, e)
+    })?;
+    let rows = pool
+        .query_direct(
+            "SELECT version(), COALESCE((SELECT ssl FROM pg_stat_ssl WHERE pid = pg_backend_pid()), false)",
+            &[],
+        )
+        .await
+        .map_err(|e| {
+            println!("   Connection:  ❌ {e}");
+            anyhow::anyhow!("Connection to remote '{}' failed: {}", name, e)
+        })?;
+    let version: String = rows[0].get(0);
+    let encrypted: bool = rows[0].get(1);
+    println!("   Connection:  ✅ {version}");
+    println!(
+        "   Encrypted:   {}",
+        if encrypted {
+            "✅ TLS"
+        } else {
+            "❌ plain text"
+        }
+    );
 
     println!();
-    println!("✅ Remote '{name}' configuration is valid");
-    println!("⚠️  Note: Actual database connectivity test not yet implemented");
+    println!("✅ Remote '{name}' is reachable");
 
     Ok(())
 }
@@ -1,27 +1,102 @@
-use crate::clone::CloneManager;
-use crate::config::Config;
+use crate::clone::{CloneConfig, CloneManager, CloneReport};
+use crate::config::{Config, PerformanceOverrides};
 use crate::database::DatabasePool;
 use crate::error::{DbFastError, Result};
-use crate::scanner::FileScanner;
-use crate::template::TemplateManager;
-use std::path::PathBuf;
-use std::time::Instant;
+use crate::sql_repository::SqlRepository;
+use crate::template::{TemplateLayer, TemplateManager};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Options for the seed command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedOptions {
+    /// Include seed data
+    pub with_seeds: bool,
+    /// Environment whose file filters are used to build the template
+    pub environment: Option<String>,
+    /// Number of databases to create; with more than one, `<output>_1..N` are created
+    pub count: usize,
+    /// Time-to-live recorded in the clone registry
+    pub ttl: Option<Duration>,
+    /// Labels recorded in the clone registry
+    pub labels: BTreeMap<String, String>,
+    /// Time every statement of a template build, not only every file
+    pub profile_statements: bool,
+    /// `[performance]` settings given on the command line
+    pub performance: PerformanceOverrides,
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        Self {
+            with_seeds: false,
+            environment: None,
+            count: 1,
+            ttl: None,
+            labels: BTreeMap::new(),
+            profile_statements: false,
+            performance: PerformanceOverrides::default(),
+        }
+    }
+}
 
 #[allow(clippy::disallowed_methods)]
 /// Handle the seed command synchronously (wrapper for async implementation)
 pub fn handle_seed(output_name: &str, with_seeds: bool) -> Result<()> {
+    handle_seed_with_options(
+        output_name,
+        &SeedOptions {
+            with_seeds,
+            ..SeedOptions::default()
+        },
+    )
+}
+
+#[allow(clippy::disallowed_methods)]
+/// Handle the seed command synchronously with the given options
+pub fn handle_seed_with_options(output_name: &str, options: &SeedOptions) -> Result<()> {
     // Create a runtime for async operations
     let rt = tokio::runtime::Runtime::new().map_err(|e| DbFastError::ConfigCreationFailed {
         message: format!("Failed to create async runtime: {e}"),
     })?;
 
-    rt.block_on(handle_seed_async(output_name, with_seeds))
+    rt.block_on(handle_seed_async_with_options(output_name, options))
 }
 
 /// Handle the seed command asynchronously with real database cloning
-#[allow(clippy::too_many_lines)] // Main async function with complex workflow
 pub async fn handle_seed_async(output_name: &str, with_seeds: bool) -> Result<()> {
+    handle_seed_async_with_options(
+        output_name,
+        &SeedOptions {
+            with_seeds,
+            ..SeedOptions::default()
+        },
+    )
+    .await
+}
+
+/// Handle the seed command asynchronously for an optional environment
+///
+/// Every environment/seed combination is built into its own template
+/// (see [`TemplateManager::variant_template_name`]) with its own change
+/// detection metadata, so schema-only and seeded databases never share a template.
+///
+/// With `count > 1` the databases `<output>_1..N` are cloned concurrently.
+#[allow(clippy::too_many_lines)] // Main async function with complex workflow
+pub async fn handle_seed_async_with_options(
+    output_name: &str,
+    options: &SeedOptions,
+) -> Result<()> {
     let start = Instant::now();
+    let with_seeds = options.with_seeds;
+    let env_name = options.environment.as_deref();
+
+    if options.count == 0 {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: "--count must be at least 1".to_string(),
+        });
+    }
 
     // Try to load config from current directory
     let config_path = std::env::current_dir()?.join("dbfast.toml");
@@ -35,40 +110,58 @@ pub async fn handle_seed_async(output_name: &str, with_seeds: bool) -> Result<()
         Config::from_file(&config_path).map_err(|e| DbFastError::ConfigCreationFailed {
             message: format!("Failed to load config: {e}"),
         })?;
+    let performance = config.performance.with_overrides(&options.performance);
+    if let Some(name) = env_name {
+        config
+            .environment(name)
+            .map_err(|message| DbFastError::ConfigCreationFailed { message })?;
+    }
+
+    let template_name = TemplateManager::variant_template_name(
+        &config.database.template_name,
+        env_name,
+        with_seeds,
+    )
+    .map_err(|e| DbFastError::ConfigCreationFailed {
+        message: e.to_string(),
+    })?;
 
     println!("🚀 Starting database creation...");
     println!("📊 Output database: {output_name}");
-    println!("📋 Template: {}", config.database.template_name);
+    println!("📋 Template: {template_name}");
+    println!(
+        "🌍 Environment: {}",
+        env_name.unwrap_or("(none, environment-specific files are skipped)")
+    );
     println!("🌱 With seeds: {with_seeds}");
     println!("📁 Repository: {}", config.repository.path);
 
     // Step 1: Create database connection pool
     println!("🔌 Connecting to PostgreSQL...");
-    let pool = DatabasePool::from_config(&config.database)
-        .await
-        .map_err(|e| DbFastError::ConfigCreationFailed {
-            message: format!("Failed to connect to database: {e}"),
-        })?;
+    let pool =
+        DatabasePool::from_config_with_pool_config(&config.database, performance.pool_config())
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to connect to database: {e}"),
+            })?;
 
     // Step 2: Smart template creation with change detection
     let repo_path = PathBuf::from(&config.repository.path);
-    println!("🔍 Scanning for SQL files and checking template state...");
+    println!("🔍 Discovering SQL files and checking template state...");
 
     let template_manager = TemplateManager::new_with_change_detection(
-        pool.clone(),
+        pool.clone()
+            .with_variables(config.environment_variables(env_name)),
         config.database.clone(),
         repo_path.clone(),
-    );
+    )
+    .with_statement_profiling(options.profile_statements)
+    .with_build_timeout(performance.build_timeout());
 
-    // Scan for SQL files
-    let scanner = FileScanner::new(&repo_path);
-    let scanned_files = scanner
-        .scan()
-        .map_err(|e| DbFastError::ConfigCreationFailed {
-            message: format!("Failed to scan SQL files: {e}"),
-        })?;
+    let layers = discover_template_layers(&config, &repo_path, env_name, with_seeds).await?;
+    let file_count: usize = layers.iter().map(|layer| layer.sql_files.len()).sum();
 
-    if scanned_files.is_empty() {
+    if file_count == 0 {
         println!(
             "⚠️  No SQL files found in repository path: {}",
             repo_path.display()
@@ -78,15 +171,20 @@ pub async fn handle_seed_async(output_name: &str, with_seeds: bool) -> Result<()
         });
     }
 
-    println!("📄 Found {} SQL files", scanned_files.len());
-
-    // Convert scanned files to paths for template creation
-    let sql_file_paths: Vec<PathBuf> = scanned_files.into_iter().map(|f| f.path).collect();
+    println!(
+        "📄 Found {file_count} SQL files in {} layer(s): {}",
+        layers.len(),
+        layers
+            .iter()
+            .map(|layer| layer.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" → ")
+    );
 
     // Smart template creation - only rebuilds if needed
     let template_start = Instant::now();
     let template_was_created = template_manager
-        .smart_create_template(&config.database.template_name, &sql_file_paths)
+        .smart_create_layered_template(&template_name, &layers)
         .await
         .map_err(|e| DbFastError::ConfigCreationFailed {
             message: format!("Failed to create/update template: {e}"),
@@ -94,37 +192,91 @@ pub async fn handle_seed_async(output_name: &str, with_seeds: bool) -> Result<()
 
     let template_duration = template_start.elapsed();
 
+    // In fingerprint mode the physical template name differs from the logical one
+    let template_name = template_manager
+        .layer_database_names(&template_name, &layers)
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to resolve template name: {e}"),
+        })?
+        .pop()
+        .unwrap_or(template_name);
+
     if template_was_created {
         println!(
-            "✅ Template '{}' created/updated in {}ms",
-            config.database.template_name,
+            "✅ Template '{template_name}' created/updated in {}ms",
             template_duration.as_millis()
         );
     } else {
         println!(
-            "⏩ Template '{}' is up to date ({}ms check)",
-            config.database.template_name,
+            "⏩ Template '{template_name}' is up to date ({}ms check)",
             template_duration.as_millis()
         );
     }
 
-    // Step 3: Create CloneManager and clone database from template
-    println!("⚡ Cloning database from template...");
-    let clone_manager = CloneManager::new(pool);
-
+    // Step 3: Create CloneManager and clone database(s) from template
+    let clone_manager = CloneManager::new_with_config(
+        pool,
+        CloneConfig {
+            ttl: options.ttl,
+            labels: options.labels.clone(),
+            ..performance.clone_config()
+        },
+    );
     let clone_start = Instant::now();
-    clone_manager
-        .clone_database(&config.database.template_name, output_name)
-        .await
-        .map_err(|e| DbFastError::ConfigCreationFailed {
-            message: format!("Failed to clone database: {e}"),
-        })?;
+
+    if options.count == 1 {
+        println!("⚡ Cloning database from template...");
+        clone_manager
+            .clone_database(&template_name, output_name)
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to clone database: {e}"),
+            })?;
+    } else {
+        let clone_names = batch_clone_names(output_name, options.count);
+        println!(
+            "⚡ Cloning {} databases from template...",
+            clone_names.len()
+        );
+        let reports = clone_manager
+            .clone_databases(&template_name, &clone_names)
+            .await;
+        print_clone_summary(&reports);
+
+        let failures: Vec<String> = reports
+            .iter()
+            .filter_map(|report| {
+                report
+                    .result
+                    .as_ref()
+                    .err()
+                    .map(|e| format!("{}: {e}", report.clone_name))
+            })
+            .collect();
+        if !failures.is_empty() {
+            return Err(DbFastError::ConfigCreationFailed {
+                message: format!(
+                    "Failed to clone {} of {} databases: {}",
+                    failures.len(),
+                    reports.len(),
+                    failures.join("; ")
+                ),
+            });
+        }
+    }
 
     let clone_duration = clone_start.elapsed();
     let total_duration = start.elapsed();
 
     // Step 4: Report success with performance metrics
-    println!("✅ Database '{output_name}' created successfully!");
+    if options.count == 1 {
+        println!("✅ Database '{output_name}' created successfully!");
+    } else {
+        println!(
+            "✅ Databases '{output_name}_1'..'{output_name}_{}' created successfully!",
+            options.count
+        );
+    }
     if template_was_created {
         println!("🏗️  Template creation: {}ms", template_duration.as_millis());
     } else {
@@ -150,3 +302,130 @@ pub async fn handle_seed_async(output_name: &str, with_seeds: bool) -> Result<()
 
     Ok(())
 }
+
+/// Names of the databases created by `seed --count N`: `<output>_1..N`
+#[must_use]
+pub fn batch_clone_names(output_name: &str, count: usize) -> Vec<String> {
+    (1..=count).map(|i| format!("{output_name}_{i}")).collect()
+}
+
+/// Print per-clone timings for a batch
+fn print_clone_summary(reports: &[CloneReport]) {
+    println!("📊 Clone summary:");
+    for report in reports {
+        let status = if report.result.is_ok() { "✅" } else { "❌" };
+        let retries = if report.attempts > 1 {
+            format!(" ({} attempts)", report.attempts)
+        } else {
+            String::new()
+        };
+        println!(
+            "   {status} {:<30} {:>6}ms{retries}",
+            report.clone_name,
+            report.duration.as_millis()
+        );
+    }
+}
+
+/// Make sure the template for an environment/seed combination is up to date
+///
+/// Used by callers that need a template without the seed command's progress output.
+///
+/// # Returns
+/// Physical name of the template database to clone, and whether it was (re)built
+pub(crate) async fn ensure_template(
+    config: &Config,
+    admin_pool: &DatabasePool,
+    repo_path: &Path,
+    env_name: Option<&str>,
+    with_seeds: bool,
+) -> Result<(String, bool)> {
+    let template_name = TemplateManager::variant_template_name(
+        &config.database.template_name,
+        env_name,
+        with_seeds,
+    )
+    .map_err(|e| DbFastError::ConfigCreationFailed {
+        message: e.to_string(),
+    })?;
+
+    let layers = discover_template_layers(config, repo_path, env_name, with_seeds).await?;
+    if layers.iter().all(|layer| layer.sql_files.is_empty()) {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: format!("No SQL files found in {}", repo_path.display()),
+        });
+    }
+
+    let template_manager = TemplateManager::new_with_change_detection(
+        admin_pool
+            .clone()
+            .with_variables(config.environment_variables(env_name)),
+        config.database.clone(),
+        repo_path.to_path_buf(),
+    )
+    .with_build_timeout(config.performance.build_timeout());
+    let rebuilt = template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to create/update template: {e}"),
+        })?;
+
+    let template = template_manager
+        .layer_database_names(&template_name, &layers)
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to resolve template name: {e}"),
+        })?
+        .pop()
+        .unwrap_or(template_name);
+
+    Ok((template, rebuilt))
+}
+
+/// Discover the template layers for an environment/seed combination
+///
+/// Configured environments are filtered with their `[environments.<name>]` rules,
+/// and other names are rejected (see [`Config::environment`]). Without any
+/// configured environments, a name selects files by directory naming conventions
+/// (e.g. `2_seed_dev/`). Seed directories are dropped unless `with_seeds` is set.
+pub(crate) async fn discover_template_layers(
+    config: &Config,
+    repo_path: &Path,
+    env_name: Option<&str>,
+    with_seeds: bool,
+) -> Result<Vec<TemplateLayer>> {
+    let repository =
+        SqlRepository::new(repo_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to open SQL repository: {e}"),
+        })?;
+
+    let discovered = match env_name {
+        Some(name) => match config
+            .environment(name)
+            .map_err(|message| DbFastError::ConfigCreationFailed { message })?
+        {
+            Some(environment) => {
+                repository
+                    .discover_sql_files_for_environment(&environment.to_environment_config(name))
+                    .await
+            }
+            None => {
+                println!(
+                    "⚠️  No [environments] configured, selecting files for '{name}' by directory names"
+                );
+                repository.discover_sql_files(&[name]).await
+            }
+        },
+        None => repository.discover_sql_files(&[]).await,
+    }
+    .map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to discover SQL files: {e}"),
+    })?;
+
+    let sql_files: Vec<PathBuf> = discovered
+        .into_iter()
+        .filter(|file| with_seeds || !repository.is_seed_file(file))
+        .collect();
+
+    Ok(TemplateLayer::group_files(&repository, &sql_files))
+}
@@ -0,0 +1,88 @@
+use crate::config::Config;
+use crate::error::{DbFastError, Result};
+use crate::server::{LeaseServer, ServeOptions};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+/// Where `dbfast serve` listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    /// Unix socket path
+    Socket(PathBuf),
+    /// Port on 127.0.0.1 for HTTP
+    Http(u16),
+}
+
+#[allow(clippy::disallowed_methods)]
+/// Handle the serve command synchronously (wrapper for async implementation)
+pub fn handle_serve(listen: Option<Listen>, options: ServeOptions) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to create async runtime: {e}"),
+    })?;
+
+    let current_dir = std::env::current_dir()?;
+    let listen =
+        listen.unwrap_or_else(|| Listen::Socket(current_dir.join(".dbfast").join("dbfast.sock")));
+    rt.block_on(handle_serve_in_dir(&current_dir, listen, options))
+}
+
+/// Run the clone-leasing server until interrupted, then drop all its databases
+pub async fn handle_serve_in_dir(dir: &Path, listen: Listen, options: ServeOptions) -> Result<()> {
+    let config_path = dir.join("dbfast.toml");
+    if !config_path.exists() {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: "No dbfast.toml config file found. Run 'dbfast init' first.".to_string(),
+        });
+    }
+
+    let config =
+        Config::from_file(&config_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to load config: {e}"),
+        })?;
+    let repo_path = dir.join(&config.repository.path);
+
+    let warm = options.warm_per_template;
+    let lease_ttl = options.lease_ttl;
+    let server = LeaseServer::new(config, repo_path, options).await?;
+    let maintenance = server.spawn_maintenance();
+
+    let serving = async {
+        match &listen {
+            #[cfg(unix)]
+            Listen::Socket(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                println!("🛰️  Serving databases on {}", path.display());
+                server.serve_unix(path).await
+            }
+            #[cfg(not(unix))]
+            Listen::Socket(_) => Err(DbFastError::ConfigCreationFailed {
+                message: "Unix sockets are not supported on this platform, use --http".to_string(),
+            }),
+            Listen::Http(port) => {
+                let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, *port));
+                println!("🛰️  Serving databases on http://{addr}");
+                server.serve_http(addr).await
+            }
+        }
+    };
+    println!(
+        "🔥 Keeping {warm} database(s) warm per template, leases expire after {}s without a heartbeat",
+        lease_ttl.as_secs()
+    );
+
+    let result = tokio::select! {
+        result = serving => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+
+    maintenance.abort();
+    let dropped = server.shutdown().await;
+    println!("🛑 Stopped, dropped {dropped} database(s)");
+    if let Listen::Socket(path) = &listen {
+        let _ = std::fs::remove_file(path);
+    }
+
+    result
+}
@@ -60,11 +60,14 @@ pub fn handle_status_in_dir(dir: &Path) -> Result<()> {
         })?;
 
     println!("\n📋 Configuration Details:");
-    println!(
-        "   Database Host: {}:{}",
-        config.database.host, config.database.port
-    );
-    println!("   Database User: {}", config.database.user);
+    match config.database.resolve() {
+        Ok(connection) => {
+            println!("   Database Host: {}", connection.address());
+            println!("   Database User: {}", connection.user);
+            println!("   Admin Database: {}", connection.admin_database);
+        }
+        Err(e) => println!("   Database: ❌ {e}"),
+    }
     println!("   Template Name: {}", config.database.template_name);
     println!(
         "   Repository: {} ({})",
@@ -170,11 +173,13 @@ fn load_config(config_path: &Path) -> Result<Config> {
 fn display_template_section(config: &Config) {
     println!("Template: {}", config.database.template_name);
     println!("  Status: ✅ Ready");
-    println!(
-        "  Database: {}:{}",
-        config.database.host, config.database.port
-    );
-    println!("  User: {}", config.database.user);
+    match config.database.resolve() {
+        Ok(connection) => {
+            println!("  Database: {}", connection.address());
+            println!("  User: {}", connection.user);
+        }
+        Err(e) => println!("  Database: ❌ {e}"),
+    }
     println!();
 }
 
@@ -0,0 +1,189 @@
+use crate::commands::seed::discover_template_layers;
+use crate::config::{Config, PerformanceOverrides};
+use crate::database::DatabasePool;
+use crate::error::{DbFastError, Result};
+use crate::template::TemplateManager;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[allow(clippy::disallowed_methods)]
+/// Handle the `template build` command synchronously (wrapper for async implementation)
+pub fn handle_template_build(
+    env_name: Option<&str>,
+    with_seeds: bool,
+    profile_statements: bool,
+    performance: &PerformanceOverrides,
+) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to create async runtime: {e}"),
+    })?;
+
+    let current_dir = std::env::current_dir()?;
+    rt.block_on(handle_template_build_in_dir(
+        &current_dir,
+        env_name,
+        with_seeds,
+        profile_statements,
+        performance,
+    ))
+}
+
+/// Build the template `dbfast seed` would clone from, if it is missing or out of date
+///
+/// A build prints its slowest files (and statements, with `profile_statements`)
+/// and stores a build report under the repository's `.dbfast/builds/`. It is
+/// cancelled after `build_timeout_ms` of `[performance]`, or of `performance`.
+pub async fn handle_template_build_in_dir(
+    dir: &Path,
+    env_name: Option<&str>,
+    with_seeds: bool,
+    profile_statements: bool,
+    performance: &PerformanceOverrides,
+) -> Result<()> {
+    let config_path = dir.join("dbfast.toml");
+    if !config_path.exists() {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: "No dbfast.toml config file found. Run 'dbfast init' first.".to_string(),
+        });
+    }
+
+    let config =
+        Config::from_file(&config_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to load config: {e}"),
+        })?;
+
+    let repo_path = dir.join(&config.repository.path);
+    let template_name = TemplateManager::variant_template_name(
+        &config.database.template_name,
+        env_name,
+        with_seeds,
+    )
+    .map_err(|e| DbFastError::ConfigCreationFailed {
+        message: e.to_string(),
+    })?;
+    let layers = discover_template_layers(&config, &repo_path, env_name, with_seeds).await?;
+    if layers.iter().all(|layer| layer.sql_files.is_empty()) {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: format!("No SQL files found in {}", repo_path.display()),
+        });
+    }
+
+    let performance = config.performance.with_overrides(performance);
+    let pool =
+        DatabasePool::from_config_with_pool_config(&config.database, performance.pool_config())
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to connect to database: {e}"),
+            })?;
+    let template_manager = TemplateManager::new_with_change_detection(
+        pool.with_variables(config.environment_variables(env_name)),
+        config.database.clone(),
+        repo_path,
+    )
+    .with_statement_profiling(profile_statements)
+    .with_build_timeout(performance.build_timeout());
+
+    println!("🏗️  Building template: {template_name}");
+    let start = Instant::now();
+    let rebuilt = template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to create/update template: {e}"),
+        })?;
+
+    if rebuilt {
+        println!(
+            "✅ Template '{template_name}' built in {}ms",
+            start.elapsed().as_millis()
+        );
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::disallowed_methods)]
+/// Handle the `template gc` command synchronously (wrapper for async implementation)
+pub fn handle_template_gc(
+    older_than_days: Option<u64>,
+    keep: Option<usize>,
+    dry_run: bool,
+) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| DbFastError::ConfigCreationFailed {
+        message: format!("Failed to create async runtime: {e}"),
+    })?;
+
+    let current_dir = std::env::current_dir()?;
+    rt.block_on(handle_template_gc_in_dir(
+        &current_dir,
+        older_than_days,
+        keep,
+        dry_run,
+    ))
+}
+
+/// Drop fingerprinted templates unused for `older_than_days` days or beyond the `keep` limit
+pub async fn handle_template_gc_in_dir(
+    dir: &Path,
+    older_than_days: Option<u64>,
+    keep: Option<usize>,
+    dry_run: bool,
+) -> Result<()> {
+    let config_path = dir.join("dbfast.toml");
+    if !config_path.exists() {
+        return Err(DbFastError::ConfigCreationFailed {
+            message: "No dbfast.toml config file found. Run 'dbfast init' first.".to_string(),
+        });
+    }
+
+    let config =
+        Config::from_file(&config_path).map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to load config: {e}"),
+        })?;
+
+    let max_age = older_than_days
+        .map(|days| {
+            i64::try_from(days)
+                .ok()
+                .and_then(chrono::Duration::try_days)
+                .ok_or_else(|| DbFastError::ConfigCreationFailed {
+                    message: format!("Invalid age: {days} days"),
+                })
+        })
+        .transpose()?;
+
+    let pool = DatabasePool::from_config(&config.database)
+        .await
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to connect to database: {e}"),
+        })?;
+
+    let template_manager = TemplateManager::new_with_change_detection(
+        pool,
+        config.database.clone(),
+        PathBuf::from(&config.repository.path),
+    );
+
+    let databases = template_manager
+        .gc_fingerprinted_templates(max_age, keep, dry_run)
+        .await
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to collect templates: {e}"),
+        })?;
+
+    if databases.is_empty() {
+        println!("✅ No fingerprinted templates to collect");
+    } else if dry_run {
+        println!("🔍 Would drop {} database(s):", databases.len());
+        for database in &databases {
+            println!("   - {database}");
+        }
+    } else {
+        println!("🧹 Dropped {} database(s):", databases.len());
+        for database in &databases {
+            println!("   - {database}");
+        }
+    }
+
+    Ok(())
+}
@@ -8,10 +8,12 @@
 //!
 //! ```toml
 //! [database]
-//! host = "localhost"
+//! host = "localhost"  # or a socket directory; unset settings follow PGHOST etc.
 //! port = 5432
 //! user = "postgres"
-//! password_env = "DB_PASSWORD"
+//! admin_database = "postgres"  # or url = "postgresql://...", service = "..."
+//! password_env = "DB_PASSWORD"  # or password_file = "...", or password_command = "..."
+//! sslmode = "require"  # disable, prefer (default), require, verify-ca or verify-full
 //! template_name = "my_app_template"
 //!
 //! [repository]
@@ -21,16 +23,31 @@
 //! [environments.development]
 //! filter_patterns = ["dev_*", "test_*"]
 //!
+//! [environments.development.variables]
+//! app_role = "app_dev"
+//! tenant_count = 3
+//!
 //! [remotes.production]
 //! url = "postgresql://user@prod-host:5432/database"
 //! env = "production"
+//!
+//! [performance]
+//! connection_pool_size = 8
+//! build_timeout_ms = 300000
 //! ```
 
+use crate::clone::CloneConfig;
+use crate::conninfo::{self, ConnInfoError, ResolvedConnection};
+use crate::credentials::PasswordSource;
+use crate::database::PoolConfig;
+use crate::environment::EnvironmentConfig;
 use crate::remote::RemoteConfig;
+use crate::tls::TlsConfig;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 
 /// Errors that can occur during configuration loading
@@ -58,25 +75,95 @@ pub struct Config {
     /// Remote database configurations
     #[serde(default)]
     pub remotes: HashMap<String, RemoteConfig>,
+    /// Pool sizes, concurrency and timeouts
+    #[serde(default)]
+    pub performance: PerformanceConfig,
 }
 
 /// Database connection configuration
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DatabaseConfig {
-    /// Database host
-    pub host: String,
+    /// Database host, or a Unix socket directory such as `/var/run/postgresql`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
     /// Database port
-    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
     /// Database user
-    pub user: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// `postgresql://` URL or key/value conninfo string for the settings not given above
+    #[serde(default, alias = "conninfo", skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Service from `pg_service.conf` for the settings not given otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Database connected to for creating and dropping templates and clones
+    /// Default: postgres
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_database: Option<String>,
     /// Environment variable containing the password
     pub password_env: Option<String>,
+    /// File containing the password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<PathBuf>,
+    /// Shell command printing the password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_command: Option<String>,
     /// Template database name
     pub template_name: String,
     /// Enable advanced multi-statement SQL parsing for `PostgreSQL` functions
+    /// Required for environment variables to be substituted
     /// Default: true
     #[serde(default = "default_allow_multi_statement")]
     pub allow_multi_statement: bool,
+    /// Seconds to wait for another process building the same template
+    /// Default: 300
+    #[serde(default = "default_template_lock_timeout_secs")]
+    pub template_lock_timeout_secs: u64,
+    /// Include a fingerprint of the SQL files in the physical template name
+    /// (e.g. `myapp_template_3fa9c1`) so templates for different branches coexist
+    /// Default: false
+    #[serde(default)]
+    pub fingerprint_templates: bool,
+    /// How multi-statement SQL files are executed
+    /// Default: native
+    #[serde(default)]
+    pub sql_executor: SqlExecutor,
+    /// `sslmode`, `sslrootcert`, `sslcert` and `sslkey`, see [`crate::tls`]
+    #[serde(flatten)]
+    pub tls: TlsConfig,
+}
+
+impl DatabaseConfig {
+    /// Where the password comes from, see [`crate::credentials`]
+    #[must_use]
+    pub fn credentials(&self) -> PasswordSource<'_> {
+        PasswordSource {
+            env: self.password_env.as_deref(),
+            file: self.password_file.as_deref(),
+            command: self.password_command.as_deref(),
+        }
+    }
+
+    /// Where to connect, resolved like libpq, see [`crate::conninfo`]
+    ///
+    /// # Errors
+    /// Returns [`ConnInfoError`] if the settings are invalid or incomplete.
+    pub fn resolve(&self) -> Result<ResolvedConnection, ConnInfoError> {
+        conninfo::resolve(self, &|name| std::env::var(name).ok())
+    }
+}
+
+/// How multi-statement SQL content is executed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlExecutor {
+    /// Run each statement over the existing connection in one transaction
+    #[default]
+    Native,
+    /// Pipe multi-statement content through the `psql` client (must be on PATH)
+    Psql,
 }
 
 /// Default value for `allow_multi_statement`
@@ -84,6 +171,109 @@ const fn default_allow_multi_statement() -> bool {
     true
 }
 
+/// Default value for `template_lock_timeout_secs`
+const fn default_template_lock_timeout_secs() -> u64 {
+    300
+}
+
+/// Pool sizes, concurrency and timeouts
+///
+/// Every setting can be overridden per command, see [`PerformanceOverrides`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PerformanceConfig {
+    /// Clones created at the same time by `dbfast seed --count` and `dbfast serve`
+    /// Default: 5
+    pub max_concurrent_clones: usize,
+    /// Connections per database connection pool
+    /// Default: 10
+    pub connection_pool_size: u32,
+    /// Milliseconds to wait for a connection to open or a pooled one to free up
+    /// Default: 30000
+    pub connection_timeout_ms: u64,
+    /// Milliseconds a clone may take, including retries while its template is busy
+    /// Default: 30000
+    pub clone_timeout_ms: u64,
+    /// Milliseconds a template build may take before it is cancelled
+    /// Default: unlimited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_timeout_ms: Option<u64>,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_clones: 5,
+            connection_pool_size: 10,
+            connection_timeout_ms: 30_000,
+            clone_timeout_ms: 30_000,
+            build_timeout_ms: None,
+        }
+    }
+}
+
+impl PerformanceConfig {
+    /// These settings with the ones given in `overrides` replaced
+    #[must_use]
+    pub fn with_overrides(self, overrides: &PerformanceOverrides) -> Self {
+        Self {
+            max_concurrent_clones: overrides
+                .max_concurrent_clones
+                .unwrap_or(self.max_concurrent_clones),
+            connection_pool_size: overrides
+                .connection_pool_size
+                .unwrap_or(self.connection_pool_size),
+            connection_timeout_ms: overrides
+                .connection_timeout_ms
+                .unwrap_or(self.connection_timeout_ms),
+            clone_timeout_ms: overrides.clone_timeout_ms.unwrap_or(self.clone_timeout_ms),
+            build_timeout_ms: overrides.build_timeout_ms.or(self.build_timeout_ms),
+        }
+    }
+
+    /// Size and connection timeout of database connection pools
+    #[must_use]
+    pub const fn pool_config(&self) -> PoolConfig {
+        PoolConfig {
+            max_size: self.connection_pool_size,
+            connection_timeout: Duration::from_millis(self.connection_timeout_ms),
+        }
+    }
+
+    /// Clone settings for dbfast's own commands, which register their clones;
+    /// the others are left at their defaults
+    #[must_use]
+    pub fn clone_config(&self) -> CloneConfig {
+        CloneConfig {
+            max_concurrent_clones: self.max_concurrent_clones,
+            clone_timeout: Duration::from_millis(self.clone_timeout_ms),
+            register_clones: true,
+            ..CloneConfig::default()
+        }
+    }
+
+    /// Time a template build may take, if limited
+    #[must_use]
+    pub fn build_timeout(&self) -> Option<Duration> {
+        self.build_timeout_ms.map(Duration::from_millis)
+    }
+}
+
+/// `[performance]` settings given on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerformanceOverrides {
+    /// Overrides `max_concurrent_clones`
+    pub max_concurrent_clones: Option<usize>,
+    /// Overrides `connection_pool_size`
+    pub connection_pool_size: Option<u32>,
+    /// Overrides `connection_timeout_ms`
+    pub connection_timeout_ms: Option<u64>,
+    /// Overrides `clone_timeout_ms`
+    pub clone_timeout_ms: Option<u64>,
+    /// Overrides `build_timeout_ms`
+    pub build_timeout_ms: Option<u64>,
+}
+
 /// Repository configuration
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct RepositoryConfig {
@@ -103,9 +293,117 @@ pub struct Environment {
     /// Directories to exclude
     #[serde(default)]
     pub exclude_directories: Vec<String>,
+    /// Variables substituted into SQL files as `:'name'`, `:"name"` or `:name`
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        deserialize_with = "deserialize_variables"
+    )]
+    pub variables: BTreeMap<String, String>,
+}
+
+/// A TOML scalar accepted as a variable value
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VariableValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+/// Deserialize `[environments.<name>.variables]`, checking names and stringifying values
+fn deserialize_variables<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = BTreeMap::<String, VariableValue>::deserialize(deserializer)?;
+    raw.into_iter()
+        .map(|(name, value)| {
+            let valid = name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(serde::de::Error::custom(format!(
+                    "invalid variable name `{name}`: use letters, digits and underscores"
+                )));
+            }
+            let value = match value {
+                VariableValue::String(value) => value,
+                VariableValue::Integer(value) => value.to_string(),
+                VariableValue::Float(value) => value.to_string(),
+                VariableValue::Boolean(value) => value.to_string(),
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+impl Environment {
+    /// Convert this configuration entry into an [`EnvironmentConfig`] used for file filtering
+    ///
+    /// An empty `include_directories` list means "no directory restriction".
+    ///
+    /// # Arguments
+    /// * `name` - Name of the environment (the key under `[environments]`)
+    #[must_use]
+    pub fn to_environment_config(&self, name: &str) -> EnvironmentConfig {
+        EnvironmentConfig {
+            name: name.to_string(),
+            include_directories: if self.include_directories.is_empty() {
+                None
+            } else {
+                Some(self.include_directories.clone())
+            },
+            exclude_directories: if self.exclude_directories.is_empty() {
+                None
+            } else {
+                Some(self.exclude_directories.clone())
+            },
+            include_files: None,
+            exclude_files: None,
+        }
+    }
 }
 
 impl Config {
+    /// Look up the environment selected with `--env`
+    ///
+    /// An unknown name is an error when any environment is configured, so a typo
+    /// does not quietly select files by directory naming conventions instead.
+    ///
+    /// # Returns
+    /// The environment, or `None` when no environments are configured at all
+    ///
+    /// # Errors
+    /// Returns a message listing the configured environments for an unknown name
+    pub fn environment(&self, name: &str) -> Result<Option<&Environment>, String> {
+        if let Some(environment) = self.environments.get(name) {
+            return Ok(Some(environment));
+        }
+        if self.environments.is_empty() {
+            return Ok(None);
+        }
+
+        let mut names: Vec<&str> = self.environments.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        Err(format!(
+            "Unknown environment '{name}'; configured environments: {}",
+            names.join(", ")
+        ))
+    }
+
+    /// Variables of the environment `env_name`, empty for unknown environments
+    #[must_use]
+    pub fn environment_variables(&self, env_name: Option<&str>) -> BTreeMap<String, String> {
+        env_name
+            .and_then(|name| self.environments.get(name))
+            .map(|environment| environment.variables.clone())
+            .unwrap_or_default()
+    }
+
     /// Create a new configuration with sensible default values
     ///
     /// Sets up a default configuration suitable for most `PostgreSQL` database
@@ -133,6 +431,7 @@ impl Config {
                     "2_seed_backend".to_string(),
                 ],
                 exclude_directories: vec![],
+                variables: BTreeMap::new(),
             },
         );
         environments.insert(
@@ -143,17 +442,27 @@ impl Config {
                     "1_seed_common".to_string(),
                     "2_seed_backend".to_string(),
                 ],
+                variables: BTreeMap::new(),
             },
         );
 
         Self {
             database: DatabaseConfig {
-                host: "localhost".to_string(),
-                port: 5432,
-                user: "postgres".to_string(),
+                host: Some("localhost".to_string()),
+                port: Some(5432),
+                user: Some("postgres".to_string()),
+                url: None,
+                service: None,
+                admin_database: None,
                 password_env: Some("POSTGRES_PASSWORD".to_string()),
+                password_file: None,
+                password_command: None,
                 template_name: template_name.to_string(),
                 allow_multi_statement: true,
+                template_lock_timeout_secs: 300,
+                fingerprint_templates: false,
+                sql_executor: SqlExecutor::Native,
+                tls: TlsConfig::default(),
             },
             repository: RepositoryConfig {
                 path: repo_path.to_string(),
@@ -161,6 +470,7 @@ impl Config {
             },
             environments,
             remotes: HashMap::new(),
+            performance: PerformanceConfig::default(),
         }
     }
 
@@ -0,0 +1,604 @@
+//! # Connection Resolution
+//!
+//! Resolves where `[database]` connects the way libpq does, so `dbfast` works
+//! wherever `psql` works without extra configuration. Each parameter (host,
+//! port, user, admin database and the TLS settings) is taken from the first of:
+//!
+//! 1. the `[database]` setting itself (`host`, `port`, `user`, `admin_database`,
+//!    `sslmode`, ...)
+//! 2. `url` (alias `conninfo`): a `postgresql://` URL or a key/value conninfo
+//!    string like `"host=/var/run/postgresql dbname=postgres"`
+//! 3. the service named by `service`, the conninfo or `PGSERVICE`, from
+//!    `PGSERVICEFILE` (or `~/.pg_service.conf`), then
+//!    `PGSYSCONFDIR/pg_service.conf`
+//! 4. `PGHOST`, `PGPORT`, `PGUSER`, `PGDATABASE`, `PGSSLMODE`,
+//!    `PGSSLROOTCERT`, `PGSSLCERT` and `PGSSLKEY`
+//! 5. defaults: the first of `/var/run/postgresql` and `/tmp` with a server
+//!    socket, else `localhost`; port 5432; the operating system user; and the
+//!    `postgres` database
+//!
+//! A host starting with `/` is a Unix socket directory, which allows peer
+//! authentication. The admin database is the one `dbfast` connects to for
+//! creating and dropping templates and clones.
+//!
+//! ```rust
+//! use dbfast::conninfo::ConnInfo;
+//!
+//! let url = ConnInfo::parse("postgresql://app@%2Fvar%2Frun%2Fpostgresql:5433/maintenance").unwrap();
+//! let conninfo = ConnInfo::parse("host=/var/run/postgresql port=5433 user=app dbname=maintenance").unwrap();
+//! assert_eq!(url, conninfo);
+//! assert_eq!(url.host.as_deref(), Some("/var/run/postgresql"));
+//! ```
+
+use crate::config::DatabaseConfig;
+use crate::credentials::Password;
+use crate::tls::{TlsConfig, TlsError};
+use std::path::PathBuf;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Port used when none is configured
+pub const DEFAULT_PORT: u16 = 5432;
+
+/// Admin database used when none is configured
+pub const DEFAULT_ADMIN_DATABASE: &str = "postgres";
+
+/// Socket directories tried, in order, when no host is configured
+#[cfg(unix)]
+const DEFAULT_SOCKET_DIRECTORIES: [&str; 2] = ["/var/run/postgresql", "/tmp"];
+
+/// libpq options accepted in a conninfo but not used by `dbfast`
+const IGNORED_OPTIONS: [&str; 9] = [
+    "application_name",
+    "fallback_application_name",
+    "connect_timeout",
+    "client_encoding",
+    "options",
+    "keepalives",
+    "target_session_attrs",
+    "channel_binding",
+    "gssencmode",
+];
+
+/// Connection settings that cannot be resolved
+#[derive(Debug, Error)]
+pub enum ConnInfoError {
+    /// A URL or conninfo string is malformed
+    #[error("Invalid connection string: {0}")]
+    Parse(String),
+
+    /// A conninfo names an option libpq does not know
+    #[error("Invalid connection option \"{0}\"")]
+    UnknownOption(String),
+
+    /// A port is not a number
+    #[error("Invalid port \"{0}\"")]
+    InvalidPort(String),
+
+    /// Several hosts are given; only one is supported
+    #[error("Multiple hosts are not supported: \"{0}\"")]
+    MultipleHosts(String),
+
+    /// The service is not defined in any service file
+    #[error("Definition of service \"{0}\" not found")]
+    ServiceNotFound(String),
+
+    /// A service file could not be read
+    #[error("Cannot read service file {}: {source}", path.display())]
+    ServiceFile {
+        /// The file
+        path: PathBuf,
+        /// Why it could not be read
+        source: std::io::Error,
+    },
+
+    /// No user is configured and the operating system user is unknown
+    #[error("No user configured; set user in [database] or PGUSER")]
+    NoUser,
+
+    /// The conninfo has a password and a password source is configured too
+    #[error("The connection string has a password, and password_env, password_file or password_command is set too")]
+    ConflictingPassword,
+
+    /// A TLS setting is invalid
+    #[error("{0}")]
+    Tls(#[from] TlsError),
+}
+
+/// libpq connection parameters, any of which may be unset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnInfo {
+    /// Host name, IP address or Unix socket directory
+    pub host: Option<String>,
+    /// Server port
+    pub port: Option<u16>,
+    /// User to connect as
+    pub user: Option<String>,
+    /// Password given inline
+    pub password: Option<Password>,
+    /// Database to connect to
+    pub dbname: Option<String>,
+    /// Service to read further parameters from
+    pub service: Option<String>,
+    /// `sslmode`, `sslrootcert`, `sslcert` and `sslkey`
+    pub tls: TlsConfig,
+}
+
+impl ConnInfo {
+    /// Parse a `postgres://`/`postgresql://` URL or a key/value conninfo string
+    pub fn parse(conninfo: &str) -> Result<Self, ConnInfoError> {
+        let conninfo = conninfo.trim();
+        if conninfo.starts_with("postgresql://") || conninfo.starts_with("postgres://") {
+            Self::parse_url(conninfo)
+        } else {
+            Self::parse_key_values(conninfo)
+        }
+    }
+
+    fn parse_url(conninfo: &str) -> Result<Self, ConnInfoError> {
+        let url = url::Url::parse(conninfo).map_err(|e| ConnInfoError::Parse(e.to_string()))?;
+        let mut info = Self::default();
+
+        if let Some(host) = url.host_str().filter(|host| !host.is_empty()) {
+            info.set("host", &percent_decode(host))?;
+        }
+        if let Some(port) = url.port() {
+            info.port = Some(port);
+        }
+        if !url.username().is_empty() {
+            info.set("user", &percent_decode(url.username()))?;
+        }
+        if let Some(password) = url.password() {
+            info.set("password", &percent_decode(password))?;
+        }
+        let dbname = url.path().trim_start_matches('/');
+        if !dbname.is_empty() {
+            info.set("dbname", &percent_decode(dbname))?;
+        }
+        for (key, value) in url.query_pairs() {
+            info.set(&key, &value)?;
+        }
+        Ok(info)
+    }
+
+    fn parse_key_values(conninfo: &str) -> Result<Self, ConnInfoError> {
+        let mut info = Self::default();
+        let mut chars = conninfo.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                return Ok(info);
+            }
+
+            let mut key = String::new();
+            while let Some(c) = chars.next_if(|c| *c != '=' && !c.is_whitespace()) {
+                key.push(c);
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.next() != Some('=') {
+                return Err(ConnInfoError::Parse(format!(
+                    "missing \"=\" after \"{key}\""
+                )));
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+            let mut value = String::new();
+            if chars.next_if_eq(&'\'').is_some() {
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => {
+                            return Err(ConnInfoError::Parse(format!(
+                                "unterminated quoted value for \"{key}\""
+                            )))
+                        }
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    if c == '\\' {
+                        value.extend(chars.next());
+                    } else {
+                        value.push(c);
+                    }
+                }
+            }
+
+            info.set(&key, &value)?;
+        }
+    }
+
+    /// Set the option `key` to `value`
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ConnInfoError> {
+        let value = value.to_string();
+        match key {
+            "host" => {
+                if value.contains(',') {
+                    return Err(ConnInfoError::MultipleHosts(value));
+                }
+                self.host = Some(value);
+            }
+            "port" => self.port = Some(parse_port(&value)?),
+            "user" => self.user = Some(value),
+            "password" => self.password = Some(Password::new(value)),
+            "dbname" => self.dbname = Some(value),
+            "service" => self.service = Some(value),
+            "sslmode" => self.tls.sslmode = Some(value.parse()?),
+            "sslrootcert" => self.tls.sslrootcert = Some(PathBuf::from(value)),
+            "sslcert" => self.tls.sslcert = Some(PathBuf::from(value)),
+            "sslkey" => self.tls.sslkey = Some(PathBuf::from(value)),
+            _ if IGNORED_OPTIONS.contains(&key) => {
+                warn!("Ignoring connection option \"{}\"", key);
+            }
+            _ => return Err(ConnInfoError::UnknownOption(key.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Parameters from the `PG*` environment variables, read through `env`
+    pub fn from_env(env: &impl Fn(&str) -> Option<String>) -> Result<Self, ConnInfoError> {
+        let mut info = Self::default();
+        for (variable, key) in [
+            ("PGHOST", "host"),
+            ("PGPORT", "port"),
+            ("PGUSER", "user"),
+            ("PGDATABASE", "dbname"),
+            ("PGSERVICE", "service"),
+            ("PGSSLMODE", "sslmode"),
+            ("PGSSLROOTCERT", "sslrootcert"),
+            ("PGSSLCERT", "sslcert"),
+            ("PGSSLKEY", "sslkey"),
+        ] {
+            if let Some(value) = env(variable).filter(|value| !value.is_empty()) {
+                info.set(key, &value)?;
+            }
+        }
+        Ok(info)
+    }
+
+    /// Parameters of `service`, from the first service file defining it
+    pub fn from_service(
+        service: &str,
+        env: &impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConnInfoError> {
+        for path in service_files(env) {
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(source) => return Err(ConnInfoError::ServiceFile { path, source }),
+            };
+            if let Some(options) = service_options(&contents, service) {
+                debug!("Using service {} from {}", service, path.display());
+                let mut info = Self::default();
+                for (key, value) in options {
+                    info.set(&key, &value)?;
+                }
+                return Ok(info);
+            }
+        }
+        Err(ConnInfoError::ServiceNotFound(service.to_string()))
+    }
+
+    /// These parameters, with the unset ones taken from `fallback`
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            host: self.host.or(fallback.host),
+            port: self.port.or(fallback.port),
+            user: self.user.or(fallback.user),
+            password: self.password.or(fallback.password),
+            dbname: self.dbname.or(fallback.dbname),
+            service: self.service.or(fallback.service),
+            tls: TlsConfig {
+                sslmode: self.tls.sslmode.or(fallback.tls.sslmode),
+                sslrootcert: self.tls.sslrootcert.or(fallback.tls.sslrootcert),
+                sslcert: self.tls.sslcert.or(fallback.tls.sslcert),
+                sslkey: self.tls.sslkey.or(fallback.tls.sslkey),
+            },
+        }
+    }
+}
+
+/// Where `[database]` connects, resolved by [`resolve`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedConnection {
+    /// Host name, IP address or Unix socket directory
+    pub host: String,
+    /// Server port
+    pub port: u16,
+    /// User to connect as
+    pub user: String,
+    /// Database for creating and dropping templates and clones
+    pub admin_database: String,
+    /// Password given in the conninfo, if any
+    pub password: Option<Password>,
+    /// TLS settings
+    pub tls: TlsConfig,
+}
+
+impl ResolvedConnection {
+    /// Whether the host is a Unix socket directory
+    #[must_use]
+    pub fn is_socket(&self) -> bool {
+        is_socket_directory(&self.host)
+    }
+
+    /// `host:port`, or the socket directory and port
+    #[must_use]
+    pub fn address(&self) -> String {
+        if self.is_socket() {
+            format!("{} (socket, port {})", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Resolve the connection of `config`, reading environment variables through `env`
+pub fn resolve(
+    config: &DatabaseConfig,
+    env: &impl Fn(&str) -> Option<String>,
+) -> Result<ResolvedConnection, ConnInfoError> {
+    let explicit = ConnInfo {
+        host: config.host.clone(),
+        port: config.port,
+        user: config.user.clone(),
+        password: None,
+        dbname: config.admin_database.clone(),
+        service: config.service.clone(),
+        tls: config.tls.clone(),
+    };
+    let mut info = match &config.url {
+        Some(url) => explicit.or(ConnInfo::parse(url)?),
+        None => explicit,
+    };
+
+    let environment = ConnInfo::from_env(env)?;
+    if let Some(service) = info.service.clone().or_else(|| environment.service.clone()) {
+        info = info.or(ConnInfo::from_service(&service, env)?);
+    }
+    let info = info.or(environment);
+
+    if info.password.is_some() && config.credentials().is_configured() {
+        return Err(ConnInfoError::ConflictingPassword);
+    }
+
+    let port = info.port.unwrap_or(DEFAULT_PORT);
+    Ok(ResolvedConnection {
+        host: info.host.unwrap_or_else(|| default_host(port)),
+        port,
+        user: match info.user {
+            Some(user) => user,
+            None => ["USER", "LOGNAME", "USERNAME"]
+                .iter()
+                .find_map(|variable| env(variable))
+                .ok_or(ConnInfoError::NoUser)?,
+        },
+        admin_database: info
+            .dbname
+            .unwrap_or_else(|| DEFAULT_ADMIN_DATABASE.to_string()),
+        password: info.password,
+        tls: info.tls,
+    })
+}
+
+/// Whether `host` names a Unix socket directory rather than a server
+#[must_use]
+pub fn is_socket_directory(host: &str) -> bool {
+    host.starts_with('/')
+}
+
+/// First default socket directory with a server listening on `port`, else `localhost`
+fn default_host(port: u16) -> String {
+    #[cfg(unix)]
+    {
+        let socket = format!(".s.PGSQL.{port}");
+        if let Some(directory) = DEFAULT_SOCKET_DIRECTORIES
+            .iter()
+            .find(|directory| std::path::Path::new(directory).join(&socket).exists())
+        {
+            return (*directory).to_string();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = port;
+    "localhost".to_string()
+}
+
+/// Service files in the order they are searched
+fn service_files(env: &impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(path) = env("PGSERVICEFILE") {
+        files.push(PathBuf::from(path));
+    } else {
+        #[cfg(windows)]
+        let user_file = env("APPDATA").map(|dir| {
+            PathBuf::from(dir)
+                .join("postgresql")
+                .join(".pg_service.conf")
+        });
+        #[cfg(not(windows))]
+        let user_file = env("HOME").map(|dir| PathBuf::from(dir).join(".pg_service.conf"));
+        files.extend(user_file);
+    }
+    if let Some(directory) = env("PGSYSCONFDIR") {
+        files.push(PathBuf::from(directory).join("pg_service.conf"));
+    }
+    files
+}
+
+/// `key=value` options of the `[service]` section of service file `contents`
+fn service_options(contents: &str, service: &str) -> Option<Vec<(String, String)>> {
+    let mut options = None;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if options.is_some() {
+                break;
+            }
+            if section == service {
+                options = Some(Vec::new());
+            }
+        } else if let (Some(options), Some((key, value))) = (options.as_mut(), line.split_once('='))
+        {
+            options.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    options
+}
+
+fn parse_port(value: &str) -> Result<u16, ConnInfoError> {
+    value
+        .parse()
+        .map_err(|_| ConnInfoError::InvalidPort(value.to_string()))
+}
+
+/// Decode `%XX` escapes of a URL component
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::SslMode;
+    use std::collections::HashMap;
+
+    fn config(toml: &str) -> DatabaseConfig {
+        toml::from_str(&format!("template_name = \"app_template\"\n{toml}")).unwrap()
+    }
+
+    fn env(variables: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let variables: HashMap<String, String> = variables
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect();
+        move |name| variables.get(name).cloned()
+    }
+
+    #[test]
+    fn test_parse_key_values() {
+        let info = ConnInfo::parse(
+            "host = db.internal port=6432 user='app user' password='it\\'s' sslmode=verify-ca application_name=x",
+        )
+        .unwrap();
+        assert_eq!(info.host.as_deref(), Some("db.internal"));
+        assert_eq!(info.port, Some(6432));
+        assert_eq!(info.user.as_deref(), Some("app user"));
+        assert_eq!(info.password.as_ref().map(Password::expose), Some("it's"));
+        assert_eq!(info.tls.sslmode, Some(SslMode::VerifyCa));
+
+        assert!(matches!(
+            ConnInfo::parse("host=a,b"),
+            Err(ConnInfoError::MultipleHosts(_))
+        ));
+        assert!(matches!(
+            ConnInfo::parse("hots=a"),
+            Err(ConnInfoError::UnknownOption(_))
+        ));
+        assert!(matches!(
+            ConnInfo::parse("port=abc"),
+            Err(ConnInfoError::InvalidPort(_))
+        ));
+        assert!(matches!(
+            ConnInfo::parse("host"),
+            Err(ConnInfoError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let env = env(&[
+            ("PGHOST", "env-host"),
+            ("PGPORT", "5433"),
+            ("PGUSER", "env-user"),
+            ("PGDATABASE", "env-db"),
+            ("USER", "os-user"),
+        ]);
+
+        let resolved = resolve(&config("host = \"db\""), &env).unwrap();
+        assert_eq!(resolved.host, "db");
+        assert_eq!(resolved.port, 5433);
+        assert_eq!(resolved.user, "env-user");
+        assert_eq!(resolved.admin_database, "env-db");
+
+        let resolved = resolve(
+            &config("url = \"postgres://url-user@url-host/url-db\"\nadmin_database = \"admin\""),
+            &env,
+        )
+        .unwrap();
+        assert_eq!(resolved.host, "url-host");
+        assert_eq!(resolved.user, "url-user");
+        assert_eq!(resolved.admin_database, "admin");
+
+        let resolved = resolve(&config(""), &|name: &str| {
+            (name == "USER").then(|| "os-user".to_string())
+        })
+        .unwrap();
+        assert_eq!(resolved.port, DEFAULT_PORT);
+        assert_eq!(resolved.user, "os-user");
+        assert_eq!(resolved.admin_database, DEFAULT_ADMIN_DATABASE);
+
+        let conflicting =
+            config("conninfo = \"host=db password=secret\"\npassword_env = \"DB_PASSWORD\"");
+        assert!(matches!(
+            resolve(&conflicting, &env),
+            Err(ConnInfoError::ConflictingPassword)
+        ));
+    }
+
+    #[test]
+    fn test_service_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("pg_service.conf");
+        std::fs::write(
+            &path,
+            "# services\n[other]\nhost=elsewhere\n\n[dev]\nhost=/var/run/postgresql\nport = 5433\ndbname=maintenance\n",
+        )
+        .unwrap();
+        let path = path.display().to_string();
+        let env = env(&[
+            ("PGSERVICEFILE", &path),
+            ("PGSERVICE", "dev"),
+            ("PGHOST", "env-host"),
+            ("PGUSER", "env-user"),
+        ]);
+
+        // The service overrides the environment, the configuration the service
+        let resolved = resolve(&config("port = 6000"), &env).unwrap();
+        assert_eq!(resolved.host, "/var/run/postgresql");
+        assert!(resolved.is_socket());
+        assert_eq!(resolved.port, 6000);
+        assert_eq!(resolved.user, "env-user");
+        assert_eq!(resolved.admin_database, "maintenance");
+
+        assert!(matches!(
+            resolve(&config("service = \"missing\""), &env),
+            Err(ConnInfoError::ServiceNotFound(_))
+        ));
+    }
+}
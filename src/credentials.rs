@@ -0,0 +1,426 @@
+//! # Credentials
+//!
+//! Resolves the password of a connection. `[database]` and every remote accept
+//! the same settings, at most one of which may be set:
+//!
+//! - `password_env`: environment variable holding the password
+//! - `password_file`: file holding the password; surrounding whitespace is
+//!   trimmed
+//! - `password_command`: shell command printing the password on stdout, e.g.
+//!   `"vault kv get -field=password secret/db"`
+//!
+//! Without any of them the password is looked up in the libpq password file
+//! (`PGPASSFILE`, or `~/.pgpass`) the way `psql` does, and no match means no
+//! password. A configured source that cannot produce a password is an error,
+//! never a silent fallback to an empty password; only a `password_env`
+//! variable that is set to an empty string means "no password".
+//!
+//! Resolved passwords are held in a [`Password`], which prints as `********`
+//! in `Debug` and `Display` output, and reach `psql`, `pg_dump` and
+//! `pg_restore` only through the `PGPASSWORD` variable of their own
+//! environment, see [`Password::apply_to`].
+//!
+//! ```rust
+//! use dbfast::credentials::{lookup_pgpass, PgPassTarget};
+//!
+//! let target = PgPassTarget {
+//!     host: "db.internal",
+//!     port: 5432,
+//!     database: "app_template",
+//!     user: "deploy",
+//! };
+//! let pgpass = "other:5432:*:deploy:nope\ndb.internal:*:*:deploy:s3cr\\:et\n";
+//! let password = lookup_pgpass(pgpass, &target).unwrap();
+//! assert_eq!(password.expose(), "s3cr:et");
+//! assert_eq!(password.to_string(), "********");
+//! ```
+
+use crate::conninfo::is_socket_directory;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// How a password is shown in logs and errors
+const REDACTED: &str = "********";
+
+/// A password that is never printed
+#[derive(Clone, PartialEq, Eq)]
+pub struct Password(String);
+
+impl Password {
+    /// Wrap `password`
+    #[must_use]
+    pub const fn new(password: String) -> Self {
+        Self(password)
+    }
+
+    /// The password itself, for handing it to a connection
+    #[must_use]
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Pass the password to a libpq client subprocess through its environment
+    pub fn apply_to<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        command.env("PGPASSWORD", &self.0)
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Password({REDACTED})")
+    }
+}
+
+impl fmt::Display for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// A configured password source could not produce a password
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    /// More than one password source is configured
+    #[error("Only one of password_env, password_file and password_command can be set")]
+    Conflicting,
+
+    /// `password_env` names an unset variable
+    #[error("Password environment variable {0} is not set")]
+    MissingEnv(String),
+
+    /// `password_file` cannot be read
+    #[error("Cannot read password file {path}: {source}")]
+    File {
+        /// The password file
+        path: PathBuf,
+        /// Why it cannot be read
+        source: io::Error,
+    },
+
+    /// `password_command` could not be run or failed
+    #[error("Password command `{command}` failed: {message}")]
+    Command {
+        /// The configured command
+        command: String,
+        /// How it failed
+        message: String,
+    },
+
+    /// A password file or command produced an empty password
+    #[error("{0} produced an empty password")]
+    Empty(String),
+}
+
+/// Connection a password is looked up for in the password file
+#[derive(Debug, Clone, Copy)]
+pub struct PgPassTarget<'a> {
+    /// Server host
+    pub host: &'a str,
+    /// Server port
+    pub port: u16,
+    /// Database connected to
+    pub database: &'a str,
+    /// User connecting
+    pub user: &'a str,
+}
+
+/// Password settings of a `[database]` or remote section
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PasswordSource<'a> {
+    /// `password_env`
+    pub env: Option<&'a str>,
+    /// `password_file`
+    pub file: Option<&'a Path>,
+    /// `password_command`
+    pub command: Option<&'a str>,
+}
+
+impl PasswordSource<'_> {
+    /// Short description of where the password comes from, without the secret
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match (self.env, self.file, self.command) {
+            (Some(_), None, None) => "environment variable",
+            (None, Some(_), None) => "password file",
+            (None, None, Some(_)) => "password command",
+            (None, None, None) => ".pgpass",
+            _ => "conflicting settings",
+        }
+    }
+
+    /// Whether a source other than the password file is configured
+    #[must_use]
+    pub const fn is_configured(&self) -> bool {
+        self.env.is_some() || self.file.is_some() || self.command.is_some()
+    }
+
+    /// Resolve the password for connecting to `target`
+    ///
+    /// # Errors
+    /// Returns [`CredentialError`] if more than one source is configured, or
+    /// the configured one cannot produce a non-empty password.
+    pub fn resolve(&self, target: &PgPassTarget<'_>) -> Result<Option<Password>, CredentialError> {
+        let password = match (self.env, self.file, self.command) {
+            (Some(name), None, None) => {
+                debug!("Reading password from environment variable {}", name);
+                let value = std::env::var(name)
+                    .map_err(|_| CredentialError::MissingEnv(name.to_string()))?;
+                // Set but empty is a deliberate "no password", e.g. for trust auth
+                if value.is_empty() {
+                    return Ok(None);
+                }
+                Password::new(value)
+            }
+            (None, Some(path), None) => {
+                debug!("Reading password from file {}", path.display());
+                let contents =
+                    std::fs::read_to_string(path).map_err(|source| CredentialError::File {
+                        path: path.to_path_buf(),
+                        source,
+                    })?;
+                non_empty(contents.trim().to_string(), || {
+                    format!("Password file {}", path.display())
+                })?
+            }
+            (None, None, Some(command)) => {
+                debug!("Running password command");
+                let stdout = run_password_command(command)?;
+                non_empty(stdout.trim().to_string(), || {
+                    format!("Password command `{command}`")
+                })?
+            }
+            (None, None, None) => return Ok(pgpass_password(target)),
+            _ => return Err(CredentialError::Conflicting),
+        };
+        Ok(Some(password))
+    }
+}
+
+fn non_empty(value: String, source: impl FnOnce() -> String) -> Result<Password, CredentialError> {
+    if value.is_empty() {
+        return Err(CredentialError::Empty(source()));
+    }
+    Ok(Password::new(value))
+}
+
+/// Run `command` through the platform shell and capture its stdout
+fn run_password_command(command: &str) -> Result<String, CredentialError> {
+    let error = |message: String| CredentialError::Command {
+        command: command.to_string(),
+        message,
+    };
+
+    #[cfg(windows)]
+    let output = Command::new("cmd").args(["/C", command]).output();
+    #[cfg(not(windows))]
+    let output = Command::new("sh").args(["-c", command]).output();
+
+    let output = output.map_err(|e| error(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(error(format!("{}: {}", output.status, stderr.trim())));
+    }
+    String::from_utf8(output.stdout).map_err(|_| error("output is not valid UTF-8".to_string()))
+}
+
+/// Location of the libpq password file: `PGPASSFILE`, or the platform default
+#[must_use]
+pub fn pgpass_file() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("PGPASSFILE") {
+        return Some(PathBuf::from(path));
+    }
+
+    #[cfg(windows)]
+    let path = std::env::var_os("APPDATA")
+        .map(|dir| PathBuf::from(dir).join("postgresql").join("pgpass.conf"));
+    #[cfg(not(windows))]
+    let path = std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".pgpass"));
+    path
+}
+
+/// Password for `target` from the password file, if it has a matching entry
+///
+/// Like libpq, a missing file means no password, and on Unix a file readable
+/// by group or others is ignored.
+fn pgpass_password(target: &PgPassTarget<'_>) -> Option<Password> {
+    let path = pgpass_file()?;
+    let metadata = std::fs::metadata(&path).ok()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o077 != 0 {
+            warn!(
+                "Password file {} has group or world access; permissions should be u=rw (0600) or less",
+                path.display()
+            );
+            return None;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => lookup_pgpass(&contents, target),
+        Err(e) => {
+            warn!("Cannot read password file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Password of the first entry of password file `contents` matching `target`
+///
+/// Entries are `host:port:database:user:password` lines, where `*` matches
+/// anything and `\:` or `\\` escape a colon or backslash. As in libpq, a
+/// Unix socket directory host matches entries for `localhost`.
+#[must_use]
+pub fn lookup_pgpass(contents: &str, target: &PgPassTarget<'_>) -> Option<Password> {
+    let port = target.port.to_string();
+    let host = if is_socket_directory(target.host) {
+        "localhost"
+    } else {
+        target.host
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(pgpass_fields)
+        .find(|fields| {
+            [host, &port, target.database, target.user]
+                .iter()
+                .zip(fields)
+                .all(|(value, field)| field == "*" || field == value)
+        })
+        .map(|mut fields| Password::new(fields.pop().unwrap_or_default()))
+}
+
+/// The five unescaped fields of a password file line
+fn pgpass_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => fields.last_mut()?.push(chars.next().unwrap_or('\\')),
+            ':' if fields.len() < 5 => fields.push(String::new()),
+            c => fields.last_mut()?.push(c),
+        }
+    }
+    (fields.len() == 5).then_some(fields)
+}
+
+/// `url` with its password, if it has one, replaced by `********`
+#[must_use]
+pub fn redact_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            // Only fails for URLs without a host, which have no password
+            let _ = parsed.set_password(Some(REDACTED));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: PgPassTarget<'static> = PgPassTarget {
+        host: "localhost",
+        port: 5432,
+        database: "app",
+        user: "postgres",
+    };
+
+    #[test]
+    fn test_pgpass_matching() {
+        let contents = "\
+# comment
+localhost:5433:*:postgres:wrong-port
+localhost:*:other:postgres:wrong-db
+*:*:app:postgres:first\\\\match
+*:*:*:*:fallback
+";
+        assert_eq!(
+            lookup_pgpass(contents, &TARGET).unwrap().expose(),
+            "first\\match"
+        );
+        assert!(lookup_pgpass("localhost:5432:app", &TARGET).is_none());
+        assert!(lookup_pgpass("", &TARGET).is_none());
+    }
+
+    #[test]
+    fn test_configured_sources() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("password");
+        std::fs::write(&file, "from-file\n").unwrap();
+
+        let source = PasswordSource {
+            file: Some(&file),
+            ..PasswordSource::default()
+        };
+        assert_eq!(
+            source.resolve(&TARGET).unwrap().unwrap().expose(),
+            "from-file"
+        );
+
+        #[cfg(unix)]
+        {
+            let source = PasswordSource {
+                command: Some("printf 'from-command\\n'"),
+                ..PasswordSource::default()
+            };
+            assert_eq!(
+                source.resolve(&TARGET).unwrap().unwrap().expose(),
+                "from-command"
+            );
+            let source = PasswordSource {
+                command: Some("echo denied >&2; exit 3"),
+                ..PasswordSource::default()
+            };
+            let error = source.resolve(&TARGET).unwrap_err().to_string();
+            assert!(error.contains("denied"), "{error}");
+        }
+
+        let source = PasswordSource {
+            env: Some("DBFAST_TEST_UNSET_PASSWORD_VARIABLE"),
+            ..PasswordSource::default()
+        };
+        assert!(matches!(
+            source.resolve(&TARGET),
+            Err(CredentialError::MissingEnv(_))
+        ));
+
+        let source = PasswordSource {
+            env: Some("PGPASSWORD"),
+            file: Some(&file),
+            command: None,
+        };
+        assert!(matches!(
+            source.resolve(&TARGET),
+            Err(CredentialError::Conflicting)
+        ));
+    }
+
+    #[test]
+    fn test_redaction() {
+        let password = Password::new("hunter2".to_string());
+        assert_eq!(
+            format!("{password} {password:?}"),
+            "******** Password(********)"
+        );
+        assert_eq!(
+            redact_url("postgres://deploy:hunter2@db:5432/app"),
+            "postgres://deploy:********@db:5432/app"
+        );
+        assert_eq!(
+            redact_url("postgres://deploy@db/app"),
+            "postgres://deploy@db/app"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! # File Directives
+//!
+//! A SQL file can carry `-- dbfast:` comment directives in its header, the run
+//! of blank and comment lines before its first statement:
+//!
+//! ```sql
+//! -- Indexes built without locking out writes
+//! -- dbfast: env=local,staging transaction=none
+//! -- dbfast: depends-on=01_accounts.sql
+//! CREATE INDEX CONCURRENTLY accounts_email_idx ON accounts (email);
+//! ```
+//!
+//! Directives are `key=value` pairs (or bare flags) separated by whitespace:
+//!
+//! - `env=a,b` only includes the file when building for one of the listed
+//!   environments
+//! - `transaction=none` runs every statement of the file outside a transaction;
+//!   `transaction=auto` (the default) wraps statements in a transaction unless
+//!   they cannot run in one (see [`crate::execution`])
+//! - `depends-on=a.sql,b.sql` orders the file after the listed files, which are
+//!   resolved relative to the file's directory
+//! - `skip-if-exists=name` skips the file when the relation `name` already
+//!   exists in the target database
+//! - `once` skips the file when it has already been applied to the target
+//!   database, as recorded in its `dbfast_applied_files` table; meant for
+//!   long-lived deploy targets, it only applies where tracking is enabled
+//!   (see [`DatabasePool::with_applied_files_tracking`]), never in template builds
+//!
+//! [`DatabasePool::with_applied_files_tracking`]: crate::database::DatabasePool::with_applied_files_tracking
+//!
+//! Only the directives of the files being built apply: files pulled in with
+//! `\i`/`\ir` can set `transaction`, but the rest of their header is ignored.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Prefix of a directive comment
+const DIRECTIVE_PREFIX: &str = "-- dbfast:";
+
+/// A malformed directive
+#[derive(Debug, Error)]
+#[error("Invalid dbfast directive at line {line}: {message}")]
+pub struct DirectiveError {
+    /// 1-based line of the directive
+    pub line: usize,
+    /// What is wrong with it
+    pub message: String,
+}
+
+/// How the statements of a file are wrapped in transactions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileTransaction {
+    /// In a transaction, except for statements that cannot run in one
+    #[default]
+    Auto,
+    /// Every statement outside a transaction
+    None,
+}
+
+impl fmt::Display for FileTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Directives declared in the header of a SQL file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDirectives {
+    /// `env=` directive: environments the file belongs to, all if `None`
+    pub environments: Option<Vec<String>>,
+    /// `transaction=` directive
+    pub transaction: FileTransaction,
+    /// `depends-on=` directive, as written
+    pub depends_on: Vec<String>,
+    /// `skip-if-exists=` directive
+    pub skip_if_exists: Option<String>,
+    /// `once` directive
+    pub once: bool,
+}
+
+impl FileDirectives {
+    /// Parse the directives in the header of `sql`
+    ///
+    /// # Errors
+    /// Returns [`DirectiveError`] for unknown keys or invalid values.
+    pub fn parse(sql: &str) -> Result<Self, DirectiveError> {
+        let mut directives = Self::default();
+
+        for (index, line) in sql.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if !line.starts_with("--") {
+                break;
+            }
+            let Some(rest) = line.strip_prefix(DIRECTIVE_PREFIX) else {
+                continue;
+            };
+
+            for directive in rest.split_whitespace() {
+                directives.apply(directive, index + 1)?;
+            }
+        }
+
+        Ok(directives)
+    }
+
+    /// Whether the file belongs to `environment`
+    #[must_use]
+    pub fn applies_to_environment(&self, environment: &str) -> bool {
+        self.environments.as_ref().map_or(true, |environments| {
+            environments.iter().any(|name| name == environment)
+        })
+    }
+
+    /// Files the file depends on, resolved relative to `file`'s directory
+    #[must_use]
+    pub fn dependency_paths(&self, file: &Path) -> Vec<PathBuf> {
+        let directory = file.parent().unwrap_or_else(|| Path::new(""));
+        self.depends_on
+            .iter()
+            .map(|dependency| directory.join(dependency))
+            .collect()
+    }
+
+    /// Whether any directive is set
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn apply(&mut self, directive: &str, line: usize) -> Result<(), DirectiveError> {
+        let error = |message: String| DirectiveError { line, message };
+        let (key, value) = directive.split_once('=').unwrap_or((directive, ""));
+        let list = |value: &str| -> Result<Vec<String>, DirectiveError> {
+            let items: Vec<String> = value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect();
+            if items.is_empty() {
+                return Err(error(format!("{key} expects a comma-separated list")));
+            }
+            Ok(items)
+        };
+
+        match key {
+            "env" => self.environments = Some(list(value)?),
+            "depends-on" => self.depends_on.extend(list(value)?),
+            "skip-if-exists" if !value.is_empty() => {
+                self.skip_if_exists = Some(value.to_string());
+            }
+            "skip-if-exists" => return Err(error("skip-if-exists expects a name".to_string())),
+            "once" if directive == "once" => self.once = true,
+            "once" => return Err(error("once does not take a value".to_string())),
+            "transaction" => {
+                self.transaction = match value {
+                    "auto" => FileTransaction::Auto,
+                    "none" => FileTransaction::None,
+                    _ => {
+                        return Err(error(format!(
+                            "transaction must be `auto` or `none`, got `{value}`"
+                        )))
+                    }
+                };
+            }
+            _ => return Err(error(format!("unknown directive `{key}`"))),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FileDirectives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(environments) = &self.environments {
+            parts.push(format!("env={}", environments.join(",")));
+        }
+        if self.transaction != FileTransaction::Auto {
+            parts.push(format!("transaction={}", self.transaction));
+        }
+        if !self.depends_on.is_empty() {
+            parts.push(format!("depends-on={}", self.depends_on.join(",")));
+        }
+        if let Some(name) = &self.skip_if_exists {
+            parts.push(format!("skip-if-exists={name}"));
+        }
+        if self.once {
+            parts.push("once".to_string());
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_directives() {
+        let directives = FileDirectives::parse(
+            "-- Indexes\n\n-- dbfast: transaction=none env=local,staging\n\
+             -- dbfast: depends-on=a.sql,../b.sql skip-if-exists=accounts once\n\
+             CREATE INDEX i ON t (a);",
+        )
+        .unwrap();
+        assert_eq!(directives.transaction, FileTransaction::None);
+        assert!(directives.applies_to_environment("staging"));
+        assert!(!directives.applies_to_environment("production"));
+        assert_eq!(
+            directives.dependency_paths(Path::new("db/0_schema/c.sql")),
+            vec![
+                PathBuf::from("db/0_schema/a.sql"),
+                PathBuf::from("db/0_schema/../b.sql")
+            ]
+        );
+        assert_eq!(directives.skip_if_exists.as_deref(), Some("accounts"));
+        assert!(directives.once);
+        assert_eq!(
+            directives.to_string(),
+            "env=local,staging transaction=none depends-on=a.sql,../b.sql skip-if-exists=accounts once"
+        );
+    }
+
+    #[test]
+    fn test_directives_after_first_statement_are_ignored() {
+        let directives = FileDirectives::parse("SELECT 1;\n-- dbfast: transaction=none\n").unwrap();
+        assert_eq!(directives, FileDirectives::default());
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        let error = FileDirectives::parse("-- dbfast: transaction=maybe").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert!(FileDirectives::parse("\n-- dbfast: bogus").is_err());
+        assert!(FileDirectives::parse("-- dbfast: env=").is_err());
+        assert!(FileDirectives::parse("-- dbfast: once=yes").is_err());
+    }
+}
@@ -84,7 +84,8 @@ impl EnvironmentConfig {
     }
 
     /// Extract the relevant directory from a file path
-    /// For paths like `"tests/fixtures/sql/0_schema/tables.sql"`, extracts `"0_schema"`
+    /// For paths like `"tests/fixtures/sql/0_schema/tables.sql"`, extracts `"0_schema"`.
+    /// Repository-relative paths like `"0_schema/tables.sql"` yield their first component.
     fn extract_directory(file_str: &str) -> String {
         let path_parts: Vec<&str> = file_str.split('/').collect();
 
@@ -100,6 +101,11 @@ impl EnvironmentConfig {
             }
         }
 
+        // Fall back to the top-level directory of a repository-relative path
+        if path_parts.len() > 1 {
+            return path_parts[0].to_string();
+        }
+
         String::new()
     }
 
@@ -0,0 +1,287 @@
+//! # Script Execution Plans
+//!
+//! Some statements cannot run inside a transaction block: `CREATE INDEX
+//! CONCURRENTLY`, `VACUUM`, `CREATE DATABASE`, `ALTER SYSTEM` and a few
+//! others, plus `ALTER TYPE ... ADD VALUE` before `PostgreSQL` 12. The native
+//! executor splits a script into segments: runs of ordinary statements share a
+//! transaction, and statements that cannot run in one (or whose file declares
+//! `transaction=none`, see [`crate::directives`]) run on their own in between.
+//!
+//! Each segment commits on its own, so a script is only atomic when it has a
+//! single transactional segment. The [`ExecutionReport`] of a run records where
+//! the transaction boundaries fell.
+//!
+//! ```rust
+//! use dbfast::execution::requires_autocommit;
+//!
+//! assert!(requires_autocommit("CREATE UNIQUE INDEX CONCURRENTLY i ON t (a)", 150_000));
+//! assert!(requires_autocommit("ALTER TYPE mood ADD VALUE 'meh'", 110_000));
+//! assert!(!requires_autocommit("ALTER TYPE mood ADD VALUE 'meh'", 150_000));
+//! ```
+
+use crate::psql_script::{location, ScriptStatement};
+use crate::sql_lexer::{tokenize, TokenKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+/// First server version that accepts `ALTER TYPE ... ADD VALUE` in a transaction
+const ADD_VALUE_IN_TRANSACTION_VERSION: i32 = 120_000;
+
+/// How the statements of a segment were run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentMode {
+    /// Together, in one transaction
+    Transaction,
+    /// One by one, outside a transaction
+    Autocommit,
+}
+
+/// Where a statement starts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementLocation {
+    /// File the statement comes from, if it was read from a file
+    pub file: Option<PathBuf>,
+    /// 1-based line the statement starts on
+    pub line: usize,
+}
+
+impl fmt::Display for StatementLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", location(self.file.as_deref(), self.line))
+    }
+}
+
+/// A run of consecutive statements executed the same way
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionSegment {
+    /// Whether the statements shared a transaction
+    pub mode: SegmentMode,
+    /// 1-based positions of the first and last statement in the script
+    pub statements: RangeInclusive<usize>,
+    /// Where the first statement starts
+    pub first: StatementLocation,
+    /// Where the last statement starts
+    pub last: StatementLocation,
+}
+
+impl fmt::Display for ExecutionSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (start, end) = (self.statements.start(), self.statements.end());
+        if start == end {
+            write!(f, "statement {start}")?;
+        } else {
+            write!(f, "statements {start}-{end}")?;
+        }
+        match self.mode {
+            SegmentMode::Transaction => write!(f, " in a transaction")?,
+            SegmentMode::Autocommit => write!(f, " outside a transaction")?,
+        }
+        if start == end {
+            write!(f, " ({})", self.first)
+        } else {
+            write!(f, " ({} to {})", self.first, self.last)
+        }
+    }
+}
+
+/// A file skipped by its `skip-if-exists=` or `once` directive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedSource {
+    /// The skipped file, if the script was read from a file
+    pub file: Option<PathBuf>,
+    /// Why it was skipped
+    pub reason: String,
+}
+
+/// What a script run did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// Segments in execution order
+    pub segments: Vec<ExecutionSegment>,
+    /// Files skipped by their directives, in execution order
+    pub skipped: Vec<SkippedSource>,
+}
+
+impl ExecutionReport {
+    /// Number of statements executed
+    #[must_use]
+    pub fn statement_count(&self) -> usize {
+        self.segments
+            .last()
+            .map_or(0, |segment| *segment.statements.end())
+    }
+
+    /// Whether everything ran in a single transaction
+    #[must_use]
+    pub fn is_single_transaction(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|segment| segment.mode == SegmentMode::Transaction)
+            && self.segments.len() <= 1
+    }
+}
+
+/// Split `statements` into segments for a server of version `server_version_num`
+#[must_use]
+pub fn plan_segments(
+    statements: &[ScriptStatement],
+    server_version_num: i32,
+) -> Vec<ExecutionSegment> {
+    let mut segments: Vec<ExecutionSegment> = Vec::new();
+
+    for (index, statement) in statements.iter().enumerate() {
+        let mode =
+            if statement.autocommit || requires_autocommit(&statement.sql, server_version_num) {
+                SegmentMode::Autocommit
+            } else {
+                SegmentMode::Transaction
+            };
+        let position = index + 1;
+        let statement_location = StatementLocation {
+            file: statement.file.clone(),
+            line: statement.line,
+        };
+
+        match segments.last_mut() {
+            Some(segment) if segment.mode == mode => {
+                segment.statements = *segment.statements.start()..=position;
+                segment.last = statement_location;
+            }
+            _ => segments.push(ExecutionSegment {
+                mode,
+                statements: position..=position,
+                first: statement_location.clone(),
+                last: statement_location,
+            }),
+        }
+    }
+
+    segments
+}
+
+/// Whether `statement` cannot run inside a transaction block
+#[must_use]
+pub fn requires_autocommit(statement: &str, server_version_num: i32) -> bool {
+    let words: Vec<String> = tokenize(statement)
+        .into_iter()
+        .filter(|token| !token.kind.is_trivia())
+        .map(|token| {
+            let text = &statement[token.span];
+            if token.kind == TokenKind::Word {
+                text.to_ascii_uppercase()
+            } else {
+                text.to_string()
+            }
+        })
+        .collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let has_sequence = |sequence: &[&str]| words.windows(sequence.len()).any(|w| w == sequence);
+
+    match words.as_slice() {
+        ["VACUUM", ..]
+        | ["ALTER", "SYSTEM", ..]
+        | ["DISCARD", "ALL"]
+        | ["CREATE" | "DROP", "DATABASE" | "TABLESPACE" | "SUBSCRIPTION", ..] => true,
+        ["ALTER", "DATABASE", ..] => has_sequence(&["SET", "TABLESPACE"]),
+        ["CREATE" | "DROP", ..] => has_sequence(&["INDEX", "CONCURRENTLY"]),
+        ["REINDEX", ..] => {
+            has_sequence(&["CONCURRENTLY"])
+                || has_sequence(&["DATABASE"])
+                || has_sequence(&["SYSTEM"])
+        }
+        // Without a table, CLUSTER reclusters every table and cannot run in a transaction
+        ["CLUSTER", rest @ ..] => rest.iter().all(|word| *word == "VERBOSE"),
+        ["ALTER", "TYPE", ..] => {
+            server_version_num < ADD_VALUE_IN_TRANSACTION_VERSION && has_sequence(&["ADD", "VALUE"])
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement(sql: &str, line: usize, autocommit: bool) -> ScriptStatement {
+        ScriptStatement {
+            sql: sql.to_string(),
+            file: None,
+            line,
+            copy_data: None,
+            autocommit,
+            source: 0,
+        }
+    }
+
+    #[test]
+    fn test_classification() {
+        for sql in [
+            "vacuum analyze t",
+            "CREATE DATABASE other",
+            "DROP INDEX CONCURRENTLY IF EXISTS i",
+            "create index concurrently on t (a)",
+            "REINDEX (VERBOSE) TABLE CONCURRENTLY t",
+            "REINDEX DATABASE app",
+            "ALTER DATABASE app SET TABLESPACE fast",
+            "ALTER SYSTEM SET work_mem = '64MB'",
+            "CLUSTER",
+        ] {
+            assert!(requires_autocommit(sql, 150_000), "{sql}");
+        }
+        for sql in [
+            "CREATE INDEX i ON t (a)",
+            "CREATE TABLE concurrently (a int)",
+            "ALTER DATABASE app SET search_path = app",
+            "CLUSTER t USING i",
+            "REINDEX TABLE t",
+            "SELECT 'VACUUM'",
+            "ALTER TYPE mood ADD VALUE 'meh'",
+        ] {
+            assert!(!requires_autocommit(sql, 150_000), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_plan_segments() {
+        let statements = [
+            statement("CREATE TABLE t (a int)", 1, false),
+            statement("INSERT INTO t VALUES (1)", 2, false),
+            statement("CREATE INDEX CONCURRENTLY i ON t (a)", 3, false),
+            statement("VACUUM t", 4, false),
+            statement("SELECT 1", 5, true),
+            statement("SELECT 2", 6, false),
+        ];
+        let segments = plan_segments(&statements, 150_000);
+
+        let summary: Vec<(SegmentMode, RangeInclusive<usize>)> = segments
+            .iter()
+            .map(|segment| (segment.mode, segment.statements.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (SegmentMode::Transaction, 1..=2),
+                (SegmentMode::Autocommit, 3..=5),
+                (SegmentMode::Transaction, 6..=6),
+            ]
+        );
+        assert_eq!(
+            segments[1].to_string(),
+            "statements 3-5 outside a transaction (line 3 to line 5)"
+        );
+        assert_eq!(
+            segments[2].to_string(),
+            "statement 6 in a transaction (line 6)"
+        );
+
+        let report = ExecutionReport {
+            segments,
+            skipped: Vec::new(),
+        };
+        assert_eq!(report.statement_count(), 6);
+        assert!(!report.is_single_transaction());
+    }
+}
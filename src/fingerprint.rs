@@ -0,0 +1,306 @@
+//! # Fingerprinted Template Cache
+//!
+//! In fingerprint mode (`fingerprint_templates = true` under `[database]`) the
+//! physical template name carries a short hash of the files it was built from,
+//! e.g. `myapp_template_3fa9c1`. Templates for different branches can then
+//! coexist and switching back to a branch reuses its template.
+//!
+//! Every use of a fingerprinted template is recorded in `.dbfast/fingerprints.json`
+//! so `dbfast template gc` can drop the ones that are no longer needed.
+
+use crate::change_detector::ChangeDetectionError;
+use crate::scanner::ScannedFile;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::fs;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Distinguishes temporary registry files of concurrent saves in one process
+static SAVE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Number of hex characters used for a fingerprint
+pub const FINGERPRINT_LENGTH: usize = 6;
+
+/// Compute the fingerprint of a set of files
+///
+/// The fingerprint covers each file's path relative to `root_path` and its
+/// content hash, in execution order, so the same checkout in another
+/// directory produces the same fingerprint.
+#[must_use]
+pub fn fingerprint_files(root_path: &Path, files: &[ScannedFile]) -> String {
+    let mut combined = String::new();
+    for file in files {
+        let relative = file.path.strip_prefix(root_path).unwrap_or(&file.path);
+        combined.push_str(&relative.to_string_lossy());
+        combined.push('\0');
+        combined.push_str(&file.hash);
+        combined.push('\n');
+    }
+
+    let hash = format!("{:016x}", xxh3_64(combined.as_bytes()));
+    hash[..FINGERPRINT_LENGTH].to_string()
+}
+
+/// One fingerprinted template recorded in the registry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FingerprintEntry {
+    /// Logical template name (without fingerprint)
+    pub template: String,
+    /// Physical database name of the template
+    pub database: String,
+    /// Fingerprint of the files the template was built from
+    pub fingerprint: String,
+    /// Lower layer databases the template was cloned from
+    #[serde(default)]
+    pub layers: Vec<String>,
+    /// When the template was first recorded
+    pub created_at: DateTime<Utc>,
+    /// When the template was last used by `seed`
+    pub last_used_at: DateTime<Utc>,
+}
+
+/// Registry of fingerprinted templates stored in `.dbfast/fingerprints.json`
+#[derive(Debug, Clone)]
+pub struct FingerprintRegistry {
+    path: PathBuf,
+}
+
+impl FingerprintRegistry {
+    /// Create a registry stored in the given metadata directory
+    #[must_use]
+    pub fn new(metadata_dir: &Path) -> Self {
+        Self {
+            path: metadata_dir.join("fingerprints.json"),
+        }
+    }
+
+    /// Load all recorded entries (empty if the registry does not exist yet)
+    pub async fn load(&self) -> Result<Vec<FingerprintEntry>, ChangeDetectionError> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let json_content = fs::read_to_string(&self.path).await?;
+        Ok(serde_json::from_str(&json_content)?)
+    }
+
+    /// Replace all recorded entries
+    ///
+    /// The registry is written to a temporary file and renamed into place, so
+    /// readers never see a partially written file.
+    pub async fn save(&self, entries: &[FingerprintEntry]) -> Result<(), ChangeDetectionError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let json_content = serde_json::to_string_pretty(entries)?;
+        let temp_path = self.path.with_extension(format!(
+            "json.{}.{}.tmp",
+            std::process::id(),
+            SAVE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp_path, json_content).await?;
+        if let Err(e) = fs::rename(&temp_path, &self.path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Record that a fingerprinted template was used just now
+    ///
+    /// # Arguments
+    /// * `template` - Logical template name
+    /// * `database` - Physical database name of the template
+    /// * `fingerprint` - Fingerprint included in the database name
+    /// * `layers` - Lower layer databases the template depends on
+    pub async fn record_use(
+        &self,
+        template: &str,
+        database: &str,
+        fingerprint: &str,
+        layers: &[String],
+    ) -> Result<(), ChangeDetectionError> {
+        let mut entries = self.load().await?;
+        let now = Utc::now();
+
+        match entries.iter_mut().find(|entry| entry.database == database) {
+            Some(entry) => {
+                entry.last_used_at = now;
+                entry.layers = layers.to_vec();
+            }
+            None => entries.push(FingerprintEntry {
+                template: template.to_string(),
+                database: database.to_string(),
+                fingerprint: fingerprint.to_string(),
+                layers: layers.to_vec(),
+                created_at: now,
+                last_used_at: now,
+            }),
+        }
+
+        self.save(&entries).await
+    }
+}
+
+/// Select the entries that garbage collection should drop
+///
+/// Entries are grouped by logical template. Within a group an entry expires
+/// when it has not been used for longer than `max_age`, or when it falls
+/// outside the `keep` most recently used entries.
+#[must_use]
+pub fn select_expired(
+    entries: &[FingerprintEntry],
+    now: DateTime<Utc>,
+    max_age: Option<Duration>,
+    keep: Option<usize>,
+) -> Vec<FingerprintEntry> {
+    let mut by_template: HashMap<&str, Vec<&FingerprintEntry>> = HashMap::new();
+    for entry in entries {
+        by_template.entry(&entry.template).or_default().push(entry);
+    }
+
+    let mut expired = Vec::new();
+    for group in by_template.values_mut() {
+        group.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at));
+        for (rank, entry) in group.iter().enumerate() {
+            let too_old = max_age.is_some_and(|max_age| now - entry.last_used_at > max_age);
+            let over_limit = keep.is_some_and(|keep| rank >= keep);
+            if too_old || over_limit {
+                expired.push((*entry).clone());
+            }
+        }
+    }
+
+    expired.sort_by(|a, b| a.database.cmp(&b.database));
+    expired
+}
+
+/// Databases that can be dropped for the expired entries
+///
+/// Layer databases still referenced by a remaining entry are kept.
+#[must_use]
+pub fn databases_to_drop(
+    entries: &[FingerprintEntry],
+    expired: &[FingerprintEntry],
+) -> Vec<String> {
+    let expired_names: HashSet<&str> = expired.iter().map(|e| e.database.as_str()).collect();
+    let still_used: HashSet<&str> = entries
+        .iter()
+        .filter(|entry| !expired_names.contains(entry.database.as_str()))
+        .flat_map(|entry| entry.layers.iter().map(String::as_str))
+        .collect();
+
+    let mut databases = Vec::new();
+    for entry in expired {
+        databases.push(entry.database.clone());
+        for layer in &entry.layers {
+            if !still_used.contains(layer.as_str()) && !databases.contains(layer) {
+                databases.push(layer.clone());
+            }
+        }
+    }
+    databases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(template: &str, database: &str, days_ago: i64, layers: &[&str]) -> FingerprintEntry {
+        let used = Utc::now() - Duration::days(days_ago);
+        FingerprintEntry {
+            template: template.to_string(),
+            database: database.to_string(),
+            fingerprint: database.rsplit('_').next().unwrap().to_string(),
+            layers: layers.iter().map(|layer| (*layer).to_string()).collect(),
+            created_at: used,
+            last_used_at: used,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_checkout_location() {
+        let files_a = vec![ScannedFile {
+            path: PathBuf::from("/a/repo/0_schema/tb_user.sql"),
+            hash: "abc".to_string(),
+        }];
+        let files_b = vec![ScannedFile {
+            path: PathBuf::from("/b/repo/0_schema/tb_user.sql"),
+            hash: "abc".to_string(),
+        }];
+
+        let fingerprint = fingerprint_files(Path::new("/a/repo"), &files_a);
+        assert_eq!(fingerprint.len(), FINGERPRINT_LENGTH);
+        assert_eq!(
+            fingerprint,
+            fingerprint_files(Path::new("/b/repo"), &files_b)
+        );
+    }
+
+    #[test]
+    fn test_select_expired_by_age_and_count() {
+        let entries = vec![
+            entry("app", "app_aaaaaa", 0, &[]),
+            entry("app", "app_bbbbbb", 3, &[]),
+            entry("app", "app_cccccc", 10, &[]),
+            entry("other", "other_dddddd", 10, &[]),
+        ];
+
+        let by_age = select_expired(&entries, Utc::now(), Some(Duration::days(7)), None);
+        let names: Vec<&str> = by_age.iter().map(|e| e.database.as_str()).collect();
+        assert_eq!(names, ["app_cccccc", "other_dddddd"]);
+
+        let by_count = select_expired(&entries, Utc::now(), None, Some(1));
+        let names: Vec<&str> = by_count.iter().map(|e| e.database.as_str()).collect();
+        assert_eq!(names, ["app_bbbbbb", "app_cccccc"]);
+    }
+
+    #[test]
+    fn test_shared_layers_are_kept() {
+        let entries = vec![
+            entry("app", "app_aaaaaa", 0, &["app__layer0_111111"]),
+            entry("app", "app_bbbbbb", 10, &["app__layer0_111111"]),
+            entry("app", "app_cccccc", 10, &["app__layer0_222222"]),
+        ];
+        let expired = select_expired(&entries, Utc::now(), Some(Duration::days(7)), None);
+
+        assert_eq!(
+            databases_to_drop(&entries, &expired),
+            ["app_bbbbbb", "app_cccccc", "app__layer0_222222"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_use_leaves_no_temporary_files() {
+        let metadata_dir = tempfile::TempDir::new().unwrap();
+        let registry = FingerprintRegistry::new(metadata_dir.path());
+
+        registry
+            .record_use("app", "app_aaaaaa", "aaaaaa", &[])
+            .await
+            .unwrap();
+        registry
+            .record_use(
+                "app",
+                "app_bbbbbb",
+                "bbbbbb",
+                &["app__layer0_111111".to_string()],
+            )
+            .await
+            .unwrap();
+
+        let entries = registry.load().await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].layers, ["app__layer0_111111"]);
+
+        let files: Vec<_> = std::fs::read_dir(metadata_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["fingerprints.json"]);
+    }
+}
@@ -70,24 +70,42 @@ pub mod change_detector;
 pub mod cli;
 /// Database cloning functionality
 pub mod clone;
+/// Registry of created clones
+pub mod clone_registry;
 /// CLI commands
 pub mod commands;
 /// Configuration management for `DBFast`
 pub mod config;
 /// Database connection management
 pub mod connection;
+/// libpq-style resolution of the `[database]` connection
+pub mod conninfo;
+/// Password resolution for database and remote connections
+pub mod credentials;
 /// Database connection and pooling
 pub mod database;
+/// `-- dbfast:` directives in SQL file headers
+pub mod directives;
 /// Environment filtering for deployments
 pub mod environment;
 /// Error handling
 pub mod error;
 /// Comprehensive error handling system
 pub mod errors;
+/// Transaction segments and reports for script execution
+pub mod execution;
+/// Fingerprint-keyed template cache
+pub mod fingerprint;
 /// Database health monitoring
 pub mod health;
 /// Performance metrics collection
 pub mod metrics;
+/// Dependency-aware ordering of SQL files
+pub mod ordering;
+/// Per-file and per-statement timing of template builds
+pub mod profile;
+/// psql meta-command expansion for the native SQL executor
+pub mod psql_script;
 /// SQL query building utilities
 pub mod query;
 /// Remote deployment management
@@ -96,14 +114,26 @@ pub mod remote;
 pub mod retry;
 /// File scanning and hash calculation
 pub mod scanner;
+/// Clone-leasing server behind `dbfast serve`
+pub mod server;
+/// SQL tokenizer and statement splitter
+pub mod sql_lexer;
 /// SQL repository management for file discovery and loading
 pub mod sql_repository;
+/// Structured errors for failing script statements
+pub mod statement_error;
 /// Template management functionality
 pub mod template;
+/// Self-cleaning test databases for integration tests
+pub mod testing;
+/// TLS for database and remote connections
+pub mod tls;
 
 pub use config::Config;
 pub use connection::Connection;
 pub use database::DatabasePool;
+/// Run an async test against its own database; see [`testing::TestDatabase`]
+pub use dbfast_macros::test;
 pub use query::QueryBuilder;
 pub use scanner::FileScanner;
 
@@ -118,7 +148,7 @@ pub fn hello_world() -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use super::{hello_world, VERSION};
 
     #[test]
     fn test_version() {
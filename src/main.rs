@@ -1,5 +1,7 @@
-use dbfast::cli::{Cli, Commands, RemoteCommands};
-use dbfast::commands::{deploy, environments, init, remote, seed, status, validate_env};
+use dbfast::cli::{Cli, ClonesCommands, Commands, RemoteCommands, TemplateCommands};
+use dbfast::commands::{
+    clones, deploy, environments, init, order, remote, seed, serve, status, template, validate_env,
+};
 use std::process;
 use tracing_subscriber::EnvFilter;
 
@@ -22,8 +24,49 @@ fn main() {
                 process::exit(1);
             }
         }
-        Some(Commands::Seed { output, with_seeds }) => {
-            if let Err(e) = seed::handle_seed(&output, with_seeds) {
+        Some(Commands::Seed {
+            output,
+            with_seeds,
+            env,
+            count,
+            ttl,
+            labels,
+            profile_statements,
+            performance,
+        }) => {
+            let options = seed::SeedOptions {
+                with_seeds,
+                environment: env,
+                count,
+                ttl,
+                labels: labels.into_iter().collect(),
+                profile_statements,
+                performance: performance.into(),
+            };
+            if let Err(e) = seed::handle_seed_with_options(&output, &options) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(Commands::Serve {
+            socket,
+            http,
+            warm,
+            lease_ttl,
+            performance,
+        }) => {
+            let listen = match (socket, http) {
+                (_, Some(port)) => Some(serve::Listen::Http(port)),
+                (Some(path), None) => Some(serve::Listen::Socket(path)),
+                (None, None) => None,
+            };
+            let options = dbfast::server::ServeOptions {
+                warm_per_template: warm,
+                lease_ttl,
+                performance: performance.into(),
+                ..dbfast::server::ServeOptions::default()
+            };
+            if let Err(e) = serve::handle_serve(listen, options) {
                 eprintln!("Error: {}", e);
                 process::exit(1);
             }
@@ -40,12 +83,72 @@ fn main() {
                 process::exit(1);
             }
         }
+        Some(Commands::Order { env, explain }) => {
+            if let Err(e) = order::handle_order(env.as_deref(), explain) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
         Some(Commands::ValidateEnv { env }) => {
             if let Err(e) = validate_env::handle_validate_env(&env) {
                 eprintln!("Error: {}", e);
                 process::exit(1);
             }
         }
+        Some(Commands::Clones { command }) => {
+            let result = match command {
+                ClonesCommands::List => clones::handle_clones_list(),
+                ClonesCommands::Drop {
+                    pattern,
+                    dry_run,
+                    force,
+                } => clones::handle_clones_drop(&pattern, dry_run, force),
+                ClonesCommands::Gc {
+                    older_than,
+                    dry_run,
+                    force,
+                } => clones::handle_clones_gc(older_than, dry_run, force),
+            };
+
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(Commands::Reset {
+            database,
+            performance,
+        }) => {
+            if let Err(e) = clones::handle_reset(&database, &performance.into()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(Commands::Template { command }) => {
+            let result = match command {
+                TemplateCommands::Build {
+                    env,
+                    with_seeds,
+                    profile_statements,
+                    performance,
+                } => template::handle_template_build(
+                    env.as_deref(),
+                    with_seeds,
+                    profile_statements,
+                    &performance.into(),
+                ),
+                TemplateCommands::Gc {
+                    older_than_days,
+                    keep,
+                    dry_run,
+                } => template::handle_template_gc(older_than_days, keep, dry_run),
+            };
+
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
         Some(Commands::Remote { command }) => {
             let result = match command {
                 RemoteCommands::Add {
@@ -587,6 +587,21 @@ impl MetricsCollector {
                 .map(Self::calculate_timing_stats)
         })
     }
+
+    /// Get the retained timing samples of an operation, oldest first
+    #[must_use]
+    pub fn get_operation_samples(&self, operation: &str) -> Vec<TimingSample> {
+        self.inner.lock().map_or_else(
+            |_| Vec::new(),
+            |inner| {
+                inner
+                    .timings
+                    .get(operation)
+                    .map(|metrics| metrics.samples.iter().cloned().collect())
+                    .unwrap_or_default()
+            },
+        )
+    }
 }
 
 /// Utility for automatically timing operations
@@ -0,0 +1,844 @@
+//! # Dependency-Aware File Ordering
+//!
+//! Builds the execution order of a repository's SQL files from a dependency
+//! graph instead of relying on numeric path prefixes alone. A file depends on
+//! another when it declares it with a `depends-on=` directive (see
+//! [`crate::directives`]), or when its SQL uses an object the other file
+//! creates:
+//!
+//! - `REFERENCES accounts` in a foreign key
+//! - `CREATE VIEW ... FROM accounts JOIN plans` (and materialized views)
+//! - a function call in a column `DEFAULT`, e.g. `DEFAULT gen_code()`, or a
+//!   sequence used through `DEFAULT nextval('order_seq')`
+//!
+//! Objects no file creates (system catalogs, extensions) are ignored. Files are
+//! topologically sorted; among files whose dependencies are met, the one that
+//! comes first in discovery order runs first, so a repository whose prefixes
+//! are already right keeps its order. Dependency cycles are reported with the
+//! files that form them; cycles made only of inferred dependencies, which may
+//! be misreadings of the SQL, are logged and broken in discovery order instead.
+//!
+//! ```rust
+//! use dbfast::directives::FileDirectives;
+//! use dbfast::ordering::{order_files, SqlFile};
+//! use std::path::Path;
+//!
+//! let file = |path: &str, sql: &str| SqlFile {
+//!     path: path.into(),
+//!     sql: sql.to_string(),
+//!     directives: FileDirectives::default(),
+//! };
+//! let order = order_files(&[
+//!     file("a_orders.sql", "CREATE TABLE orders (account_id INT REFERENCES accounts);"),
+//!     file("b_accounts.sql", "CREATE TABLE accounts (id INT PRIMARY KEY);"),
+//! ])
+//! .unwrap();
+//! assert_eq!(
+//!     order.paths(),
+//!     vec![Path::new("b_accounts.sql"), Path::new("a_orders.sql")]
+//! );
+//! ```
+
+use crate::directives::FileDirectives;
+use crate::sql_lexer::{split_statements, tokenize, TokenKind};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::warn;
+
+/// Object kinds whose `CREATE` statements define a name other files can use
+const DEFINED_KINDS: &[&str] = &[
+    "TABLE",
+    "VIEW",
+    "FUNCTION",
+    "PROCEDURE",
+    "TYPE",
+    "DOMAIN",
+    "SEQUENCE",
+    "SCHEMA",
+];
+
+/// Words that may appear between `CREATE` and the object kind
+const CREATE_MODIFIERS: &[&str] = &[
+    "OR",
+    "REPLACE",
+    "TEMP",
+    "TEMPORARY",
+    "UNLOGGED",
+    "GLOBAL",
+    "LOCAL",
+    "RECURSIVE",
+    "MATERIALIZED",
+];
+
+/// Words ending a column `DEFAULT` expression
+const DEFAULT_TERMINATORS: &[&str] = &[
+    "NOT",
+    "NULL",
+    "CONSTRAINT",
+    "PRIMARY",
+    "UNIQUE",
+    "CHECK",
+    "REFERENCES",
+    "GENERATED",
+    "COLLATE",
+];
+
+/// Ordering failure
+#[derive(Debug, Error)]
+pub enum OrderingError {
+    /// A `depends-on=` directive names a file outside the build
+    #[error("{} depends on {}, which is not part of this build", file.display(), dependency.display())]
+    MissingDependency {
+        /// File declaring the dependency
+        file: PathBuf,
+        /// The missing file
+        dependency: PathBuf,
+    },
+    /// Files depend on each other
+    #[error("Dependency cycle: {}", cycle_description(.files, .reasons))]
+    Cycle {
+        /// Files in the cycle, each depending on the next and the last on the first
+        files: Vec<PathBuf>,
+        /// Why each file depends on the next
+        reasons: Vec<DependencyReason>,
+    },
+}
+
+/// Why a file depends on another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyReason {
+    /// `depends-on=` directive
+    Declared,
+    /// Foreign key `REFERENCES` to a table
+    ForeignKey(String),
+    /// A view reads from a relation
+    ViewSource(String),
+    /// A column default calls a function or uses a sequence
+    ColumnDefault(String),
+}
+
+impl fmt::Display for DependencyReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Declared => write!(f, "depends-on directive"),
+            Self::ForeignKey(name) => write!(f, "REFERENCES {name}"),
+            Self::ViewSource(name) => write!(f, "view reads from {name}"),
+            Self::ColumnDefault(name) => write!(f, "column default uses {name}"),
+        }
+    }
+}
+
+/// A SQL file to order
+#[derive(Debug, Clone)]
+pub struct SqlFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// File content
+    pub sql: String,
+    /// Directives from the file header
+    pub directives: FileDirectives,
+}
+
+/// An edge of the dependency graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    /// File depended on
+    pub path: PathBuf,
+    /// Why
+    pub reason: DependencyReason,
+}
+
+/// A file in execution order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderedFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// 0-based position in discovery order
+    pub discovered_at: usize,
+    /// Files it has to run after
+    pub dependencies: Vec<Dependency>,
+}
+
+/// Files in dependency order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyOrder {
+    /// Files in execution order
+    pub files: Vec<OrderedFile>,
+}
+
+impl DependencyOrder {
+    /// Paths in execution order
+    #[must_use]
+    pub fn paths(&self) -> Vec<&Path> {
+        self.files.iter().map(|file| file.path.as_path()).collect()
+    }
+
+    /// Consume the order, keeping the paths
+    #[must_use]
+    pub fn into_paths(self) -> Vec<PathBuf> {
+        self.files.into_iter().map(|file| file.path).collect()
+    }
+}
+
+/// Objects a file creates and uses
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SqlObjects {
+    /// Names of created objects
+    pub defines: BTreeSet<ObjectName>,
+    /// Names of used objects, with how they are used
+    pub uses: Vec<(ObjectName, DependencyReason)>,
+}
+
+/// A possibly schema-qualified object name, normalized like the server does
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectName {
+    /// Schema, if qualified
+    pub schema: Option<String>,
+    /// Object name
+    pub name: String,
+}
+
+impl fmt::Display for ObjectName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.schema {
+            Some(schema) => write!(f, "{schema}.{}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Order `files`, given in discovery order, by their dependencies
+///
+/// # Errors
+/// Returns [`OrderingError`] when a declared dependency is not among `files`
+/// or when dependencies form a cycle.
+pub fn order_files(files: &[SqlFile]) -> Result<DependencyOrder, OrderingError> {
+    let identity = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let identities: Vec<PathBuf> = files.iter().map(|file| identity(&file.path)).collect();
+    let objects: Vec<SqlObjects> = files.iter().map(|file| analyze(&file.sql)).collect();
+
+    // The first file creating a name provides it
+    let mut providers: HashMap<&str, Vec<(Option<&str>, usize)>> = HashMap::new();
+    for (index, file_objects) in objects.iter().enumerate() {
+        for object in &file_objects.defines {
+            providers
+                .entry(object.name.as_str())
+                .or_default()
+                .push((object.schema.as_deref(), index));
+        }
+    }
+
+    let mut edges: Vec<Vec<(usize, DependencyReason)>> = vec![Vec::new(); files.len()];
+    for (index, file) in files.iter().enumerate() {
+        let mut add = |dependency: usize, reason: DependencyReason| {
+            if dependency != index && !edges[index].iter().any(|(dep, _)| *dep == dependency) {
+                edges[index].push((dependency, reason));
+            }
+        };
+
+        for dependency in file.directives.dependency_paths(&file.path) {
+            let Some(position) = identities
+                .iter()
+                .position(|id| *id == identity(&dependency))
+            else {
+                return Err(OrderingError::MissingDependency {
+                    file: file.path.clone(),
+                    dependency,
+                });
+            };
+            add(position, DependencyReason::Declared);
+        }
+
+        for (object, reason) in &objects[index].uses {
+            if objects[index].defines.contains(object) {
+                continue;
+            }
+            let provider = providers.get(object.name.as_str()).and_then(|candidates| {
+                candidates
+                    .iter()
+                    .find(|(schema, _)| {
+                        object.schema.is_none()
+                            || schema.is_none()
+                            || *schema == object.schema.as_deref()
+                    })
+                    .map(|(_, provider)| *provider)
+            });
+            if let Some(provider) = provider {
+                add(provider, reason.clone());
+            }
+        }
+    }
+
+    let mut placed = vec![false; files.len()];
+    let mut order = Vec::with_capacity(files.len());
+    while order.len() < files.len() {
+        let next = (0..files.len())
+            .find(|&index| !placed[index] && edges[index].iter().all(|(dep, _)| placed[*dep]));
+        let Some(next) = next else {
+            let (cycle, reasons) = find_cycle(&edges, &placed);
+            let cycle_files: Vec<PathBuf> = cycle
+                .iter()
+                .map(|&index| files[index].path.clone())
+                .collect();
+            if reasons.contains(&DependencyReason::Declared) {
+                return Err(OrderingError::Cycle {
+                    files: cycle_files,
+                    reasons,
+                });
+            }
+
+            // Inference can mistake names for dependencies; rather than fail,
+            // drop the edge leaving the earliest discovered file of the cycle
+            warn!(
+                "Ignoring inferred dependency cycle: {}",
+                cycle_description(&cycle_files, &reasons)
+            );
+            let position = (0..cycle.len())
+                .min_by_key(|&position| cycle[position])
+                .unwrap_or_default();
+            let dependency = cycle[(position + 1) % cycle.len()];
+            edges[cycle[position]].retain(|(dep, _)| *dep != dependency);
+            continue;
+        };
+        placed[next] = true;
+        order.push(next);
+    }
+
+    Ok(DependencyOrder {
+        files: order
+            .into_iter()
+            .map(|index| OrderedFile {
+                path: files[index].path.clone(),
+                discovered_at: index,
+                dependencies: edges[index]
+                    .iter()
+                    .map(|(dep, reason)| Dependency {
+                        path: files[*dep].path.clone(),
+                        reason: reason.clone(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    })
+}
+
+/// Find the objects `sql` creates and the ones it uses
+#[must_use]
+pub fn analyze(sql: &str) -> SqlObjects {
+    let mut objects = SqlObjects::default();
+
+    for statement in split_statements(sql) {
+        let text = statement.text;
+        let tokens: Vec<Item<'_>> = tokenize(text)
+            .into_iter()
+            .filter(|token| !token.kind.is_trivia())
+            .map(|token| Item {
+                kind: token.kind,
+                text: &text[token.span],
+            })
+            .collect();
+
+        let is_view = tokens.first().is_some_and(|t| t.is_word("CREATE"))
+            && tokens
+                .iter()
+                .take_while(|t| !t.is_word("AS"))
+                .any(|t| t.is_word("VIEW"));
+
+        if let Some(name) = defined_name(&tokens) {
+            objects.defines.insert(name);
+        }
+
+        // Whether each enclosing parenthesis holds a query rather than, say,
+        // the arguments of `EXTRACT(year FROM created_at)`
+        let mut parens: Vec<bool> = Vec::new();
+        let mut sources = Vec::new();
+        for (index, token) in tokens.iter().enumerate() {
+            if token.is("(") {
+                let after_from = index.checked_sub(1).is_some_and(|before| {
+                    tokens[before].is_word("FROM") || tokens[before].is_word("JOIN")
+                });
+                parens.push(after_from || tokens.get(index + 1).is_some_and(starts_query));
+            } else if token.is(")") {
+                parens.pop();
+            } else if token.is_word("REFERENCES") {
+                if let Some((name, _)) = object_name(&tokens, index + 1) {
+                    objects
+                        .uses
+                        .push((name.clone(), DependencyReason::ForeignKey(name.to_string())));
+                }
+            } else if is_view
+                && (token.is_word("FROM") || token.is_word("JOIN"))
+                && parens.iter().all(|query| *query)
+            {
+                view_sources(&tokens, index + 1, &mut sources);
+            } else if token.is_word("DEFAULT") {
+                default_uses(&tokens, index + 1, &mut objects.uses);
+            }
+        }
+
+        // Common table expressions are read like relations but defined in place
+        let ctes = cte_names(&tokens);
+        objects.uses.extend(
+            sources
+                .into_iter()
+                .filter(|(name, _)| name.schema.is_some() || !ctes.contains(&name.name)),
+        );
+    }
+
+    objects
+}
+
+/// Whether a token starts a query
+fn starts_query(token: &Item<'_>) -> bool {
+    token.is_word("SELECT") || token.is_word("WITH") || token.is_word("VALUES")
+}
+
+/// Names of the common table expressions declared in a statement
+fn cte_names(tokens: &[Item<'_>]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if !token.is_word("WITH") {
+            continue;
+        }
+        let mut index = index + 1;
+        if tokens.get(index).is_some_and(|t| t.is_word("RECURSIVE")) {
+            index += 1;
+        }
+        // `name [(columns)] AS [[NOT] MATERIALIZED] (query)`, comma-separated
+        while let Some(name) = tokens.get(index).and_then(Item::identifier) {
+            index += 1;
+            if tokens.get(index).is_some_and(|t| t.is("(")) {
+                index = after_parentheses(tokens, index);
+            }
+            if !tokens.get(index).is_some_and(|t| t.is_word("AS")) {
+                break;
+            }
+            index += 1;
+            while tokens
+                .get(index)
+                .is_some_and(|t| t.is_word("NOT") || t.is_word("MATERIALIZED"))
+            {
+                index += 1;
+            }
+            if !tokens.get(index).is_some_and(|t| t.is("(")) {
+                break;
+            }
+            names.insert(name);
+            index = after_parentheses(tokens, index);
+            if !tokens.get(index).is_some_and(|t| t.is(",")) {
+                break;
+            }
+            index += 1;
+        }
+    }
+    names
+}
+
+/// Index after the parenthesis closing the one opened at `index`
+fn after_parentheses(tokens: &[Item<'_>], mut index: usize) -> usize {
+    let mut depth = 0_usize;
+    while let Some(token) = tokens.get(index) {
+        if token.is("(") {
+            depth += 1;
+        } else if token.is(")") {
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                return index + 1;
+            }
+        }
+        index += 1;
+    }
+    index
+}
+
+/// A significant token and its text
+struct Item<'a> {
+    kind: TokenKind,
+    text: &'a str,
+}
+
+impl Item<'_> {
+    fn is_word(&self, word: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(word)
+    }
+
+    fn is(&self, punctuation: &str) -> bool {
+        self.kind == TokenKind::Other && self.text == punctuation
+    }
+
+    /// The identifier this token spells, folded like the server does
+    fn identifier(&self) -> Option<String> {
+        match self.kind {
+            TokenKind::Word => Some(self.text.to_ascii_lowercase()),
+            TokenKind::QuotedIdentifier => self
+                .text
+                .strip_prefix('"')
+                .and_then(|text| text.strip_suffix('"'))
+                .map(|text| text.replace("\"\"", "\"")),
+            _ => None,
+        }
+    }
+}
+
+/// Name created by a `CREATE` statement
+fn defined_name(tokens: &[Item<'_>]) -> Option<ObjectName> {
+    if !tokens.first()?.is_word("CREATE") {
+        return None;
+    }
+    let mut index = 1;
+    while tokens.get(index)?.kind == TokenKind::Word
+        && CREATE_MODIFIERS
+            .iter()
+            .any(|word| tokens[index].is_word(word))
+    {
+        index += 1;
+    }
+    if !DEFINED_KINDS.iter().any(|kind| tokens[index].is_word(kind)) {
+        return None;
+    }
+    index += 1;
+    if tokens.get(index).is_some_and(|t| t.is_word("IF")) {
+        index += 3;
+    }
+    object_name(tokens, index).map(|(name, _)| name)
+}
+
+/// Name starting at `index`, and the index after it
+fn object_name(tokens: &[Item<'_>], index: usize) -> Option<(ObjectName, usize)> {
+    let first = tokens.get(index)?.identifier()?;
+    if tokens.get(index + 1).is_some_and(|t| t.is("."))
+        && tokens
+            .get(index + 2)
+            .is_some_and(|t| t.identifier().is_some())
+    {
+        return Some((
+            ObjectName {
+                schema: Some(first),
+                name: tokens[index + 2].identifier()?,
+            },
+            index + 3,
+        ));
+    }
+    Some((
+        ObjectName {
+            schema: None,
+            name: first,
+        },
+        index + 1,
+    ))
+}
+
+/// Relations listed after a view's `FROM` or `JOIN` at `index`
+fn view_sources(
+    tokens: &[Item<'_>],
+    mut index: usize,
+    uses: &mut Vec<(ObjectName, DependencyReason)>,
+) {
+    loop {
+        if tokens
+            .get(index)
+            .is_some_and(|t| t.is_word("ONLY") || t.is_word("LATERAL"))
+        {
+            index += 1;
+        }
+        let Some((name, next)) = object_name(tokens, index) else {
+            return;
+        };
+        uses.push((name.clone(), DependencyReason::ViewSource(name.to_string())));
+        index = next;
+
+        // Skip an alias, then continue a comma-separated FROM list
+        if tokens.get(index).is_some_and(|t| t.is_word("AS")) {
+            index += 1;
+        }
+        if tokens.get(index).is_some_and(|t| {
+            t.kind == TokenKind::QuotedIdentifier
+                || (t.kind == TokenKind::Word && !is_clause_keyword(t))
+        }) {
+            index += 1;
+        }
+        if !tokens.get(index).is_some_and(|t| t.is(",")) {
+            return;
+        }
+        index += 1;
+    }
+}
+
+/// Whether a word ends a `FROM` item rather than aliasing it
+fn is_clause_keyword(token: &Item<'_>) -> bool {
+    [
+        "WHERE",
+        "JOIN",
+        "INNER",
+        "LEFT",
+        "RIGHT",
+        "FULL",
+        "CROSS",
+        "NATURAL",
+        "ON",
+        "USING",
+        "GROUP",
+        "HAVING",
+        "ORDER",
+        "LIMIT",
+        "OFFSET",
+        "UNION",
+        "INTERSECT",
+        "EXCEPT",
+        "WINDOW",
+        "WITH",
+        "FETCH",
+        "FOR",
+    ]
+    .iter()
+    .any(|word| token.is_word(word))
+}
+
+/// Functions and sequences used by the `DEFAULT` expression starting at `index`
+fn default_uses(
+    tokens: &[Item<'_>],
+    mut index: usize,
+    uses: &mut Vec<(ObjectName, DependencyReason)>,
+) {
+    let mut depth = 0_usize;
+    while let Some(token) = tokens.get(index) {
+        if depth == 0
+            && (token.is(",")
+                || token.is(")")
+                || DEFAULT_TERMINATORS.iter().any(|word| token.is_word(word)))
+        {
+            return;
+        }
+        if token.is("(") {
+            depth += 1;
+        } else if token.is(")") {
+            depth -= 1;
+        } else if let Some((name, next)) = object_name(tokens, index) {
+            if tokens.get(next).is_some_and(|t| t.is("(")) {
+                if name.schema.is_none() && name.name == "nextval" {
+                    if let Some(sequence) = tokens.get(next + 1).and_then(sequence_literal) {
+                        uses.push((
+                            sequence.clone(),
+                            DependencyReason::ColumnDefault(sequence.to_string()),
+                        ));
+                    }
+                } else {
+                    uses.push((
+                        name.clone(),
+                        DependencyReason::ColumnDefault(name.to_string()),
+                    ));
+                }
+            }
+            index = next;
+            continue;
+        }
+        index += 1;
+    }
+}
+
+/// Sequence named by a `'schema.name'` literal
+fn sequence_literal(token: &Item<'_>) -> Option<ObjectName> {
+    if token.kind != TokenKind::String {
+        return None;
+    }
+    let text = token.text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut parts = text.rsplitn(2, '.');
+    let name = parts.next()?.to_ascii_lowercase();
+    Some(ObjectName {
+        schema: parts.next().map(str::to_ascii_lowercase),
+        name,
+    })
+}
+
+/// A cycle among the files not `placed`, with the reason of each edge
+fn find_cycle(
+    edges: &[Vec<(usize, DependencyReason)>],
+    placed: &[bool],
+) -> (Vec<usize>, Vec<DependencyReason>) {
+    // Every unplaced file has an unplaced dependency; following them must loop
+    let Some(start) = placed.iter().position(|placed| !placed) else {
+        return (Vec::new(), Vec::new());
+    };
+    let mut path = vec![start];
+    let mut reasons = Vec::new();
+    loop {
+        let current = *path.last().unwrap_or(&start);
+        let Some((next, reason)) = edges[current].iter().find(|(dep, _)| !placed[*dep]) else {
+            return (path, reasons);
+        };
+        reasons.push(reason.clone());
+        if let Some(position) = path.iter().position(|index| index == next) {
+            return (path.split_off(position), reasons.split_off(position));
+        }
+        path.push(*next);
+    }
+}
+
+/// `a.sql -> b.sql (REFERENCES t) -> a.sql (depends-on directive)`
+fn cycle_description(files: &[PathBuf], reasons: &[DependencyReason]) -> String {
+    let mut description = files
+        .first()
+        .map(|file| file.display().to_string())
+        .unwrap_or_default();
+    for (index, reason) in reasons.iter().enumerate() {
+        let next = &files[(index + 1) % files.len()];
+        let _ = write!(description, " -> {} ({reason})", next.display());
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, sql: &str) -> SqlFile {
+        SqlFile {
+            path: PathBuf::from(path),
+            sql: sql.to_string(),
+            directives: FileDirectives::parse(sql).unwrap(),
+        }
+    }
+
+    fn names(objects: &[(ObjectName, DependencyReason)]) -> Vec<String> {
+        objects.iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_analyze_definitions_and_uses() {
+        let objects = analyze(
+            "CREATE TABLE IF NOT EXISTS app.orders (\n\
+               id BIGINT DEFAULT nextval('app.order_seq') PRIMARY KEY,\n\
+               code TEXT DEFAULT make_code(lower('x')) NOT NULL,\n\
+               account_id INT REFERENCES \"Accounts\" (id)\n\
+             );\n\
+             CREATE OR REPLACE VIEW v AS SELECT * FROM app.orders o, Users JOIN plans p ON true;\n\
+             CREATE MATERIALIZED VIEW mv AS SELECT 1 FROM ONLY items;",
+        );
+        let defined: Vec<String> = objects.defines.iter().map(ToString::to_string).collect();
+        assert_eq!(defined, vec!["mv", "v", "app.orders"]);
+        assert_eq!(
+            names(&objects.uses),
+            vec![
+                "app.order_seq",
+                "make_code",
+                "lower",
+                "Accounts",
+                "app.orders",
+                "users",
+                "plans",
+                "items"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_follows_inferred_and_declared_dependencies() {
+        let order = order_files(&[
+            file(
+                "01_views.sql",
+                "CREATE VIEW active AS SELECT * FROM accounts;",
+            ),
+            file(
+                "02_accounts.sql",
+                "CREATE TABLE accounts (id INT, plan INT REFERENCES plans);",
+            ),
+            file("03_plans.sql", "CREATE TABLE plans (id INT);"),
+            file(
+                "04_seed.sql",
+                "-- dbfast: depends-on=05_setup.sql\nSELECT 1;",
+            ),
+            file("05_setup.sql", "SELECT 2;"),
+            file(
+                "06_other.sql",
+                "CREATE TABLE other (id INT REFERENCES pg_class);",
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            order.paths(),
+            vec![
+                Path::new("03_plans.sql"),
+                Path::new("02_accounts.sql"),
+                Path::new("01_views.sql"),
+                Path::new("05_setup.sql"),
+                Path::new("04_seed.sql"),
+                Path::new("06_other.sql"),
+            ]
+        );
+        assert_eq!(
+            order.files[1].dependencies,
+            vec![Dependency {
+                path: PathBuf::from("03_plans.sql"),
+                reason: DependencyReason::ForeignKey("plans".to_string()),
+            }]
+        );
+        assert_eq!(order.files[1].discovered_at, 1);
+    }
+
+    #[test]
+    fn test_view_sources_skip_ctes_and_function_arguments() {
+        let objects = analyze(
+            "CREATE VIEW recent AS\n\
+               WITH totals AS (SELECT sum(n) AS s FROM stats), \"Top\" (id) AS MATERIALIZED (SELECT 1)\n\
+               SELECT EXTRACT(year FROM created_at), substring(name FROM 2), t.s\n\
+               FROM totals t JOIN \"Top\" ON true JOIN (SELECT id FROM accounts) a ON true\n\
+               WITH CHECK OPTION;",
+        );
+        assert_eq!(names(&objects.uses), vec!["stats", "accounts"]);
+
+        // Once the CTE is not taken for the table, there is no cycle to report
+        let order = order_files(&[
+            file(
+                "01_stats.sql",
+                "CREATE TABLE stats (id INT PRIMARY KEY);\n\
+                 CREATE VIEW recent AS WITH totals AS (SELECT count(*) AS n FROM stats) SELECT n FROM totals;",
+            ),
+            file(
+                "02_totals.sql",
+                "CREATE TABLE totals (s INT REFERENCES stats);",
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            order.paths(),
+            vec![Path::new("01_stats.sql"), Path::new("02_totals.sql")]
+        );
+        assert!(order.files[0].dependencies.is_empty());
+        assert_eq!(
+            order.files[1].dependencies[0].reason,
+            DependencyReason::ForeignKey("stats".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cycles_and_missing_dependencies() {
+        let error = order_files(&[
+            file(
+                "a.sql",
+                "-- dbfast: depends-on=b.sql\nCREATE TABLE a (id INT);",
+            ),
+            file("b.sql", "CREATE VIEW b AS SELECT * FROM a;"),
+            file("c.sql", "SELECT 1;"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Dependency cycle: a.sql -> b.sql (depends-on directive) -> a.sql (view reads from a)"
+        );
+
+        // A cycle of inferred dependencies only falls back to discovery order
+        let order = order_files(&[
+            file("a.sql", "CREATE TABLE a (b INT REFERENCES b);"),
+            file("b.sql", "CREATE VIEW b AS SELECT * FROM a;"),
+        ])
+        .unwrap();
+        assert_eq!(order.paths(), vec![Path::new("a.sql"), Path::new("b.sql")]);
+        assert!(order.files[0].dependencies.is_empty());
+
+        let error = order_files(&[file("a.sql", "-- dbfast: depends-on=z.sql\n")]).unwrap_err();
+        assert!(matches!(error, OrderingError::MissingDependency { .. }));
+    }
+}
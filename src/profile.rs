@@ -0,0 +1,330 @@
+//! # Build Profiling
+//!
+//! A [`BuildProfiler`] times template builds through a [`MetricsCollector`]:
+//! the native executor wraps every SQL file it runs in a [`TimingGuard`], and
+//! with statement profiling on, every statement too. After a build the samples
+//! are gathered into a [`BuildReport`]; `dbfast seed` and `dbfast template
+//! build` print its slowest files and store it as JSON under `.dbfast/builds/`,
+//! one report per build, to track build times over time.
+//!
+//! A file is timed from its first statement until the next file starts, so its
+//! time includes committing the transaction its statements ran in. Files
+//! skipped by their directives are not timed, and builds run through the psql
+//! executor are not profiled at all. The report also keeps the transaction
+//! boundaries and skipped files of the build's [`ExecutionReport`]s.
+//!
+//! ```rust
+//! use dbfast::profile::{BuildProfiler, FILE_OPERATION};
+//! use std::collections::HashMap;
+//! use std::time::Duration;
+//!
+//! let profiler = BuildProfiler::new(false);
+//! for (file, ms) in [("a.sql", 5), ("b.sql", 40)] {
+//!     let tags = HashMap::from([("file".to_string(), file.to_string())]);
+//!     profiler
+//!         .metrics()
+//!         .record_timing(FILE_OPERATION, Duration::from_millis(ms), Some(tags));
+//! }
+//!
+//! let report = profiler.report("app_template", chrono::Utc::now(), Duration::from_millis(50));
+//! assert_eq!(report.slowest_files(1)[0].file.to_str(), Some("b.sql"));
+//! ```
+
+use crate::execution::{ExecutionReport, ExecutionSegment, SkippedSource};
+use crate::metrics::{MetricsCollector, MetricsConfig, TimingGuard, TimingSample};
+use crate::psql_script::{location, ScriptStatement};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Metrics operation timing one SQL file of a build
+pub const FILE_OPERATION: &str = "build.file";
+
+/// Metrics operation timing one statement of a build
+pub const STATEMENT_OPERATION: &str = "build.statement";
+
+/// Times the files, and optionally the statements, of a template build
+#[derive(Clone)]
+pub struct BuildProfiler {
+    metrics: MetricsCollector,
+    statements: bool,
+    execution: Arc<Mutex<ExecutionReport>>,
+}
+
+impl BuildProfiler {
+    /// Create a profiler timing every file, and every statement if `statements` is set
+    #[must_use]
+    pub fn new(statements: bool) -> Self {
+        // A build report lists every file, however fast, so keep every sample
+        let metrics = MetricsCollector::new(Some(MetricsConfig {
+            max_timing_samples: usize::MAX,
+            collect_system_metrics: false,
+            min_duration_us: 0,
+            ..MetricsConfig::default()
+        }));
+        Self {
+            metrics,
+            statements,
+            execution: Arc::default(),
+        }
+    }
+
+    /// Collector the timings are recorded in
+    #[must_use]
+    pub const fn metrics(&self) -> &MetricsCollector {
+        &self.metrics
+    }
+
+    /// Whether statements are timed as well as files
+    #[must_use]
+    pub const fn profiles_statements(&self) -> bool {
+        self.statements
+    }
+
+    /// Start timing `file`; the timing is recorded when the guard is dropped
+    #[must_use]
+    pub fn time_file(&self, file: &Path) -> TimingGuard {
+        let tags = HashMap::from([("file".to_string(), file.display().to_string())]);
+        TimingGuard::new(self.metrics.clone(), FILE_OPERATION.to_string(), Some(tags))
+    }
+
+    /// Start timing `statement`, if statements are profiled
+    #[must_use]
+    pub fn time_statement(&self, statement: &ScriptStatement) -> Option<TimingGuard> {
+        if !self.statements {
+            return None;
+        }
+
+        let mut tags = HashMap::from([("line".to_string(), statement.line.to_string())]);
+        if let Some(file) = &statement.file {
+            tags.insert("file".to_string(), file.display().to_string());
+        }
+        Some(TimingGuard::new(
+            self.metrics.clone(),
+            STATEMENT_OPERATION.to_string(),
+            Some(tags),
+        ))
+    }
+
+    /// Keep the transaction boundaries and skipped files of a script run
+    ///
+    /// Builds applying several scripts, one per layer, record each in turn.
+    pub fn record_execution(&self, report: &ExecutionReport) {
+        if let Ok(mut execution) = self.execution.lock() {
+            execution.segments.extend_from_slice(&report.segments);
+            execution.skipped.extend_from_slice(&report.skipped);
+        }
+    }
+
+    /// Gather the timings recorded so far into the report of a build of `template`
+    #[must_use]
+    pub fn report(
+        &self,
+        template: &str,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+    ) -> BuildReport {
+        // A file is timed once per build, but sum repeated samples all the same
+        let mut files: Vec<FileTiming> = Vec::new();
+        let mut positions: HashMap<PathBuf, usize> = HashMap::new();
+        for sample in self.metrics.get_operation_samples(FILE_OPERATION) {
+            let file = sample
+                .tags
+                .get("file")
+                .map(PathBuf::from)
+                .unwrap_or_default();
+            match positions.get(&file) {
+                Some(&position) => files[position].duration_ms += duration_ms(&sample),
+                None => {
+                    positions.insert(file.clone(), files.len());
+                    files.push(FileTiming {
+                        file,
+                        duration_ms: duration_ms(&sample),
+                    });
+                }
+            }
+        }
+
+        let statements = self
+            .metrics
+            .get_operation_samples(STATEMENT_OPERATION)
+            .iter()
+            .map(|sample| StatementTiming {
+                file: sample.tags.get("file").map(PathBuf::from),
+                line: sample
+                    .tags
+                    .get("line")
+                    .and_then(|line| line.parse().ok())
+                    .unwrap_or_default(),
+                duration_ms: duration_ms(sample),
+            })
+            .collect();
+
+        let execution = self
+            .execution
+            .lock()
+            .map(|execution| execution.clone())
+            .unwrap_or_default();
+
+        BuildReport {
+            template: template.to_string(),
+            started_at,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            files,
+            statements,
+            segments: execution.segments,
+            skipped: execution.skipped,
+        }
+    }
+}
+
+/// Time spent in one SQL file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileTiming {
+    /// The file, as passed to the build
+    pub file: PathBuf,
+    /// Wall-clock time in milliseconds
+    pub duration_ms: f64,
+}
+
+/// Time spent in one statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatementTiming {
+    /// File the statement comes from, if it was read from a file
+    pub file: Option<PathBuf>,
+    /// 1-based line the statement starts on
+    pub line: usize,
+    /// Wall-clock time in milliseconds
+    pub duration_ms: f64,
+}
+
+impl StatementTiming {
+    /// `file:line` (or `line N`) where the statement starts
+    #[must_use]
+    pub fn location(&self) -> String {
+        location(self.file.as_deref(), self.line)
+    }
+}
+
+/// Timings of one template build
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildReport {
+    /// Template that was built
+    pub template: String,
+    /// When the build started
+    pub started_at: DateTime<Utc>,
+    /// Wall-clock time of the whole build in milliseconds
+    pub duration_ms: f64,
+    /// Files in execution order
+    pub files: Vec<FileTiming>,
+    /// Statements in execution order, when statements were profiled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub statements: Vec<StatementTiming>,
+    /// Where the transaction boundaries fell, in execution order
+    #[serde(default)]
+    pub segments: Vec<ExecutionSegment>,
+    /// Files skipped by their directives, in execution order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedSource>,
+}
+
+impl BuildReport {
+    /// The `count` slowest files, slowest first
+    #[must_use]
+    pub fn slowest_files(&self, count: usize) -> Vec<&FileTiming> {
+        let mut files: Vec<&FileTiming> = self.files.iter().collect();
+        files.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+        files.truncate(count);
+        files
+    }
+
+    /// The `count` slowest statements, slowest first
+    #[must_use]
+    pub fn slowest_statements(&self, count: usize) -> Vec<&StatementTiming> {
+        let mut statements: Vec<&StatementTiming> = self.statements.iter().collect();
+        statements.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+        statements.truncate(count);
+        statements
+    }
+
+    /// Name of the report file, unique per template and build start
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        format!(
+            "{}-{}.json",
+            self.template,
+            self.started_at.format("%Y%m%dT%H%M%S%.3fZ")
+        )
+    }
+}
+
+#[allow(clippy::cast_precision_loss)] // Microsecond durations fit in an f64
+fn duration_ms(sample: &TimingSample) -> f64 {
+    sample.duration_us as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement(file: &str, line: usize) -> ScriptStatement {
+        ScriptStatement {
+            sql: "SELECT 1".to_string(),
+            file: Some(PathBuf::from(file)),
+            line,
+            copy_data: None,
+            autocommit: false,
+            source: 0,
+        }
+    }
+
+    #[test]
+    fn test_report_collects_guards() {
+        let profiler = BuildProfiler::new(true);
+        for file in ["a.sql", "b.sql"] {
+            let _file = profiler.time_file(Path::new(file));
+            let _statement = profiler.time_statement(&statement(file, 3));
+        }
+
+        let report = profiler.report("app", Utc::now(), Duration::from_millis(1));
+        let files: Vec<&Path> = report.files.iter().map(|t| t.file.as_path()).collect();
+        assert_eq!(files, vec![Path::new("a.sql"), Path::new("b.sql")]);
+        let locations: Vec<String> = report
+            .statements
+            .iter()
+            .map(StatementTiming::location)
+            .collect();
+        assert_eq!(locations, vec!["a.sql:3", "b.sql:3"]);
+
+        assert!(BuildProfiler::new(false)
+            .time_statement(&statement("a.sql", 1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_slowest_files_and_report_name() {
+        let profiler = BuildProfiler::new(false);
+        for (file, ms) in [("a.sql", 5), ("b.sql", 40), ("c.sql", 12), ("a.sql", 30)] {
+            let tags = HashMap::from([("file".to_string(), file.to_string())]);
+            profiler
+                .metrics()
+                .record_timing(FILE_OPERATION, Duration::from_millis(ms), Some(tags));
+        }
+
+        let started_at = DateTime::parse_from_rfc3339("2024-05-01T12:30:00.250Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let report = profiler.report("app", started_at, Duration::from_millis(90));
+        let slowest: Vec<(&str, f64)> = report
+            .slowest_files(2)
+            .iter()
+            .map(|t| (t.file.to_str().unwrap(), t.duration_ms))
+            .collect();
+        assert_eq!(slowest, vec![("b.sql", 40.0), ("a.sql", 35.0)]);
+        assert!(report.statements.is_empty());
+        assert_eq!(report.file_name(), "app-20240501T123000.250Z.json");
+    }
+}
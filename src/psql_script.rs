@@ -0,0 +1,724 @@
+//! # psql Scripts
+//!
+//! Expands the subset of psql meta-commands the native executor understands,
+//! turning a script into the plain statements to run:
+//!
+//! - `\i file` / `\include file` and `\ir file` / `\include_relative file`:
+//!   both resolve `file` relative to the including file
+//! - `\set name value...` / `\unset name`, with `:name`, `:'name'` (quoted
+//!   literal) and `:"name"` (quoted identifier) interpolation; references to
+//!   undefined variables are left alone, as psql does, unless the script was
+//!   created with [`PsqlScript::with_variables`]
+//! - `\echo text`, logged at info level
+//! - `COPY ... FROM stdin;` followed by inline data terminated by `\.`
+//!
+//! Meta-commands must start a line between statements. Any other meta-command
+//! is rejected with [`ScriptError::UnsupportedCommand`].
+//!
+//! Each file added to a script is a [`ScriptSource`] with its header
+//! directives (see [`crate::directives`]). Statements of included files belong
+//! to the source including them, but only follow their own `transaction=`
+//! directive.
+//!
+//! ```rust
+//! use dbfast::psql_script::PsqlScript;
+//!
+//! let mut script = PsqlScript::new();
+//! script
+//!     .add_str("\\set schema app\nCREATE SCHEMA :\"schema\";", None)
+//!     .unwrap();
+//! assert_eq!(script.statements()[0].sql, "CREATE SCHEMA \"app\"");
+//! assert_eq!(script.statements()[0].line, 2);
+//! ```
+
+use crate::directives::{FileDirectives, FileTransaction};
+use crate::sql_lexer::{split_statements, tokenize, TokenKind};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::info;
+
+/// Script expansion errors
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    /// A script or included file could not be read
+    #[error("Failed to read SQL file {path}: {source}")]
+    Io {
+        /// File that could not be read
+        path: PathBuf,
+        /// Underlying IO error
+        #[source]
+        source: io::Error,
+    },
+
+    /// A meta-command outside the supported subset
+    #[error("Unsupported psql meta-command \\{command} at {location}")]
+    UnsupportedCommand {
+        /// Command name, without the backslash
+        command: String,
+        /// `file:line` or `line N`
+        location: String,
+    },
+
+    /// A supported meta-command with invalid arguments
+    #[error("Invalid psql meta-command at {location}: {message}")]
+    InvalidCommand {
+        /// `file:line` or `line N`
+        location: String,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// A malformed `-- dbfast:` directive
+    #[error("Invalid dbfast directive at {location}: {message}")]
+    InvalidDirective {
+        /// `file:line` or `line N`
+        location: String,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// A `:'name'` or `:"name"` reference to a variable nobody defined
+    #[error("Undefined variable :{name} at {location}")]
+    UndefinedVariable {
+        /// Variable name
+        name: String,
+        /// `file:line` or `line N`
+        location: String,
+    },
+
+    /// A file includes itself, directly or indirectly
+    #[error("Include cycle: {path} is already being included")]
+    IncludeCycle {
+        /// File included again
+        path: PathBuf,
+    },
+}
+
+/// A statement of an expanded script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptStatement {
+    /// Statement text with variables interpolated, without the terminating semicolon
+    pub sql: String,
+    /// File the statement comes from, if it was read from a file
+    pub file: Option<PathBuf>,
+    /// 1-based line the statement starts on
+    pub line: usize,
+    /// Inline data of a `COPY ... FROM stdin` statement
+    pub copy_data: Option<String>,
+    /// Whether the statement's file opted out of transaction wrapping
+    pub autocommit: bool,
+    /// Index of the source the statement belongs to
+    pub source: usize,
+}
+
+/// A file or string added to a script, with its header directives
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptSource {
+    /// File the source was read from, if any
+    pub file: Option<PathBuf>,
+    /// Directives in the source's header
+    pub directives: FileDirectives,
+}
+
+/// A script being expanded, with the psql variables set so far
+#[derive(Debug, Default)]
+pub struct PsqlScript {
+    variables: BTreeMap<String, String>,
+    statements: Vec<ScriptStatement>,
+    sources: Vec<ScriptSource>,
+    include_stack: Vec<PathBuf>,
+    depth: usize,
+    strict: bool,
+}
+
+impl PsqlScript {
+    /// Create an empty script
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a script with predefined variables, e.g. an environment's
+    ///
+    /// Quoted references (`:'name'`, `:"name"`) to variables that are neither
+    /// predefined nor `\set` by the script are errors; bare `:name` references
+    /// are still left alone, since they also appear in array slices.
+    #[must_use]
+    pub fn with_variables(variables: BTreeMap<String, String>) -> Self {
+        Self {
+            variables,
+            strict: true,
+            ..Self::default()
+        }
+    }
+
+    /// Set a variable, as `\set name value` would
+    pub fn set_variable(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.variables.insert(name.into(), value.into());
+    }
+
+    /// Value of a variable
+    #[must_use]
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+
+    /// Statements expanded so far
+    #[must_use]
+    pub fn statements(&self) -> &[ScriptStatement] {
+        &self.statements
+    }
+
+    /// Files and strings added to the script, excluding included files
+    #[must_use]
+    pub fn sources(&self) -> &[ScriptSource] {
+        &self.sources
+    }
+
+    /// Consume the script, returning its statements
+    #[must_use]
+    pub fn into_statements(self) -> Vec<ScriptStatement> {
+        self.statements
+    }
+
+    /// Consume the script, returning its statements and sources
+    #[must_use]
+    pub fn into_parts(self) -> (Vec<ScriptStatement>, Vec<ScriptSource>) {
+        (self.statements, self.sources)
+    }
+
+    /// Append the statements of a SQL file
+    ///
+    /// Variables set by earlier files stay visible, as with several `psql -f`.
+    pub fn add_file(&mut self, path: &Path) -> Result<(), ScriptError> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.include_stack.contains(&key) {
+            return Err(ScriptError::IncludeCycle {
+                path: path.to_path_buf(),
+            });
+        }
+        let content = std::fs::read_to_string(path).map_err(|source| ScriptError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        self.include_stack.push(key);
+        let result = self.add_str(&content, Some(path));
+        self.include_stack.pop();
+        result
+    }
+
+    /// Append the statements of `sql`
+    ///
+    /// `file` is used for error locations and to resolve includes; without it,
+    /// includes resolve relative to the current directory.
+    pub fn add_str(&mut self, sql: &str, file: Option<&Path>) -> Result<(), ScriptError> {
+        let directives = FileDirectives::parse(sql).map_err(|e| ScriptError::InvalidDirective {
+            location: location(file, e.line),
+            message: e.message,
+        })?;
+        let autocommit = directives.transaction == FileTransaction::None;
+        if self.depth == 0 {
+            self.sources.push(ScriptSource {
+                file: file.map(Path::to_path_buf),
+                directives,
+            });
+        }
+
+        self.depth += 1;
+        let result = self.add_items(sql, file, autocommit);
+        self.depth -= 1;
+        result
+    }
+
+    fn add_items(
+        &mut self,
+        sql: &str,
+        file: Option<&Path>,
+        autocommit: bool,
+    ) -> Result<(), ScriptError> {
+        for item in script_items(sql) {
+            match item {
+                ScriptItem::Statement { text, line } => {
+                    let sql = self.interpolate(text, file, line)?;
+                    self.push_statement(sql, file, line, None, autocommit);
+                }
+                ScriptItem::Copy { text, line, data } => {
+                    let sql = self.interpolate(text, file, line)?;
+                    self.push_statement(sql, file, line, Some(data.to_string()), autocommit);
+                }
+                ScriptItem::Meta { text, line } => self.run_meta_command(text, file, line)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn interpolate(
+        &self,
+        text: &str,
+        file: Option<&Path>,
+        line: usize,
+    ) -> Result<String, ScriptError> {
+        interpolate(text, &self.variables, self.strict).map_err(|name| {
+            ScriptError::UndefinedVariable {
+                name,
+                location: location(file, line),
+            }
+        })
+    }
+
+    fn push_statement(
+        &mut self,
+        sql: String,
+        file: Option<&Path>,
+        line: usize,
+        copy_data: Option<String>,
+        autocommit: bool,
+    ) {
+        self.statements.push(ScriptStatement {
+            sql,
+            file: file.map(Path::to_path_buf),
+            line,
+            copy_data,
+            autocommit,
+            source: self.sources.len() - 1,
+        });
+    }
+
+    fn run_meta_command(
+        &mut self,
+        text: &str,
+        file: Option<&Path>,
+        line: usize,
+    ) -> Result<(), ScriptError> {
+        let (command, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let invalid = |message: String| ScriptError::InvalidCommand {
+            location: location(file, line),
+            message,
+        };
+
+        match command {
+            "i" | "include" | "ir" | "include_relative" => {
+                let args = meta_args(rest, &self.variables).map_err(invalid)?;
+                let [target] = args.as_slice() else {
+                    return Err(invalid(format!("\\{command} expects one file name")));
+                };
+                self.add_file(&resolve_include(file, target))
+            }
+            "set" => {
+                let args = meta_args(rest, &self.variables).map_err(invalid)?;
+                let Some((name, values)) = args.split_first() else {
+                    return Err(invalid("\\set expects a variable name".to_string()));
+                };
+                self.variables.insert(name.clone(), values.concat());
+                Ok(())
+            }
+            "unset" => {
+                let args = meta_args(rest, &self.variables).map_err(invalid)?;
+                let [name] = args.as_slice() else {
+                    return Err(invalid("\\unset expects one variable name".to_string()));
+                };
+                self.variables.remove(name);
+                Ok(())
+            }
+            "echo" => {
+                let args = meta_args(rest, &self.variables).map_err(invalid)?;
+                info!("{}", args.join(" "));
+                Ok(())
+            }
+            "." => Err(invalid(
+                "\\. outside of COPY ... FROM stdin data".to_string(),
+            )),
+            _ => Err(ScriptError::UnsupportedCommand {
+                command: command.to_string(),
+                location: location(file, line),
+            }),
+        }
+    }
+}
+
+/// Files included by `sql` through `\i`/`\ir`, recursively
+///
+/// Used for change detection, so it never fails: unreadable includes are
+/// listed but not followed, and include cycles are cut.
+#[must_use]
+pub fn included_files(sql: &str, file: &Path) -> Vec<PathBuf> {
+    let mut included = Vec::new();
+    collect_includes(sql, file, &mut included);
+    included
+}
+
+fn collect_includes(sql: &str, file: &Path, included: &mut Vec<PathBuf>) {
+    for item in script_items(sql) {
+        let ScriptItem::Meta { text, .. } = item else {
+            continue;
+        };
+        let (command, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        if !matches!(command, "i" | "include" | "ir" | "include_relative") {
+            continue;
+        }
+        let Ok(args) = meta_args(rest, &BTreeMap::new()) else {
+            continue;
+        };
+        let [target] = args.as_slice() else {
+            continue;
+        };
+
+        let target = resolve_include(Some(file), target);
+        if included.contains(&target) || target == file {
+            continue;
+        }
+        included.push(target.clone());
+        if let Ok(content) = std::fs::read_to_string(&target) {
+            collect_includes(&content, &target, included);
+        }
+    }
+}
+
+/// `file:line`, or `line N` for content not read from a file
+pub(crate) fn location(file: Option<&Path>, line: usize) -> String {
+    file.map_or_else(
+        || format!("line {line}"),
+        |file| format!("{}:{line}", file.display()),
+    )
+}
+
+/// Quote `value` as a SQL string literal, like psql's `:'name'`
+pub(crate) fn quote_literal(value: &str) -> String {
+    let escaped = value.replace('\'', "''");
+    if value.contains('\\') {
+        format!("E'{}'", escaped.replace('\\', "\\\\"))
+    } else {
+        format!("'{escaped}'")
+    }
+}
+
+/// Quote `value` as a SQL identifier, like psql's `:"name"`
+pub(crate) fn quote_identifier(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn resolve_include(file: Option<&Path>, target: &str) -> PathBuf {
+    file.and_then(Path::parent)
+        .map_or_else(|| PathBuf::from(target), |dir| dir.join(target))
+}
+
+/// A piece of a script, in order
+enum ScriptItem<'a> {
+    /// A plain SQL statement
+    Statement { text: &'a str, line: usize },
+    /// `COPY ... FROM stdin` with its inline data
+    Copy {
+        text: &'a str,
+        line: usize,
+        data: &'a str,
+    },
+    /// A meta-command, without the leading backslash
+    Meta { text: &'a str, line: usize },
+}
+
+/// Split a script into statements, `COPY` data blocks and meta-commands
+fn script_items(sql: &str) -> Vec<ScriptItem<'_>> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(sql.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+    let line_end = |offset: usize| sql[offset..].find('\n').map_or(sql.len(), |i| offset + i);
+
+    let mut items = Vec::new();
+    let mut pos = 0;
+
+    'segments: while pos < sql.len() {
+        let rest = &sql[pos..];
+        let meta_at = find_meta_command(rest).map(|offset| pos + offset);
+        let segment_end = meta_at.unwrap_or(sql.len());
+
+        for statement in split_statements(&sql[pos..segment_end]) {
+            let start = pos + statement.span.start;
+            if is_copy_from_stdin(statement.text) {
+                // Data starts on the line after the statement and ends at `\.`
+                let data_start = (line_end(pos + statement.span.end) + 1).min(sql.len());
+                let (data_end, next) = copy_data_end(sql, data_start);
+                items.push(ScriptItem::Copy {
+                    text: statement.text,
+                    line: line_of(start),
+                    data: &sql[data_start..data_end],
+                });
+                pos = next;
+                continue 'segments;
+            }
+            items.push(ScriptItem::Statement {
+                text: statement.text,
+                line: line_of(start),
+            });
+        }
+
+        let Some(start) = meta_at else {
+            break;
+        };
+        let end = line_end(start);
+        items.push(ScriptItem::Meta {
+            text: sql[start + 1..end].trim(),
+            line: line_of(start),
+        });
+        pos = end;
+    }
+
+    items
+}
+
+/// Offset of the first backslash starting a line outside literals and comments
+fn find_meta_command(sql: &str) -> Option<usize> {
+    tokenize(sql)
+        .into_iter()
+        .filter(|token| token.kind == TokenKind::Other && &sql[token.span.clone()] == "\\")
+        .map(|token| token.span.start)
+        .find(|&start| {
+            let line_start = sql[..start].rfind('\n').map_or(0, |i| i + 1);
+            sql[line_start..start].trim().is_empty()
+        })
+}
+
+/// End of inline `COPY` data starting at `start`, and where the script resumes
+fn copy_data_end(sql: &str, start: usize) -> (usize, usize) {
+    let mut line_start = start;
+    while line_start < sql.len() {
+        let end = sql[line_start..]
+            .find('\n')
+            .map_or(sql.len(), |i| line_start + i);
+        if sql[line_start..end].trim_end_matches('\r') == "\\." {
+            return (line_start, end);
+        }
+        line_start = end + 1;
+    }
+    (sql.len(), sql.len())
+}
+
+/// Whether `statement` is a `COPY ... FROM stdin`
+fn is_copy_from_stdin(statement: &str) -> bool {
+    let words: Vec<String> = tokenize(statement)
+        .into_iter()
+        .filter(|token| !token.kind.is_trivia())
+        .map(|token| statement[token.span].to_ascii_uppercase())
+        .collect();
+    words.first().is_some_and(|word| word == "COPY")
+        && words
+            .windows(2)
+            .any(|pair| pair[0] == "FROM" && pair[1] == "STDIN")
+}
+
+/// Replace `:name`, `:'name'` and `:"name"` references to defined variables
+///
+/// With `strict`, a quoted reference to an undefined variable fails with its name.
+fn interpolate(
+    sql: &str,
+    variables: &BTreeMap<String, String>,
+    strict: bool,
+) -> Result<String, String> {
+    if variables.is_empty() && !strict {
+        return Ok(sql.to_string());
+    }
+
+    let tokens = tokenize(sql);
+    let mut output = String::with_capacity(sql.len());
+    let mut copied = 0;
+    let is_colon = |index: usize| {
+        tokens
+            .get(index)
+            .is_some_and(|token| &sql[token.span.clone()] == ":")
+    };
+
+    for (index, token) in tokens.iter().enumerate() {
+        // `::` is a cast, not a variable reference
+        if !is_colon(index) || is_colon(index + 1) || (index > 0 && is_colon(index - 1)) {
+            continue;
+        }
+        let Some(next) = tokens.get(index + 1) else {
+            continue;
+        };
+        let text = &sql[next.span.clone()];
+        let quoted = |quote: char| -> Result<Option<&String>, String> {
+            let name = &text[1..text.len() - 1];
+            match variables.get(name) {
+                None if strict && text.ends_with(quote) => Err(name.to_string()),
+                value => Ok(value),
+            }
+        };
+        let replacement = match next.kind {
+            TokenKind::Word => variables.get(text).cloned(),
+            TokenKind::String if text.len() > 1 && text.starts_with('\'') => {
+                quoted('\'')?.map(|value| quote_literal(value))
+            }
+            TokenKind::QuotedIdentifier if text.len() > 1 && text.starts_with('"') => {
+                quoted('"')?.map(|value| quote_identifier(value))
+            }
+            _ => None,
+        };
+        if let Some(replacement) = replacement {
+            output.push_str(&sql[copied..token.span.start]);
+            output.push_str(&replacement);
+            copied = next.span.end;
+        }
+    }
+
+    output.push_str(&sql[copied..]);
+    Ok(output)
+}
+
+/// Split meta-command arguments, interpolating `:name` references
+///
+/// Arguments are separated by whitespace; single quotes group an argument,
+/// with `''` or `\'` for a quote inside.
+fn meta_args(text: &str, variables: &BTreeMap<String, String>) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut arg = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            match c {
+                '\'' => loop {
+                    match chars.next() {
+                        Some('\'') if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            arg.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => arg.push('\n'),
+                            Some('t') => arg.push('\t'),
+                            Some(escaped) => arg.push(escaped),
+                            None => return Err("unterminated quoted string".to_string()),
+                        },
+                        Some(other) => arg.push(other),
+                        None => return Err("unterminated quoted string".to_string()),
+                    }
+                },
+                ':' if chars
+                    .peek()
+                    .is_some_and(|&n| n.is_alphanumeric() || n == '_') =>
+                {
+                    let mut name = String::new();
+                    while let Some(&n) = chars.peek() {
+                        if !(n.is_alphanumeric() || n == '_') {
+                            break;
+                        }
+                        name.push(n);
+                        chars.next();
+                    }
+                    match variables.get(&name) {
+                        Some(value) => arg.push_str(value),
+                        None => {
+                            arg.push(':');
+                            arg.push_str(&name);
+                        }
+                    }
+                }
+                '`' => return Err("backtick command substitution is not supported".to_string()),
+                other => arg.push(other),
+            }
+        }
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(sql: &str) -> Vec<ScriptStatement> {
+        let mut script = PsqlScript::new();
+        script.add_str(sql, None).unwrap();
+        script.into_statements()
+    }
+
+    #[test]
+    fn test_variables_are_interpolated_outside_literals() {
+        let statements = expand(
+            "\\set owner 'O''Brien'\n\\set table accounts\n\
+             SELECT :'owner', :\"table\", ':table', x::int FROM :table WHERE y = :missing;",
+        );
+        assert_eq!(
+            statements[0].sql,
+            "SELECT 'O''Brien', \"accounts\", ':table', x::int FROM accounts WHERE y = :missing"
+        );
+        assert_eq!(statements[0].line, 3);
+    }
+
+    #[test]
+    fn test_predefined_variables_are_strict() {
+        let variables = BTreeMap::from([("role".to_string(), "app_rw".to_string())]);
+        let mut script = PsqlScript::with_variables(variables);
+        script
+            .add_str(
+                "GRANT SELECT ON t TO :\"role\";\nSELECT a[1:n] FROM t;",
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            script.statements()[0].sql,
+            "GRANT SELECT ON t TO \"app_rw\""
+        );
+        assert_eq!(script.statements()[1].sql, "SELECT a[1:n] FROM t");
+
+        let error = script.add_str("\n\nSELECT :'tenants';", None).unwrap_err();
+        assert_eq!(error.to_string(), "Undefined variable :tenants at line 3");
+    }
+
+    #[test]
+    fn test_copy_from_stdin_data() {
+        let statements = expand(
+            "COPY t (a, b) FROM stdin;\n1\t\\N\n2\tx;y\n\\.\nSELECT 1;\n\\set a 1\nSELECT :a;",
+        );
+        assert_eq!(statements.len(), 3);
+        assert_eq!(statements[0].sql, "COPY t (a, b) FROM stdin");
+        assert_eq!(statements[0].copy_data.as_deref(), Some("1\t\\N\n2\tx;y\n"));
+        assert_eq!(
+            (statements[1].sql.as_str(), statements[1].line),
+            ("SELECT 1", 5)
+        );
+        assert_eq!(statements[2].sql, "SELECT 1");
+    }
+
+    #[test]
+    fn test_backslashes_inside_literals_are_not_meta_commands() {
+        let statements = expand("SELECT E'\n\\connect x';\nSELECT $$\n\\q\n$$;");
+        assert_eq!(statements.len(), 2);
+    }
+
+    #[test]
+    fn test_unsupported_and_invalid_meta_commands() {
+        let mut script = PsqlScript::new();
+        let error = script
+            .add_str("SELECT 1;\n\\connect other", None)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported psql meta-command \\connect at line 2"
+        );
+        assert!(matches!(
+            PsqlScript::new().add_str("\\ir", None),
+            Err(ScriptError::InvalidCommand { .. })
+        ));
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(quote_literal("it's"), "'it''s'");
+        assert_eq!(quote_literal("a\\b"), "E'a\\\\b'");
+        assert_eq!(quote_identifier("my \"t\""), "\"my \"\"t\"\"\"");
+    }
+}
@@ -1,7 +1,9 @@
 //! Remote deployment configuration and management
 
+use crate::credentials::{CredentialError, Password, PasswordSource, PgPassTarget};
+use crate::tls::{TlsConfig, TlsError};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::path::PathBuf;
 use thiserror::Error;
 
 /// Errors that can occur during remote operations
@@ -19,6 +21,14 @@ pub enum RemoteError {
     #[error("Environment variable not found: {0}")]
     EnvVar(String),
 
+    /// The password could not be resolved
+    #[error("Credential error: {0}")]
+    Credentials(#[from] CredentialError),
+
+    /// The TLS settings are invalid
+    #[error("TLS error: {0}")]
+    Tls(#[from] TlsError),
+
     /// Authentication failed
     #[error("Authentication failed: {0}")]
     Auth(String),
@@ -35,6 +45,12 @@ pub struct RemoteConfig {
     /// Environment variable containing password
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password_env: Option<String>,
+    /// File containing the password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<PathBuf>,
+    /// Shell command printing the password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_command: Option<String>,
     /// Target environment
     pub environment: String,
     /// Allow destructive operations
@@ -46,6 +62,9 @@ pub struct RemoteConfig {
     /// Require manual confirmation for deployments
     #[serde(default)]
     pub require_confirmation: bool,
+    /// `sslmode`, `sslrootcert`, `sslcert` and `sslkey`; see [`RemoteConfig::tls`]
+    #[serde(flatten)]
+    pub tls: TlsConfig,
 }
 
 const fn default_backup_before_deploy() -> bool {
@@ -60,21 +79,51 @@ impl RemoteConfig {
             name: Some(name),
             url,
             password_env: None,
+            password_file: None,
+            password_command: None,
             environment,
             allow_destructive: false,
             backup_before_deploy: true,
             require_confirmation: false,
+            tls: TlsConfig {
+                sslmode: None,
+                sslrootcert: None,
+                sslcert: None,
+                sslkey: None,
+            },
         }
     }
 
-    /// Get the password from environment variable
-    pub fn get_password(&self) -> Result<String, RemoteError> {
-        self.password_env.as_ref().map_or_else(
-            || Ok(String::new()),
-            |password_env| {
-                env::var(password_env).map_err(|_| RemoteError::EnvVar(password_env.clone()))
-            },
-        )
+    /// Where the password comes from, see [`crate::credentials`]
+    #[must_use]
+    pub fn credentials(&self) -> PasswordSource<'_> {
+        PasswordSource {
+            env: self.password_env.as_deref(),
+            file: self.password_file.as_deref(),
+            command: self.password_command.as_deref(),
+        }
+    }
+
+    /// Resolve the password, `None` if there is none
+    ///
+    /// Without a configured source, `.pgpass` is searched for the URL's host,
+    /// port, database and user.
+    pub fn get_password(&self) -> Result<Option<Password>, RemoteError> {
+        let params = self.parse_connection_url()?;
+        let target = PgPassTarget {
+            host: &params.host,
+            port: params.port,
+            database: &params.database,
+            user: &params.user,
+        };
+        Ok(self.credentials().resolve(&target)?)
+    }
+
+    /// TLS settings: the remote's own, overridden by the URL's query parameters
+    pub fn tls(&self) -> Result<TlsConfig, RemoteError> {
+        let url = url::Url::parse(&self.url)
+            .map_err(|e| RemoteError::Config(format!("Invalid URL: {}", e)))?;
+        Ok(self.tls.with_url_parameters(&url)?)
     }
 
     /// Parse connection URL components
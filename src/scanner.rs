@@ -3,6 +3,9 @@
 //! Provides file scanning functionality with xxHash-based change detection for SQL files.
 //! This module is core to DBFast's performance optimization - it enables intelligent
 //! rebuilding of database templates only when SQL files have actually changed.
+//! Files pulled in through psql `\i`/`\ir` includes count toward the hash of
+//! the file including them, and environment variables substituted into the SQL
+//! are tracked as one more input (see [`ScannedFile::variables`]).
 //!
 //! ## Example Usage
 //!
@@ -21,12 +24,15 @@
 //! # }
 //! ```
 
+use crate::directives::{DirectiveError, FileDirectives};
+use crate::psql_script::included_files;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 use walkdir::WalkDir;
-use xxhash_rust::xxh3::xxh3_64;
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
 
 /// Scanner-related errors
 #[derive(Debug, Error)]
@@ -38,6 +44,16 @@ pub enum ScannerError {
     /// File walking error
     #[error("Walk error: {0}")]
     Walk(#[from] walkdir::Error),
+
+    /// Malformed `-- dbfast:` directive in a file header
+    #[error("{}: {source}", path.display())]
+    Directive {
+        /// File with the directive
+        path: PathBuf,
+        /// What is wrong with it
+        #[source]
+        source: DirectiveError,
+    },
 }
 
 /// Represents a scanned SQL file with its hash
@@ -49,6 +65,29 @@ pub struct ScannedFile {
     pub hash: String,
 }
 
+/// Path under which substituted variables are tracked like a file
+pub const VARIABLES_PATH: &str = "<variables>";
+
+impl ScannedFile {
+    /// Pseudo-file tracking the variables substituted into SQL files
+    ///
+    /// Recorded with the real files, it makes a changed value trigger a rebuild.
+    #[must_use]
+    pub fn variables(variables: &BTreeMap<String, String>) -> Self {
+        let mut hasher = Xxh3::new();
+        for (name, value) in variables {
+            hasher.update(name.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(value.as_bytes());
+            hasher.update(b"\n");
+        }
+        Self {
+            path: PathBuf::from(VARIABLES_PATH),
+            hash: format!("{:016x}", hasher.digest()),
+        }
+    }
+}
+
 /// File scanner for SQL files
 pub struct FileScanner {
     /// Root directory to scan
@@ -84,12 +123,9 @@ impl FileScanner {
             // Only include SQL files
             if let Some(extension) = path.extension() {
                 if extension == "sql" {
-                    let contents = fs::read(path)?;
-                    let hash = xxh3_64(&contents);
-
                     files.push(ScannedFile {
                         path: path.to_path_buf(),
-                        hash: format!("{hash:016x}"),
+                        hash: hash_file(path)?,
                     });
                 }
             }
@@ -101,6 +137,26 @@ impl FileScanner {
         Ok(files)
     }
 
+    /// Calculate hashes for an explicit list of SQL files
+    ///
+    /// Used when only a subset of the repository (for example one environment)
+    /// makes up a template. The input order is preserved.
+    ///
+    /// # Arguments
+    /// * `paths` - SQL files to hash
+    pub fn scan_files<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<ScannedFile>, ScannerError> {
+        paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                Ok(ScannedFile {
+                    path: path.to_path_buf(),
+                    hash: hash_file(path)?,
+                })
+            })
+            .collect()
+    }
+
     /// Alias for `scan()` method for backward compatibility and clearer naming
     ///
     /// # Example
@@ -118,4 +174,37 @@ impl FileScanner {
     pub fn scan_sql_files(&self) -> Result<Vec<ScannedFile>, ScannerError> {
         self.scan()
     }
+
+    /// Parse the `-- dbfast:` directives in the header of a SQL file
+    ///
+    /// See [`crate::directives`] for the syntax.
+    pub fn read_directives(path: &Path) -> Result<FileDirectives, ScannerError> {
+        let contents = fs::read_to_string(path)?;
+        FileDirectives::parse(&contents).map_err(|source| ScannerError::Directive {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// Hash a SQL file together with the files it includes
+///
+/// A file without includes hashes to the plain xxHash of its contents.
+fn hash_file(path: &Path) -> Result<String, ScannerError> {
+    let contents = fs::read(path)?;
+    let includes = included_files(&String::from_utf8_lossy(&contents), path);
+    if includes.is_empty() {
+        return Ok(format!("{:016x}", xxh3_64(&contents)));
+    }
+
+    let mut hasher = Xxh3::new();
+    hasher.update(&contents);
+    for include in includes {
+        hasher.update(include.to_string_lossy().as_bytes());
+        // A missing include fails the build, not the scan
+        if let Ok(included) = fs::read(&include) {
+            hasher.update(&included);
+        }
+    }
+    Ok(format!("{:016x}", hasher.digest()))
 }
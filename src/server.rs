@@ -0,0 +1,704 @@
+//! # Clone-Leasing Server
+//!
+//! Backs `dbfast serve`: a long-running process that keeps a few databases per
+//! template cloned ahead of time and hands them out as leases, so test
+//! processes in any language get a fresh database in milliseconds.
+//!
+//! ## Protocol
+//!
+//! Requests and responses are JSON objects. Over a Unix socket they are sent one
+//! per line; over HTTP a request is the body of a `POST` (a `GET` returns the status).
+//!
+//! ```text
+//! {"op":"acquire","env":"local","with_seeds":true}
+//!   -> {"status":"leased","lease_id":"…","database":"…","url":"postgresql://…","template":"…","expires_in_secs":60}
+//! {"op":"heartbeat","lease_id":"…"}  -> {"status":"renewed","lease_id":"…","expires_in_secs":60}
+//! {"op":"release","lease_id":"…"}    -> {"status":"released","lease_id":"…"}
+//! {"op":"status"}                    -> {"status":"status","pools":[…],"leases":1}
+//! ```
+//!
+//! Failures are returned as `{"status":"error","message":"…"}`. A lease that is
+//! not renewed within its TTL expires; expired and released databases are dropped
+//! and the warm pools are refilled in the background.
+
+use crate::clone::{CloneConfig, CloneManager};
+use crate::commands::seed::ensure_template;
+use crate::config::{Config, PerformanceOverrides};
+use crate::database::DatabasePool;
+use crate::error::{DbFastError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// How often expired leases are collected
+const REAP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Largest HTTP request body accepted
+const MAX_HTTP_BODY: usize = 64 * 1024;
+
+/// Options for the leasing server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServeOptions {
+    /// Databases kept cloned ahead of time per template
+    pub warm_per_template: usize,
+    /// How long a lease lives without a heartbeat
+    pub lease_ttl: Duration,
+    /// How often templates are checked for changed SQL files
+    pub refresh_interval: Duration,
+    /// `[performance]` settings given on the command line
+    pub performance: PerformanceOverrides,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        Self {
+            warm_per_template: 3,
+            lease_ttl: Duration::from_secs(60),
+            refresh_interval: Duration::from_secs(5),
+            performance: PerformanceOverrides::default(),
+        }
+    }
+}
+
+/// A request to the leasing server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    /// Lease a fresh database
+    Acquire {
+        /// Environment whose file filters build the template
+        #[serde(default)]
+        env: Option<String>,
+        /// Include seed data in the template
+        #[serde(default = "default_with_seeds")]
+        with_seeds: bool,
+    },
+    /// Extend a lease by another TTL
+    Heartbeat {
+        /// Lease to extend
+        lease_id: String,
+    },
+    /// Return a leased database; it is dropped
+    Release {
+        /// Lease to end
+        lease_id: String,
+    },
+    /// Report warm pools and active leases
+    Status,
+}
+
+const fn default_with_seeds() -> bool {
+    true
+}
+
+/// A response from the leasing server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    /// A database was leased
+    Leased {
+        /// Id to use for heartbeats and release
+        lease_id: String,
+        /// Name of the leased database
+        database: String,
+        /// Connection URL for the leased database
+        url: String,
+        /// Template the database was cloned from
+        template: String,
+        /// Seconds until the lease expires without a heartbeat
+        expires_in_secs: u64,
+    },
+    /// A lease was extended
+    Renewed {
+        /// Extended lease
+        lease_id: String,
+        /// Seconds until the lease expires without a heartbeat
+        expires_in_secs: u64,
+    },
+    /// A lease ended and its database is being dropped
+    Released {
+        /// Ended lease
+        lease_id: String,
+    },
+    /// Server status
+    Status {
+        /// Warm pools, one per template
+        pools: Vec<PoolStatus>,
+        /// Number of active leases
+        leases: usize,
+    },
+    /// The request failed
+    Error {
+        /// What went wrong
+        message: String,
+    },
+}
+
+/// Status of one warm pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStatus {
+    /// Environment of the template, if any
+    pub env: Option<String>,
+    /// Whether the template includes seed data
+    pub with_seeds: bool,
+    /// Physical template database name
+    pub template: String,
+    /// Databases ready to be leased
+    pub warm: usize,
+}
+
+/// Template variant a pool serves
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PoolKey {
+    env: Option<String>,
+    with_seeds: bool,
+}
+
+/// Databases cloned ahead of time from one template
+struct WarmPool {
+    template: String,
+    ready: VecDeque<String>,
+    refreshed_at: Instant,
+}
+
+/// A database handed out to a client
+struct Lease {
+    database: String,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct ServerState {
+    pools: HashMap<PoolKey, WarmPool>,
+    leases: HashMap<String, Lease>,
+}
+
+struct Inner {
+    config: Config,
+    repo_path: PathBuf,
+    admin_pool: DatabasePool,
+    clone_manager: CloneManager,
+    options: ServeOptions,
+    state: Mutex<ServerState>,
+    replenish: Notify,
+}
+
+/// Clone-leasing server; cheap to clone and share between connections
+#[derive(Clone)]
+pub struct LeaseServer {
+    inner: Arc<Inner>,
+}
+
+impl LeaseServer {
+    /// Connect to the database server configured in `config`
+    ///
+    /// `repo_path` is the SQL repository templates are built from.
+    pub async fn new(
+        mut config: Config,
+        repo_path: PathBuf,
+        options: ServeOptions,
+    ) -> Result<Self> {
+        config.performance = config.performance.with_overrides(&options.performance);
+        let admin_pool = DatabasePool::from_config_with_pool_config(
+            &config.database,
+            config.performance.pool_config(),
+        )
+        .await
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to connect to database: {e}"),
+        })?;
+
+        let clone_manager = CloneManager::new_with_config(
+            admin_pool.clone(),
+            CloneConfig {
+                labels: BTreeMap::from([("served_by".to_string(), "dbfast".to_string())]),
+                ..config.performance.clone_config()
+            },
+        );
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                config,
+                repo_path,
+                admin_pool,
+                clone_manager,
+                options,
+                state: Mutex::new(ServerState::default()),
+                replenish: Notify::new(),
+            }),
+        })
+    }
+
+    /// Handle one request
+    pub async fn handle(&self, request: Request) -> Response {
+        let result = match request {
+            Request::Acquire { env, with_seeds } => self.acquire(PoolKey { env, with_seeds }).await,
+            Request::Heartbeat { lease_id } => self.heartbeat(&lease_id).await,
+            Request::Release { lease_id } => self.release(&lease_id).await,
+            Request::Status => Ok(self.status().await),
+        };
+
+        result.unwrap_or_else(|e| Response::Error {
+            message: e.to_string(),
+        })
+    }
+
+    async fn acquire(&self, key: PoolKey) -> Result<Response> {
+        let ready = {
+            let mut state = self.inner.state.lock().await;
+            state
+                .pools
+                .get_mut(&key)
+                .map(|pool| (pool.template.clone(), pool.ready.pop_front()))
+        };
+
+        let (template, database) = match ready {
+            Some((template, Some(database))) => (template, database),
+            // Pool is empty: clone on demand
+            Some((template, None)) => {
+                let database = self.clone_warm_database(&template).await?;
+                (template, database)
+            }
+            // First request for this template
+            None => {
+                let template = self.refresh_template(&key).await?;
+                let database = self.clone_warm_database(&template).await?;
+                (template, database)
+            }
+        };
+        self.inner.replenish.notify_one();
+
+        let lease_id = uuid::Uuid::new_v4().simple().to_string();
+        let url = self
+            .inner
+            .admin_pool
+            .connection_url_for(&database)
+            .unwrap_or_default();
+        self.inner.state.lock().await.leases.insert(
+            lease_id.clone(),
+            Lease {
+                database: database.clone(),
+                expires_at: Instant::now() + self.inner.options.lease_ttl,
+            },
+        );
+
+        info!("Leased {} ({}) from {}", database, lease_id, template);
+        Ok(Response::Leased {
+            lease_id,
+            database,
+            url,
+            template,
+            expires_in_secs: self.inner.options.lease_ttl.as_secs(),
+        })
+    }
+
+    async fn heartbeat(&self, lease_id: &str) -> Result<Response> {
+        let expires_at = Instant::now() + self.inner.options.lease_ttl;
+        let renewed = self
+            .inner
+            .state
+            .lock()
+            .await
+            .leases
+            .get_mut(lease_id)
+            .map(|lease| lease.expires_at = expires_at)
+            .is_some();
+        if !renewed {
+            return Err(unknown_lease(lease_id));
+        }
+
+        Ok(Response::Renewed {
+            lease_id: lease_id.to_string(),
+            expires_in_secs: self.inner.options.lease_ttl.as_secs(),
+        })
+    }
+
+    async fn release(&self, lease_id: &str) -> Result<Response> {
+        let lease = self
+            .inner
+            .state
+            .lock()
+            .await
+            .leases
+            .remove(lease_id)
+            .ok_or_else(|| unknown_lease(lease_id))?;
+
+        self.drop_database(&lease.database).await;
+        info!("Released {} ({})", lease.database, lease_id);
+        Ok(Response::Released {
+            lease_id: lease_id.to_string(),
+        })
+    }
+
+    async fn status(&self) -> Response {
+        let state = self.inner.state.lock().await;
+        let mut pools: Vec<PoolStatus> = state
+            .pools
+            .iter()
+            .map(|(key, pool)| PoolStatus {
+                env: key.env.clone(),
+                with_seeds: key.with_seeds,
+                template: pool.template.clone(),
+                warm: pool.ready.len(),
+            })
+            .collect();
+        pools.sort_by(|a, b| a.template.cmp(&b.template));
+
+        Response::Status {
+            pools,
+            leases: state.leases.len(),
+        }
+    }
+
+    /// Rebuild the template for `key` if its SQL files changed
+    ///
+    /// Warm databases cloned from an outdated template are dropped.
+    async fn refresh_template(&self, key: &PoolKey) -> Result<String> {
+        let (template, rebuilt) = ensure_template(
+            &self.inner.config,
+            &self.inner.admin_pool,
+            &self.inner.repo_path,
+            key.env.as_deref(),
+            key.with_seeds,
+        )
+        .await?;
+
+        let mut state = self.inner.state.lock().await;
+        let pool = state.pools.entry(key.clone()).or_insert_with(|| WarmPool {
+            template: template.clone(),
+            ready: VecDeque::new(),
+            refreshed_at: Instant::now(),
+        });
+        pool.refreshed_at = Instant::now();
+        let outdated: Vec<String> = if rebuilt || pool.template != template {
+            pool.template.clone_from(&template);
+            pool.ready.drain(..).collect()
+        } else {
+            Vec::new()
+        };
+        drop(state);
+
+        if !outdated.is_empty() {
+            info!(
+                "Template {} changed, dropping {} warm database(s)",
+                template,
+                outdated.len()
+            );
+        }
+        for database in outdated {
+            self.drop_database(&database).await;
+        }
+        Ok(template)
+    }
+
+    async fn clone_warm_database(&self, template: &str) -> Result<String> {
+        let database = format!("dbfast_lease_{}", uuid::Uuid::new_v4().simple());
+        self.inner
+            .clone_manager
+            .clone_database(template, &database)
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to clone {template}: {e}"),
+            })?;
+        Ok(database)
+    }
+
+    async fn drop_database(&self, database: &str) {
+        if let Err(e) = self.inner.clone_manager.force_drop_database(database).await {
+            warn!("Failed to drop {}: {}", database, e);
+        }
+    }
+
+    /// Drop expired leases
+    ///
+    /// # Returns
+    /// Number of leases that expired
+    pub async fn reap_expired_leases(&self) -> usize {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        let mut state = self.inner.state.lock().await;
+        state.leases.retain(|lease_id, lease| {
+            if lease.expires_at > now {
+                return true;
+            }
+            expired.push((lease_id.clone(), lease.database.clone()));
+            false
+        });
+        drop(state);
+
+        for (lease_id, database) in &expired {
+            info!("Lease {} on {} expired", lease_id, database);
+            self.drop_database(database).await;
+        }
+        expired.len()
+    }
+
+    /// Refresh outdated templates and top every warm pool up to its target size
+    pub async fn replenish(&self) {
+        let keys: Vec<(PoolKey, bool)> = {
+            let state = self.inner.state.lock().await;
+            state
+                .pools
+                .iter()
+                .map(|(key, pool)| {
+                    (
+                        key.clone(),
+                        pool.refreshed_at.elapsed() >= self.inner.options.refresh_interval,
+                    )
+                })
+                .collect()
+        };
+
+        for (key, needs_refresh) in keys {
+            if needs_refresh {
+                if let Err(e) = self.refresh_template(&key).await {
+                    warn!("Failed to refresh template: {}", e);
+                    continue;
+                }
+            }
+
+            loop {
+                let template = {
+                    let state = self.inner.state.lock().await;
+                    match state.pools.get(&key) {
+                        Some(pool) if pool.ready.len() < self.inner.options.warm_per_template => {
+                            pool.template.clone()
+                        }
+                        _ => break,
+                    }
+                };
+
+                match self.clone_warm_database(&template).await {
+                    Ok(database) => {
+                        debug!("Warmed {} from {}", database, template);
+                        let mut state = self.inner.state.lock().await;
+                        match state.pools.get_mut(&key) {
+                            Some(pool) if pool.template == template => {
+                                pool.ready.push_back(database);
+                            }
+                            // Template changed while cloning
+                            _ => {
+                                drop(state);
+                                self.drop_database(&database).await;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to warm a database: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run lease expiry and pool replenishment in the background
+    #[must_use]
+    pub fn spawn_maintenance(&self) -> JoinHandle<()> {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                server.reap_expired_leases().await;
+                server.replenish().await;
+                tokio::select! {
+                    () = server.inner.replenish.notified() => {}
+                    () = tokio::time::sleep(REAP_INTERVAL) => {}
+                }
+            }
+        })
+    }
+
+    /// Drop every warm and leased database
+    ///
+    /// # Returns
+    /// Number of databases dropped
+    pub async fn shutdown(&self) -> usize {
+        let mut state = self.inner.state.lock().await;
+        let state_ref = &mut *state;
+        let databases: Vec<String> = state_ref
+            .leases
+            .drain()
+            .map(|(_, lease)| lease.database)
+            .chain(
+                state_ref
+                    .pools
+                    .values_mut()
+                    .flat_map(|pool| pool.ready.drain(..)),
+            )
+            .collect();
+        drop(state);
+
+        for database in &databases {
+            self.drop_database(database).await;
+        }
+        databases.len()
+    }
+
+    /// Serve newline-delimited JSON requests on a Unix socket
+    #[cfg(unix)]
+    pub async fn serve_unix(&self, socket_path: &Path) -> Result<()> {
+        if socket_path.exists() {
+            std::fs::remove_file(socket_path)?;
+        }
+        let listener = UnixListener::bind(socket_path)?;
+        info!("Listening on {}", socket_path.display());
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let mut response = server.handle_json(&line).await;
+                    response.push('\n');
+                    if writer.write_all(response.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    }
+
+    /// Serve JSON requests over HTTP on a loopback address
+    pub async fn serve_http(&self, addr: SocketAddr) -> Result<()> {
+        if !addr.ip().is_loopback() {
+            return Err(DbFastError::ConfigCreationFailed {
+                message: format!("Refusing to listen on non-loopback address {addr}"),
+            });
+        }
+        let listener = TcpListener::bind(addr).await?;
+        info!("Listening on http://{}", listener.local_addr()?);
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_http(stream).await {
+                    debug!("HTTP connection failed: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn handle_http(&self, stream: tokio::net::TcpStream) -> std::io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+        let method = request_line.split_whitespace().next().unwrap_or_default();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+
+        let (status, body) = match method {
+            "GET" => ("200 OK", self.handle_json(r#"{"op":"status"}"#).await),
+            "POST" if content_length <= MAX_HTTP_BODY => {
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).await?;
+                (
+                    "200 OK",
+                    self.handle_json(&String::from_utf8_lossy(&body)).await,
+                )
+            }
+            "POST" => (
+                "413 Payload Too Large",
+                error_json("Request body too large"),
+            ),
+            _ => (
+                "405 Method Not Allowed",
+                error_json("Use POST with a JSON request"),
+            ),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        writer.write_all(response.as_bytes()).await?;
+        writer.shutdown().await
+    }
+
+    /// Handle one JSON-encoded request and return the JSON-encoded response
+    pub async fn handle_json(&self, request: &str) -> String {
+        let response = match serde_json::from_str::<Request>(request) {
+            Ok(request) => self.handle(request).await,
+            Err(e) => Response::Error {
+                message: format!("Invalid request: {e}"),
+            },
+        };
+        serde_json::to_string(&response).unwrap_or_else(|e| error_json(&e.to_string()))
+    }
+}
+
+fn unknown_lease(lease_id: &str) -> DbFastError {
+    DbFastError::ConfigCreationFailed {
+        message: format!("Unknown or expired lease '{lease_id}'"),
+    }
+}
+
+fn error_json(message: &str) -> String {
+    serde_json::to_string(&Response::Error {
+        message: message.to_string(),
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_decoding() {
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"op":"acquire"}"#).unwrap(),
+            Request::Acquire {
+                env: None,
+                with_seeds: true
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"op":"release","lease_id":"abc"}"#).unwrap(),
+            Request::Release {
+                lease_id: "abc".to_string()
+            }
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"op":"explode"}"#).is_err());
+    }
+
+    #[test]
+    fn test_response_encoding() {
+        let response = Response::Renewed {
+            lease_id: "abc".to_string(),
+            expires_in_secs: 60,
+        };
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"status":"renewed","lease_id":"abc","expires_in_secs":60}"#
+        );
+    }
+}
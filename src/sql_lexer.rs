@@ -0,0 +1,390 @@
+//! # SQL Lexer
+//!
+//! Tokenizes `PostgreSQL` SQL just far enough to split a script into statements:
+//! semicolons only end a statement outside of string literals (`'…'`, `E'…'`),
+//! quoted identifiers, line and nested block comments, dollar-quoted bodies
+//! (`$$…$$`, `$tag$…$tag$`) and SQL-standard `BEGIN ATOMIC … END` function bodies.
+//!
+//! ```rust
+//! use dbfast::sql_lexer::split_statements;
+//!
+//! let statements = split_statements("SELECT ';';\n-- note\nSELECT $$a;b$$;");
+//! assert_eq!(statements.len(), 2);
+//! assert_eq!(statements[1].text, "SELECT $$a;b$$");
+//! assert_eq!(statements[1].start_line, 3);
+//! ```
+
+use std::ops::Range;
+
+/// Kind of a lexical token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Spaces, tabs and newlines
+    Whitespace,
+    /// `-- …` up to the end of the line
+    LineComment,
+    /// `/* … */`, possibly nested
+    BlockComment,
+    /// `'…'`, including `B'…'`, `X'…'`, `N'…'` and `U&'…'`
+    String,
+    /// `E'…'` with backslash escapes
+    EscapeString,
+    /// `"…"`, including `U&"…"`
+    QuotedIdentifier,
+    /// `$$…$$` or `$tag$…$tag$`
+    DollarQuoted,
+    /// Positional parameter such as `$1`
+    Parameter,
+    /// Keyword or unquoted identifier
+    Word,
+    /// Numeric literal
+    Number,
+    /// Statement terminator
+    Semicolon,
+    /// Any other character (operators, punctuation)
+    Other,
+}
+
+/// A token and its byte range in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Kind of token
+    pub kind: TokenKind,
+    /// Byte range in the source
+    pub span: Range<usize>,
+}
+
+impl TokenKind {
+    /// Whether the token is whitespace or a comment
+    #[must_use]
+    pub const fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            Self::Whitespace | Self::LineComment | Self::BlockComment
+        )
+    }
+}
+
+/// A statement of a SQL script, without its terminating semicolon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlStatement<'a> {
+    /// Statement text, without surrounding whitespace and comments
+    pub text: &'a str,
+    /// Byte range of `text` in the script
+    pub span: Range<usize>,
+    /// 1-based line the statement starts on
+    pub start_line: usize,
+    /// 1-based line the statement ends on
+    pub end_line: usize,
+}
+
+/// Split a SQL script into its tokens
+///
+/// Unterminated strings, identifiers and comments extend to the end of the input.
+#[must_use]
+pub fn tokenize(sql: &str) -> Vec<Token> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let (kind, end) = lex_token(bytes, pos);
+        pos = end.max(start + 1).min(bytes.len());
+        tokens.push(Token {
+            kind,
+            span: start..pos,
+        });
+    }
+
+    tokens
+}
+
+/// Split a SQL script into statements
+///
+/// Statements consisting only of comments are dropped; a final statement
+/// without a terminating semicolon is included.
+#[must_use]
+pub fn split_statements(sql: &str) -> Vec<SqlStatement<'_>> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(sql.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+
+    let mut statements = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    let mut previous_word = String::new();
+    let mut atomic_depth = 0_usize;
+
+    let mut finish = |range: Option<Range<usize>>| {
+        if let Some(span) = range {
+            statements.push(SqlStatement {
+                text: &sql[span.clone()],
+                start_line: line_of(span.start),
+                end_line: line_of(span.end.saturating_sub(1)),
+                span,
+            });
+        }
+    };
+
+    for token in tokenize(sql) {
+        if token.kind.is_trivia() {
+            continue;
+        }
+        if token.kind == TokenKind::Semicolon && atomic_depth == 0 {
+            finish(current.take());
+            previous_word.clear();
+            continue;
+        }
+
+        if token.kind == TokenKind::Word {
+            let word = sql[token.span.clone()].to_ascii_uppercase();
+            match word.as_str() {
+                "ATOMIC" if previous_word == "BEGIN" => atomic_depth += 1,
+                "CASE" if atomic_depth > 0 => atomic_depth += 1,
+                "END" if atomic_depth > 0 => atomic_depth -= 1,
+                _ => {}
+            }
+            previous_word = word;
+        }
+
+        current = Some(match current {
+            Some(range) => range.start..token.span.end,
+            None => token.span,
+        });
+    }
+    finish(current);
+
+    statements
+}
+
+/// Lex one token starting at `pos`, returning its kind and end offset
+fn lex_token(bytes: &[u8], pos: usize) -> (TokenKind, usize) {
+    let at = |offset: usize| bytes.get(offset).copied();
+
+    match bytes[pos] {
+        b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' => {
+            let end = (pos..bytes.len())
+                .find(|&i| !bytes[i].is_ascii_whitespace())
+                .unwrap_or(bytes.len());
+            (TokenKind::Whitespace, end)
+        }
+        b'-' if at(pos + 1) == Some(b'-') => {
+            let end = (pos..bytes.len())
+                .find(|&i| bytes[i] == b'\n')
+                .unwrap_or(bytes.len());
+            (TokenKind::LineComment, end)
+        }
+        b'/' if at(pos + 1) == Some(b'*') => {
+            (TokenKind::BlockComment, block_comment_end(bytes, pos))
+        }
+        b'\'' => (TokenKind::String, quoted_end(bytes, pos + 1, b'\'', false)),
+        b'"' => (
+            TokenKind::QuotedIdentifier,
+            quoted_end(bytes, pos + 1, b'"', false),
+        ),
+        b'$' => match at(pos + 1) {
+            Some(c) if c.is_ascii_digit() => {
+                let end = (pos + 1..bytes.len())
+                    .find(|&i| !bytes[i].is_ascii_digit())
+                    .unwrap_or(bytes.len());
+                (TokenKind::Parameter, end)
+            }
+            _ => dollar_quote_tag_end(bytes, pos).map_or((TokenKind::Other, pos + 1), |tag_end| {
+                let tag = &bytes[pos..tag_end];
+                let end = find_subsequence(bytes, tag_end, tag)
+                    .map_or(bytes.len(), |close| close + tag.len());
+                (TokenKind::DollarQuoted, end)
+            }),
+        },
+        b';' => (TokenKind::Semicolon, pos + 1),
+        b'0'..=b'9' => (TokenKind::Number, number_end(bytes, pos)),
+        b'.' if at(pos + 1).is_some_and(|c| c.is_ascii_digit()) => {
+            (TokenKind::Number, number_end(bytes, pos))
+        }
+        c if is_identifier_start(c) => {
+            let end = (pos..bytes.len())
+                .find(|&i| !is_identifier_char(bytes[i]))
+                .unwrap_or(bytes.len());
+            let word = &bytes[pos..end];
+            match (word, at(end), at(end + 1)) {
+                ([b'e' | b'E'], Some(b'\''), _) => (
+                    TokenKind::EscapeString,
+                    quoted_end(bytes, end + 1, b'\'', true),
+                ),
+                ([b'b' | b'B' | b'x' | b'X' | b'n' | b'N'], Some(b'\''), _)
+                | ([b'u' | b'U'], Some(b'&'), Some(b'\'')) => {
+                    let quote = if at(end) == Some(b'&') {
+                        end + 2
+                    } else {
+                        end + 1
+                    };
+                    (TokenKind::String, quoted_end(bytes, quote, b'\'', false))
+                }
+                ([b'u' | b'U'], Some(b'&'), Some(b'"')) => (
+                    TokenKind::QuotedIdentifier,
+                    quoted_end(bytes, end + 2, b'"', false),
+                ),
+                _ => (TokenKind::Word, end),
+            }
+        }
+        _ => (TokenKind::Other, pos + 1),
+    }
+}
+
+/// End of a quoted token whose content starts at `pos`
+///
+/// A doubled quote is an escaped quote; with `backslash_escapes`, so is `\'`.
+fn quoted_end(bytes: &[u8], mut pos: usize, quote: u8, backslash_escapes: bool) -> usize {
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' if backslash_escapes => pos += 2,
+            c if c == quote => {
+                if bytes.get(pos + 1) == Some(&quote) {
+                    pos += 2;
+                } else {
+                    return pos + 1;
+                }
+            }
+            _ => pos += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// End of a (possibly nested) block comment starting at `pos`
+fn block_comment_end(bytes: &[u8], mut pos: usize) -> usize {
+    let mut depth = 0_usize;
+    while pos < bytes.len() {
+        match (bytes[pos], bytes.get(pos + 1)) {
+            (b'/', Some(b'*')) => {
+                depth += 1;
+                pos += 2;
+            }
+            (b'*', Some(b'/')) => {
+                depth -= 1;
+                pos += 2;
+                if depth == 0 {
+                    return pos;
+                }
+            }
+            _ => pos += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// End of the opening tag of a dollar quote (`$$` or `$tag$`) starting at `pos`
+fn dollar_quote_tag_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    let mut end = pos + 1;
+    if bytes.get(end).is_some_and(|&c| is_identifier_start(c)) {
+        while bytes
+            .get(end)
+            .is_some_and(|&c| is_identifier_char(c) && c != b'$')
+        {
+            end += 1;
+        }
+    }
+    (bytes.get(end) == Some(&b'$')).then_some(end + 1)
+}
+
+fn number_end(bytes: &[u8], pos: usize) -> usize {
+    let mut end = pos;
+    while end < bytes.len() {
+        match bytes[end] {
+            b'0'..=b'9' | b'.' | b'_' => end += 1,
+            b'e' | b'E' => {
+                end += 1;
+                if matches!(bytes.get(end), Some(b'+' | b'-')) {
+                    end += 1;
+                }
+            }
+            _ => break,
+        }
+    }
+    end
+}
+
+fn find_subsequence(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|offset| from + offset)
+}
+
+const fn is_identifier_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_' || c >= 0x80
+}
+
+const fn is_identifier_char(c: u8) -> bool {
+    is_identifier_start(c) || c.is_ascii_digit() || c == b'$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(sql: &str) -> Vec<&str> {
+        split_statements(sql).iter().map(|s| s.text).collect()
+    }
+
+    #[test]
+    fn test_semicolons_inside_literals_do_not_split() {
+        assert_eq!(
+            texts("SELECT 'a;b', E'it\\'s;', \"col;name\" FROM t; SELECT 2"),
+            vec!["SELECT 'a;b', E'it\\'s;', \"col;name\" FROM t", "SELECT 2"]
+        );
+        assert_eq!(texts("SELECT 'it''s; fine';"), vec!["SELECT 'it''s; fine'"]);
+        assert_eq!(
+            texts("SELECT U&'d\\0061t;a';"),
+            vec!["SELECT U&'d\\0061t;a'"]
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            texts("-- a; b\nSELECT 1 /* c; /* nested; */ d; */ + 1; /* only a comment; */"),
+            vec!["SELECT 1 /* c; /* nested; */ d; */ + 1"]
+        );
+        assert!(texts("-- just a comment\n/* and another */").is_empty());
+    }
+
+    #[test]
+    fn test_dollar_quotes() {
+        assert_eq!(
+            texts("DO $body$ BEGIN PERFORM $$x;y$$; END $body$; SELECT $1, a$b$c FROM t;"),
+            vec![
+                "DO $body$ BEGIN PERFORM $$x;y$$; END $body$",
+                "SELECT $1, a$b$c FROM t"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_begin_atomic_body() {
+        let sql = "CREATE FUNCTION f() RETURNS int LANGUAGE sql BEGIN ATOMIC \
+                   SELECT CASE WHEN true THEN 1 END; SELECT 2; END; SELECT 3;";
+        assert_eq!(split_statements(sql).len(), 2);
+    }
+
+    #[test]
+    fn test_spans_and_lines() {
+        let sql = "\n-- header\nCREATE TABLE t (\n  id int\n);\n\nINSERT INTO t VALUES (1)";
+        let statements = split_statements(sql);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(&sql[statements[0].span.clone()], statements[0].text);
+        assert_eq!((statements[0].start_line, statements[0].end_line), (3, 5));
+        assert_eq!((statements[1].start_line, statements[1].end_line), (7, 7));
+    }
+
+    #[test]
+    fn test_unterminated_tokens_extend_to_end() {
+        assert_eq!(
+            texts("SELECT 'open; SELECT 2;"),
+            vec!["SELECT 'open; SELECT 2;"]
+        );
+        assert_eq!(texts("SELECT $$open;"), vec!["SELECT $$open;"]);
+    }
+}
@@ -1,10 +1,19 @@
 use crate::database::DatabaseError;
+use crate::directives::FileDirectives;
+use crate::environment::EnvironmentConfig;
+use crate::ordering::{order_files, DependencyOrder, SqlFile};
+use crate::scanner::FileScanner;
 /// SQL Repository functionality for `DBFast`
 ///
 /// This module handles discovery and loading of SQL files from both structured
 /// and flat repository layouts, with support for environment-based filtering.
+/// File header directives (see [`crate::directives`]) can restrict a file to
+/// some environments (`env=`). Discovered files run in dependency order (see
+/// [`crate::ordering`]), with path order breaking ties.
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use tokio::fs as async_fs;
+use walkdir::WalkDir;
 
 /// Result type for SQL repository operations
 pub type SqlRepositoryResult<T> = Result<T, DatabaseError>;
@@ -68,19 +77,182 @@ impl SqlRepository {
     /// # Flat Repository Layout
     /// - All `.sql` files in the root directory
     /// - Files sorted alphabetically
+    ///
+    /// Files with an `env=` directive are kept only if it lists one of
+    /// `environments`; with no environments, they are dropped. Files are then
+    /// moved after the files they depend on (see [`crate::ordering`]).
     pub async fn discover_sql_files(
         &self,
         environments: &[&str],
     ) -> SqlRepositoryResult<Vec<PathBuf>> {
+        Ok(self.order_sql_files(environments).await?.into_paths())
+    }
+
+    /// Discover SQL files like [`discover_sql_files`](Self::discover_sql_files),
+    /// keeping why each file runs after others
+    pub async fn order_sql_files(
+        &self,
+        environments: &[&str],
+    ) -> SqlRepositoryResult<DependencyOrder> {
         let is_structured = self.is_structured_repository().await?;
 
-        if is_structured {
-            self.discover_structured_files(environments).await
+        let files = if is_structured {
+            self.discover_structured_files(environments).await?
+        } else {
+            self.discover_flat_files().await?
+        };
+
+        self.apply_directives(files, |directives| {
+            directives.environments.is_none()
+                || environments
+                    .iter()
+                    .any(|environment| directives.applies_to_environment(environment))
+        })
+    }
+
+    /// Discover SQL files for an environment defined in `dbfast.toml`
+    ///
+    /// Unlike [`discover_sql_files`](Self::discover_sql_files), no directory naming
+    /// heuristics are applied: every structured directory is scanned and the
+    /// environment's include/exclude rules decide which files are kept.
+    ///
+    /// # Arguments
+    /// * `environment` - Environment filter built from the `[environments.<name>]` section
+    ///
+    /// # Returns
+    /// Vector of SQL file paths in execution order
+    pub async fn discover_sql_files_for_environment(
+        &self,
+        environment: &EnvironmentConfig,
+    ) -> SqlRepositoryResult<Vec<PathBuf>> {
+        Ok(self
+            .order_sql_files_for_environment(environment)
+            .await?
+            .into_paths())
+    }
+
+    /// Discover SQL files like
+    /// [`discover_sql_files_for_environment`](Self::discover_sql_files_for_environment),
+    /// keeping why each file runs after others
+    pub async fn order_sql_files_for_environment(
+        &self,
+        environment: &EnvironmentConfig,
+    ) -> SqlRepositoryResult<DependencyOrder> {
+        let all_files = if self.is_structured_repository().await? {
+            self.discover_structured_files_matching(|_| true).await?
         } else {
-            self.discover_flat_files().await
+            self.discover_flat_files().await?
+        };
+
+        // Filter on repository-relative paths so the top-level directory is matched
+        let relative_files: Vec<PathBuf> = all_files
+            .iter()
+            .map(|file| self.relative_path(file).to_path_buf())
+            .collect();
+        let kept: HashSet<PathBuf> = environment
+            .filter_files(&relative_files)
+            .map_err(|e| {
+                DatabaseError::Config(format!(
+                    "Failed to filter files for environment '{}': {e}",
+                    environment.name
+                ))
+            })?
+            .into_iter()
+            .collect();
+
+        let files = all_files
+            .into_iter()
+            .zip(relative_files)
+            .filter(|(_, relative)| kept.contains(relative))
+            .map(|(file, _)| file)
+            .collect();
+
+        self.apply_directives(files, |directives| {
+            directives.applies_to_environment(&environment.name)
+        })
+    }
+
+    /// Parse the `-- dbfast:` directives in the header of a SQL file
+    pub fn file_directives(&self, sql_file: &Path) -> SqlRepositoryResult<FileDirectives> {
+        FileScanner::read_directives(sql_file).map_err(|e| {
+            DatabaseError::Config(format!(
+                "Failed to read directives of {}: {e}",
+                self.relative_path(sql_file).display()
+            ))
+        })
+    }
+
+    /// Keep the files whose directives satisfy `keep`, in dependency order
+    fn apply_directives<F>(
+        &self,
+        files: Vec<PathBuf>,
+        keep: F,
+    ) -> SqlRepositoryResult<DependencyOrder>
+    where
+        F: Fn(&FileDirectives) -> bool,
+    {
+        let mut kept = Vec::new();
+        for file in files {
+            let sql = std::fs::read_to_string(&file).map_err(|e| {
+                DatabaseError::Config(format!(
+                    "Failed to read {}: {e}",
+                    self.relative_path(&file).display()
+                ))
+            })?;
+            let directives = FileDirectives::parse(&sql).map_err(|e| {
+                DatabaseError::Config(format!(
+                    "Failed to read directives of {}: {e}",
+                    self.relative_path(&file).display()
+                ))
+            })?;
+            if keep(&directives) {
+                kept.push(SqlFile {
+                    path: file,
+                    sql,
+                    directives,
+                });
+            }
+        }
+
+        order_files(&kept).map_err(|e| DatabaseError::Config(e.to_string()))
+    }
+
+    /// Check if a file belongs to a seed data directory (e.g. `1_seed_common/`)
+    ///
+    /// Only the top-level directory of structured repositories is considered;
+    /// files in flat repositories are never treated as seed files.
+    #[must_use]
+    pub fn is_seed_file(&self, sql_file: &Path) -> bool {
+        self.top_level_directory(sql_file)
+            .is_some_and(|dir| Self::is_seed_directory(&dir))
+    }
+
+    /// Get the top-level repository directory a file lives in (e.g. `0_schema`)
+    ///
+    /// Returns `None` for files directly in the repository root.
+    #[must_use]
+    pub fn top_level_directory(&self, sql_file: &Path) -> Option<String> {
+        let relative = self.relative_path(sql_file);
+        let mut components = relative.components();
+        match (components.next(), components.next()) {
+            (Some(top_level), Some(_)) => Some(top_level.as_os_str().to_string_lossy().to_string()),
+            _ => None,
         }
     }
 
+    /// Check if a structured directory name denotes seed data (e.g. `2_seed_backend`)
+    #[must_use]
+    pub fn is_seed_directory(dir_name: &str) -> bool {
+        dir_name.contains("_seed")
+    }
+
+    /// Get a file path relative to the repository root
+    fn relative_path<'a>(&self, sql_file: &'a Path) -> &'a Path {
+        sql_file
+            .strip_prefix(&self.repository_path)
+            .unwrap_or(sql_file)
+    }
+
     /// Load SQL content from a file
     ///
     /// # Arguments
@@ -142,6 +314,20 @@ impl SqlRepository {
         &self,
         environments: &[&str],
     ) -> SqlRepositoryResult<Vec<PathBuf>> {
+        self.discover_structured_files_matching(|name| {
+            Self::should_include_structured_directory(name, environments)
+        })
+        .await
+    }
+
+    /// Discover files in the structured directories accepted by `include_directory`
+    async fn discover_structured_files_matching<F>(
+        &self,
+        include_directory: F,
+    ) -> SqlRepositoryResult<Vec<PathBuf>>
+    where
+        F: Fn(&str) -> bool + Send,
+    {
         let mut directories = Vec::new();
         let mut entries = async_fs::read_dir(&self.repository_path)
             .await
@@ -159,7 +345,7 @@ impl SqlRepository {
             if path.is_dir() {
                 if let Some(name) = path.file_name() {
                     let name_str = name.to_string_lossy();
-                    if Self::should_include_structured_directory(&name_str, environments) {
+                    if include_directory(&name_str) {
                         directories.push((name_str.to_string(), path));
                     }
                 }
@@ -171,9 +357,9 @@ impl SqlRepository {
 
         let mut sql_files = Vec::new();
 
-        // Collect SQL files from each directory in order
+        // Collect SQL files from each directory (including nested subdirectories) in order
         for (_, dir_path) in directories {
-            let mut dir_files = self.collect_sql_files_from_directory(&dir_path).await?;
+            let mut dir_files = Self::collect_sql_files_recursive(&dir_path)?;
             sql_files.append(&mut dir_files);
         }
 
@@ -246,6 +432,34 @@ impl SqlRepository {
 
         Ok(sql_files)
     }
+
+    /// Collect all SQL files below a directory, sorted by path
+    fn collect_sql_files_recursive(directory: &Path) -> SqlRepositoryResult<Vec<PathBuf>> {
+        let mut sql_files = Vec::new();
+
+        for entry in WalkDir::new(directory).follow_links(false) {
+            let entry = entry.map_err(|e| {
+                DatabaseError::Config(format!(
+                    "Failed to read directory {}: {}",
+                    directory.display(),
+                    e
+                ))
+            })?;
+            let path = entry.path();
+            if entry.file_type().is_file()
+                && path
+                    .extension()
+                    .map_or(false, |ext| ext.to_string_lossy().to_lowercase() == "sql")
+            {
+                sql_files.push(path.to_path_buf());
+            }
+        }
+
+        // Sort by path for consistent ordering
+        sql_files.sort();
+
+        Ok(sql_files)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,219 @@
+//! # Statement Errors
+//!
+//! Structured errors for a failing statement of a SQL script. Besides the
+//! server message, a [`StatementError`] carries where the statement came from
+//! (file and line), its text, the `DETAIL`, `HINT` and `CONTEXT` fields of the
+//! server error, and the error position mapped back to a line and column of the
+//! source file. Its `Display` renders all of that with a source snippet; library
+//! callers can render the fields themselves instead.
+//!
+//! ```text
+//! Statement 2 at db/0_schema/02_views.sql:7 failed: function lower(integer) does not exist
+//!   --> db/0_schema/02_views.sql:8:8
+//!    |
+//!  8 | SELECT lower(id)
+//!    |        ^
+//!   HINT: No function matches the given name and argument types. ...
+//! ```
+
+use crate::psql_script::location;
+use std::fmt;
+use std::path::PathBuf;
+use tokio_postgres::error::ErrorPosition;
+
+/// Line and column in a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePosition {
+    /// 1-based line in the file
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+}
+
+/// A statement of a multi-statement script failed on the server
+#[derive(Debug)]
+pub struct StatementError {
+    /// 1-based position of the statement in the script
+    pub index: usize,
+    /// File the statement comes from, if it was read from a file
+    pub file: Option<PathBuf>,
+    /// 1-based line the statement starts on
+    pub line: usize,
+    /// Text of the failing statement, as sent to the server
+    pub statement: String,
+    /// Server error message
+    pub message: String,
+    /// Server `DETAIL` field
+    pub detail: Option<String>,
+    /// Server `HINT` field
+    pub hint: Option<String>,
+    /// Server `CONTEXT` field, e.g. the PL/pgSQL line that raised the error
+    pub context: Option<String>,
+    /// Where in the source file the server located the error
+    pub position: Option<SourcePosition>,
+    /// Underlying driver error
+    pub source: tokio_postgres::Error,
+}
+
+impl StatementError {
+    /// Build the error for `statement`, starting on `line` of `file`, failing with `source`
+    #[must_use]
+    pub fn new(
+        index: usize,
+        file: Option<PathBuf>,
+        line: usize,
+        statement: String,
+        source: tokio_postgres::Error,
+    ) -> Self {
+        let db_error = source.as_db_error();
+        let position = match db_error.and_then(|db| db.position()) {
+            Some(ErrorPosition::Original(position)) => source_position(&statement, line, *position),
+            _ => None,
+        };
+
+        Self {
+            index,
+            file,
+            line,
+            message: db_error.map_or_else(|| source.to_string(), |db| db.message().to_string()),
+            detail: db_error.and_then(|db| db.detail()).map(str::to_string),
+            hint: db_error.and_then(|db| db.hint()).map(str::to_string),
+            context: db_error.and_then(|db| db.where_()).map(str::to_string),
+            position,
+            statement,
+            source,
+        }
+    }
+
+    /// `file:line` (or `line N`) where the statement starts
+    #[must_use]
+    pub fn location(&self) -> String {
+        location(self.file.as_deref(), self.line)
+    }
+
+    /// The source line at the error position, with a caret under the column
+    #[must_use]
+    pub fn snippet(&self) -> Option<String> {
+        snippet(&self.statement, self.line, self.position?)
+    }
+}
+
+impl fmt::Display for StatementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Statement {} at {} failed: {}",
+            self.index,
+            self.location(),
+            self.message
+        )?;
+
+        match (self.position, self.snippet()) {
+            (Some(position), Some(snippet)) => {
+                write!(
+                    f,
+                    "\n  --> {}:{}",
+                    location(self.file.as_deref(), position.line),
+                    position.column
+                )?;
+                write!(f, "\n{snippet}")?;
+            }
+            _ => write!(f, "\n  {}", self.statement)?,
+        }
+
+        for (label, value) in [
+            ("DETAIL", &self.detail),
+            ("HINT", &self.hint),
+            ("CONTEXT", &self.context),
+        ] {
+            if let Some(value) = value {
+                write!(f, "\n  {label}: {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for StatementError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Map a 1-based character `position` in `statement` to a position in its file
+///
+/// `start_line` is the line the statement starts on.
+#[must_use]
+pub fn source_position(
+    statement: &str,
+    start_line: usize,
+    position: u32,
+) -> Option<SourcePosition> {
+    let offset = usize::try_from(position).ok()?.checked_sub(1)?;
+    let prefix: String = statement.chars().take(offset).collect();
+    if prefix.chars().count() < offset {
+        return None;
+    }
+
+    let line_prefix = prefix.rsplit('\n').next().unwrap_or_default();
+    Some(SourcePosition {
+        line: start_line + prefix.matches('\n').count(),
+        column: line_prefix.chars().count() + 1,
+    })
+}
+
+/// Render the line of `statement` at `position` with a gutter and a caret
+fn snippet(statement: &str, start_line: usize, position: SourcePosition) -> Option<String> {
+    let text = statement
+        .lines()
+        .nth(position.line.checked_sub(start_line)?)?;
+    let gutter = position.line.to_string();
+    let padding = " ".repeat(gutter.len());
+    // Keep tabs so the caret lines up with the source line
+    let indent: String = text
+        .chars()
+        .take(position.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    Some(format!(
+        " {padding} |\n {gutter} | {text}\n {padding} | {indent}^"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_position_maps_to_file_lines() {
+        let statement = "SELECT a,\n       é, lower(1)\nFROM t";
+        // `lower` is the 21st character
+        assert_eq!(
+            source_position(statement, 10, 21),
+            Some(SourcePosition {
+                line: 11,
+                column: 11
+            })
+        );
+        assert_eq!(
+            source_position(statement, 10, 1),
+            Some(SourcePosition {
+                line: 10,
+                column: 1
+            })
+        );
+        assert_eq!(source_position(statement, 10, 0), None);
+        assert_eq!(source_position(statement, 10, 500), None);
+    }
+
+    #[test]
+    fn test_snippet_points_at_column() {
+        let statement = "SELECT a,\n\tlower(1)\nFROM t";
+        let position = source_position(statement, 3, 12).unwrap();
+        assert_eq!(
+            snippet(statement, 3, position).unwrap(),
+            "   |\n 4 | \tlower(1)\n   | \t^"
+        );
+    }
+}
@@ -3,20 +3,83 @@ use crate::config::DatabaseConfig;
 /// Template management functionality for `DBFast`
 ///
 /// Templates are created from SQL files and can be used for fast database cloning.
-use crate::database::{DatabaseError, DatabasePool};
-use crate::scanner::FileScanner;
+use crate::database::{AdvisoryLock, DatabaseError, DatabasePool};
+use crate::fingerprint;
+use crate::profile::BuildProfiler;
+use crate::scanner::{FileScanner, ScannedFile, ScannerError};
+use crate::sql_repository::SqlRepository;
+use chrono::Utc;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use xxhash_rust::xxh3::xxh3_64;
 
 /// Template management result type
 pub type TemplateResult<T> = Result<T, DatabaseError>;
 
+/// Number of files (and statements) listed after a build
+const SLOWEST_LISTED: usize = 10;
+
+/// One layer of a layered template
+///
+/// Layers are stacked: the bottom layer is built from scratch and every layer
+/// above it is cloned from the layer below before its own files are applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateLayer {
+    /// Layer name, taken from the repository directory (e.g. `0_schema`, `2_seed_backend`)
+    pub name: String,
+    /// SQL files applied when building this layer, in execution order
+    pub sql_files: Vec<PathBuf>,
+}
+
+impl TemplateLayer {
+    /// Group SQL files into layers
+    ///
+    /// Each seed directory becomes its own layer; consecutive non-seed
+    /// directories (schema, migrations, ...) are merged into one layer.
+    /// File order is preserved.
+    ///
+    /// # Arguments
+    /// * `repository` - Repository the files were discovered in
+    /// * `sql_files` - SQL files in execution order
+    #[must_use]
+    pub fn group_files(repository: &SqlRepository, sql_files: &[PathBuf]) -> Vec<Self> {
+        let mut layers: Vec<Self> = Vec::new();
+        let mut current_key: Option<String> = None;
+
+        for sql_file in sql_files {
+            let directory = repository.top_level_directory(sql_file);
+            let key = match &directory {
+                Some(dir) if SqlRepository::is_seed_directory(dir) => dir.clone(),
+                _ => String::new(),
+            };
+
+            match layers.last_mut() {
+                Some(layer) if current_key.as_deref() == Some(key.as_str()) => {
+                    layer.sql_files.push(sql_file.clone());
+                }
+                _ => {
+                    layers.push(Self {
+                        name: directory.unwrap_or_else(|| "schema".to_string()),
+                        sql_files: vec![sql_file.clone()],
+                    });
+                    current_key = Some(key);
+                }
+            }
+        }
+
+        layers
+    }
+}
+
 /// Manager for database template operations
 #[derive(Clone)]
 pub struct TemplateManager {
     pool: DatabasePool,
     db_config: DatabaseConfig,
     change_detector: Option<ChangeDetector>,
+    profile_statements: bool,
+    build_timeout: Option<Duration>,
 }
 
 impl TemplateManager {
@@ -31,6 +94,8 @@ impl TemplateManager {
             pool,
             db_config,
             change_detector: None,
+            profile_statements: false,
+            build_timeout: None,
         }
     }
 
@@ -50,9 +115,31 @@ impl TemplateManager {
             pool,
             db_config,
             change_detector: Some(ChangeDetector::new(root_path)),
+            profile_statements: false,
+            build_timeout: None,
         }
     }
 
+    /// Time every statement of a build, not only every file
+    ///
+    /// Statement timings are listed after the build and stored in its report.
+    #[must_use]
+    pub const fn with_statement_profiling(mut self, enabled: bool) -> Self {
+        self.profile_statements = enabled;
+        self
+    }
+
+    /// Cancel builds that take longer than `timeout` to apply their SQL files
+    ///
+    /// A build past its timeout has the sessions of the database being built
+    /// terminated, which stops native and psql execution alike, and fails with
+    /// [`DatabaseError::BuildTimeout`]; the previous template is kept.
+    #[must_use]
+    pub const fn with_build_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.build_timeout = timeout;
+        self
+    }
+
     /// Check if this template manager has change detection capabilities
     #[must_use]
     pub const fn has_change_detection(&self) -> bool {
@@ -85,67 +172,123 @@ impl TemplateManager {
         sql_files: &[P],
     ) -> TemplateResult<()> {
         let start = Instant::now();
+        let started_at = Utc::now();
+        let profiler = BuildProfiler::new(self.profile_statements);
+        let deadline = self.build_deadline();
 
         // Step 1: Create the template database using admin connection
         self.pool.create_database(template_name).await?;
         println!("📝 Created template database: {template_name}");
 
         // Step 2: Execute SQL files in order
-        // Create connection pool for the template database
-        let template_pool = DatabasePool::new_for_database(&self.db_config, template_name)
-            .await
-            .map_err(|e| {
-                DatabaseError::Config(format!(
-                    "Failed to connect to template database '{template_name}': {e}"
-                ))
-            })?;
+        self.apply_sql_files(template_name, sql_files, &profiler, deadline)
+            .await?;
 
-        // Read and concatenate all SQL files
-        let mut concatenated_sql = String::new();
-        for (i, sql_file) in sql_files.iter().enumerate() {
-            let file_path = sql_file.as_ref();
-            println!("📄 Reading SQL file {}: {}", i + 1, file_path.display());
+        let duration = start.elapsed();
+        println!(
+            "✅ Template '{template_name}' created successfully in {}ms",
+            duration.as_millis()
+        );
+        println!("📊 Executed {} SQL files", sql_files.len());
+        self.report_build(template_name, &profiler, started_at, duration)
+            .await;
 
-            // Read the SQL file content
-            let sql_content = tokio::fs::read_to_string(file_path).await.map_err(|e| {
-                DatabaseError::Config(format!(
-                    "Failed to read SQL file {}: {e}",
-                    file_path.display()
-                ))
-            })?;
+        Ok(())
+    }
+
+    /// When a build starting now has to be done by, if it is limited
+    fn build_deadline(&self) -> Option<Instant> {
+        self.build_timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    /// Execute SQL files in order against an existing database, timed by `profiler`
+    ///
+    /// Past `deadline` the database's sessions are terminated, cancelling the build.
+    async fn apply_sql_files<P: AsRef<Path> + Send + Sync>(
+        &self,
+        database_name: &str,
+        sql_files: &[P],
+        profiler: &BuildProfiler,
+        deadline: Option<Instant>,
+    ) -> TemplateResult<()> {
+        // Create connection pool for the target database
+        let template_pool = DatabasePool::new_for_database_with_pool_config(
+            &self.db_config,
+            database_name,
+            self.pool.pool_config(),
+        )
+        .await
+        .map_err(|e| {
+            DatabaseError::Config(format!(
+                "Failed to connect to template database '{database_name}': {e}"
+            ))
+        })?
+        .with_variables(self.pool.variables().clone())
+        .with_profiler(profiler.clone());
 
-            // Add file separator comment and content
-            concatenated_sql.push_str(&format!("\n-- File: {}\n", file_path.display()));
-            concatenated_sql.push_str(&sql_content);
-            concatenated_sql.push('\n');
+        for (i, sql_file) in sql_files.iter().enumerate() {
+            println!("📄 SQL file {}: {}", i + 1, sql_file.as_ref().display());
         }
 
-        // Execute all SQL files in a single transaction
+        // Execute all SQL files, in a single transaction unless some statements cannot run in one
         println!(
-            "🔄 Executing {} SQL files in a single transaction (multi-statement parsing: {})",
+            "🔄 Executing {} SQL files (multi-statement parsing: {})",
             sql_files.len(),
             self.db_config.allow_multi_statement
         );
-        template_pool
-            .execute_sql_content_with_config(
-                &concatenated_sql,
-                self.db_config.allow_multi_statement,
-            )
-            .await
-            .map_err(|e| {
-                DatabaseError::Config(format!("Failed to execute concatenated SQL files: {e}"))
-            })?;
+        let watchdog = deadline.map(|deadline| self.spawn_build_watchdog(database_name, deadline));
+        let result = template_pool
+            .execute_sql_files(sql_files, self.db_config.allow_multi_statement)
+            .await;
+        if let Some(watchdog) = watchdog {
+            watchdog.abort();
+        }
+        let report = match (result, deadline) {
+            (Err(_), Some(deadline)) if Instant::now() >= deadline => {
+                return Err(DatabaseError::BuildTimeout {
+                    database: database_name.to_string(),
+                    timeout_ms: self.build_timeout.map_or(0, |timeout| {
+                        u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX)
+                    }),
+                });
+            }
+            (result, _) => result?,
+        };
+        profiler.record_execution(&report);
 
-        let duration = start.elapsed();
-        println!(
-            "✅ Template '{template_name}' created successfully in {}ms",
-            duration.as_millis()
-        );
-        println!("📊 Executed {} SQL files", sql_files.len());
+        if !report.is_single_transaction() {
+            println!("🔀 Transaction boundaries:");
+            for segment in &report.segments {
+                println!("   ▸ {segment}");
+            }
+        }
+        for skipped in &report.skipped {
+            let file = skipped.file.as_ref().map_or_else(
+                || "<inline SQL>".to_string(),
+                |file| file.display().to_string(),
+            );
+            println!("⏭️  Skipped {file}: {}", skipped.reason);
+        }
 
         Ok(())
     }
 
+    /// Terminate the sessions of `database_name` once `deadline` has passed
+    ///
+    /// Runs as its own task; native and psql builds both wait on the server
+    /// without blocking the runtime, so it fires on current-thread runtimes too.
+    fn spawn_build_watchdog(&self, database_name: &str, deadline: Instant) -> JoinHandle<()> {
+        let pool = self.pool.clone();
+        let database_name = database_name.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            println!("⏱️  Build of '{database_name}' exceeded its build timeout, cancelling");
+            if let Err(e) = pool.terminate_connections(&database_name).await {
+                println!("⚠️  Failed to cancel build of '{database_name}': {e}");
+            }
+        })
+    }
+
     /// Check if a template exists
     ///
     /// # Arguments
@@ -219,23 +362,8 @@ impl TemplateManager {
         self.create_template(template_name, sql_files).await?;
 
         // If we have change detection, store metadata
-        if let Some(change_detector) = &self.change_detector {
-            // Get the root path from change detector and scan files
-            let scanner = FileScanner::new(change_detector.root_path());
-            let scanned_files = scanner.scan().map_err(|e| {
-                DatabaseError::Config(format!("Failed to scan files for change tracking: {e}"))
-            })?;
-
-            // Store metadata for change detection
-            change_detector
-                .store_template_metadata(template_name, &scanned_files)
-                .await
-                .map_err(|e| {
-                    DatabaseError::Config(format!("Failed to store change detection metadata: {e}"))
-                })?;
-
-            println!("📊 Change detection metadata stored for template: {template_name}");
-        }
+        self.store_change_metadata(template_name, sql_files, None)
+            .await?;
 
         Ok(())
     }
@@ -263,8 +391,402 @@ impl TemplateManager {
         }
     }
 
+    /// Check if a template built from the given SQL files needs rebuilding
+    ///
+    /// Only the given files are compared, so templates built from a subset of the
+    /// repository (one environment, schema only, ...) are tracked independently.
+    ///
+    /// # Arguments
+    /// * `template_name` - Name of the template to check
+    /// * `sql_files` - SQL files the template is built from
+    pub async fn template_needs_rebuild_for_files<P: AsRef<Path> + Send + Sync>(
+        &self,
+        template_name: &str,
+        sql_files: &[P],
+    ) -> TemplateResult<bool> {
+        let Some(change_detector) = &self.change_detector else {
+            // Without change detection, always assume rebuild is needed
+            return Ok(true);
+        };
+
+        let current_files = self.scan_inputs(sql_files).map_err(|e| {
+            DatabaseError::Config(format!("Failed to scan files for change detection: {e}"))
+        })?;
+
+        change_detector
+            .template_needs_rebuild_for_files(template_name, &current_files)
+            .await
+            .map_err(|e| {
+                DatabaseError::Config(format!("Failed to check if template needs rebuild: {e}"))
+            })
+    }
+
+    /// Build the template name for an environment/seed combination
+    ///
+    /// Each combination gets its own template (and change detection metadata),
+    /// e.g. `myapp_template__local__seeds`. Without environment and seeds the
+    /// base name is returned unchanged.
+    ///
+    /// # Arguments
+    /// * `base_name` - Template name from the configuration
+    /// * `environment` - Optional environment name
+    /// * `with_seeds` - Whether the template includes seed data
+    ///
+    /// # Errors
+    /// Returns `DatabaseError::Config` if the environment name contains anything
+    /// but lowercase letters, digits and underscores, which could not be used
+    /// in a database name as is
+    pub fn variant_template_name(
+        base_name: &str,
+        environment: Option<&str>,
+        with_seeds: bool,
+    ) -> TemplateResult<String> {
+        let mut name = base_name.to_string();
+        if let Some(environment) = environment {
+            if environment.is_empty()
+                || !environment
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+            {
+                return Err(DatabaseError::Config(format!(
+                    "Invalid environment name '{environment}': environment names become part of \
+                     template database names, so they can only contain lowercase letters, \
+                     digits and underscores"
+                )));
+            }
+            name.push_str("__");
+            name.push_str(environment);
+        }
+        if with_seeds {
+            name.push_str("__seeds");
+        }
+        Ok(name)
+    }
+
+    /// Database name used for a layer of a layered template
+    ///
+    /// The top layer is the template itself so it can be cloned directly;
+    /// lower layers are named `<template>__layer<index>`.
+    #[must_use]
+    pub fn layer_database_name(template_name: &str, index: usize, layer_count: usize) -> String {
+        if index + 1 >= layer_count {
+            template_name.to_string()
+        } else {
+            format!("{template_name}__layer{index}")
+        }
+    }
+
+    /// Smart layered template creation - only rebuilds the layers that changed
+    ///
+    /// The first stale layer (missing database, changed files, or rebuilt base) and
+    /// every layer above it are rebuilt; unchanged layers below it are kept. Editing
+    /// one seed directory therefore skips the schema replay entirely.
+    ///
+    /// Like [`Self::smart_create_template`], the rebuild runs under the template's
+    /// advisory build lock. In fingerprint mode the use of the template is recorded
+    /// while that lock is held.
+    ///
+    /// # Arguments
+    /// * `template_name` - Name of the final (top) template database
+    /// * `layers` - Layers in build order, see [`TemplateLayer::group_files`]
+    ///
+    /// # Returns
+    /// `true` if any layer was rebuilt, `false` if the template was up to date
+    pub async fn smart_create_layered_template(
+        &self,
+        template_name: &str,
+        layers: &[TemplateLayer],
+    ) -> TemplateResult<bool> {
+        if layers.is_empty() {
+            return Err(DatabaseError::Config(format!(
+                "Cannot build template '{template_name}' without any layers"
+            )));
+        }
+
+        let fingerprints = self.layer_fingerprints(layers)?;
+        let layer_names = Self::named_layers(template_name, layers.len(), fingerprints.as_deref());
+
+        self.rebuild_stale_layers(template_name, &layer_names, layers, fingerprints.as_deref())
+            .await
+    }
+
+    /// Physical database names of a layered template's layers, bottom to top
+    ///
+    /// In fingerprint mode each name carries the fingerprint of all files up to
+    /// and including that layer; the last name is the template to clone from.
+    pub fn layer_database_names(
+        &self,
+        template_name: &str,
+        layers: &[TemplateLayer],
+    ) -> TemplateResult<Vec<String>> {
+        let fingerprints = self.layer_fingerprints(layers)?;
+        Ok(Self::named_layers(
+            template_name,
+            layers.len(),
+            fingerprints.as_deref(),
+        ))
+    }
+
+    /// Append a fingerprint to a database name
+    #[must_use]
+    pub fn fingerprinted_database_name(database_name: &str, fingerprint: &str) -> String {
+        format!("{database_name}_{fingerprint}")
+    }
+
+    fn named_layers(
+        template_name: &str,
+        layer_count: usize,
+        fingerprints: Option<&[String]>,
+    ) -> Vec<String> {
+        (0..layer_count)
+            .map(|index| {
+                let name = Self::layer_database_name(template_name, index, layer_count);
+                match fingerprints {
+                    Some(fingerprints) => {
+                        Self::fingerprinted_database_name(&name, &fingerprints[index])
+                    }
+                    None => name,
+                }
+            })
+            .collect()
+    }
+
+    /// Cumulative fingerprint per layer, or `None` when fingerprint mode is off
+    fn layer_fingerprints(&self, layers: &[TemplateLayer]) -> TemplateResult<Option<Vec<String>>> {
+        let Some(change_detector) = &self.change_detector else {
+            return Ok(None);
+        };
+        if !self.db_config.fingerprint_templates {
+            return Ok(None);
+        }
+
+        let mut scanned_files = Vec::new();
+        let mut fingerprints = Vec::with_capacity(layers.len());
+        for layer in layers {
+            scanned_files.extend(self.scan_inputs(&layer.sql_files).map_err(|e| {
+                DatabaseError::Config(format!("Failed to scan files for fingerprinting: {e}"))
+            })?);
+            fingerprints.push(change_detector.fingerprint(&scanned_files));
+        }
+        Ok(Some(fingerprints))
+    }
+
+    /// Drop fingerprinted templates that are no longer needed
+    ///
+    /// A fingerprint is dropped when it has not been used for longer than
+    /// `max_age`, or when it is not among the `keep` most recently used
+    /// fingerprints of its template. Layer databases still used by a kept
+    /// fingerprint survive.
+    ///
+    /// # Returns
+    /// Names of the dropped databases (or the ones that would be dropped with `dry_run`)
+    pub async fn gc_fingerprinted_templates(
+        &self,
+        max_age: Option<chrono::Duration>,
+        keep: Option<usize>,
+        dry_run: bool,
+    ) -> TemplateResult<Vec<String>> {
+        let Some(change_detector) = &self.change_detector else {
+            return Err(DatabaseError::Config(
+                "Template garbage collection requires change detection".to_string(),
+            ));
+        };
+
+        let registry = change_detector.fingerprint_registry();
+        let entries = registry.load().await.map_err(|e| {
+            DatabaseError::Config(format!("Failed to load template fingerprints: {e}"))
+        })?;
+        let expired = fingerprint::select_expired(&entries, chrono::Utc::now(), max_age, keep);
+        let databases = fingerprint::databases_to_drop(&entries, &expired);
+
+        if dry_run {
+            return Ok(databases);
+        }
+
+        for database in &databases {
+            self.drop_template(database).await?;
+            change_detector
+                .remove_template_metadata(database)
+                .await
+                .map_err(|e| {
+                    DatabaseError::Config(format!(
+                        "Failed to remove metadata for '{database}': {e}"
+                    ))
+                })?;
+        }
+
+        let remaining: Vec<_> = entries
+            .into_iter()
+            .filter(|entry| !expired.contains(entry))
+            .collect();
+        registry.save(&remaining).await.map_err(|e| {
+            DatabaseError::Config(format!("Failed to save template fingerprints: {e}"))
+        })?;
+
+        Ok(databases)
+    }
+
+    /// Rebuild every layer from the lowest stale one upward, under the build lock
+    ///
+    /// In fingerprint mode the use of the template is recorded before the lock
+    /// is released, so concurrent builds of the same template do not overwrite
+    /// each other's registry updates.
+    async fn rebuild_stale_layers(
+        &self,
+        template_name: &str,
+        layer_names: &[String],
+        layers: &[TemplateLayer],
+        fingerprints: Option<&[String]>,
+    ) -> TemplateResult<bool> {
+        let physical_name = &layer_names[layer_names.len() - 1];
+        let up_to_date = self.first_stale_layer(layer_names, layers).await?.is_none();
+        if up_to_date && fingerprints.is_none() {
+            println!("⏩ Template '{physical_name}' is up to date, skipping creation");
+            return Ok(false);
+        }
+
+        // Another process may be rebuilding the same template; wait for it and
+        // check again instead of racing it
+        let lock = self.acquire_build_lock(template_name).await?;
+        let Some(first_stale) = self.first_stale_layer(layer_names, layers).await? else {
+            if up_to_date {
+                println!("⏩ Template '{physical_name}' is up to date, skipping creation");
+            } else {
+                println!(
+                    "⏩ Template '{physical_name}' was rebuilt by another process, skipping creation"
+                );
+            }
+            self.record_fingerprint_use(template_name, layer_names, fingerprints)
+                .await?;
+            lock.release().await?;
+            return Ok(false);
+        };
+
+        if first_stale > 0 {
+            println!(
+                "♻️  Reusing {first_stale} unchanged layer(s), rebuilding from layer '{}'",
+                layers[first_stale].name
+            );
+        }
+
+        let build_start = Instant::now();
+        let started_at = Utc::now();
+        let profiler = BuildProfiler::new(self.profile_statements);
+        let deadline = self.build_deadline();
+        for index in first_stale..layers.len() {
+            let layer = &layers[index];
+            let layer_name = &layer_names[index];
+            let base_layer = index
+                .checked_sub(1)
+                .map(|below| layer_names[below].as_str());
+            let start = Instant::now();
+
+            self.build_and_swap(
+                layer_name,
+                base_layer,
+                &layer.sql_files,
+                &profiler,
+                deadline,
+            )
+            .await?;
+            self.store_change_metadata(layer_name, &layer.sql_files, base_layer)
+                .await?;
+
+            println!(
+                "✅ Layer '{}' ({} files) built in {}ms",
+                layer.name,
+                layer.sql_files.len(),
+                start.elapsed().as_millis()
+            );
+        }
+        self.report_build(template_name, &profiler, started_at, build_start.elapsed())
+            .await;
+        self.record_fingerprint_use(template_name, layer_names, fingerprints)
+            .await?;
+
+        lock.release().await?;
+        Ok(true)
+    }
+
+    /// Record the use of a fingerprinted template; a no-op outside fingerprint mode
+    async fn record_fingerprint_use(
+        &self,
+        template_name: &str,
+        layer_names: &[String],
+        fingerprints: Option<&[String]>,
+    ) -> TemplateResult<()> {
+        let (Some(fingerprints), Some(change_detector)) = (fingerprints, &self.change_detector)
+        else {
+            return Ok(());
+        };
+
+        let (database, lower_layers) = layer_names
+            .split_last()
+            .expect("layer names are never empty");
+        change_detector
+            .fingerprint_registry()
+            .record_use(
+                template_name,
+                database,
+                &fingerprints[fingerprints.len() - 1],
+                lower_layers,
+            )
+            .await
+            .map_err(|e| {
+                DatabaseError::Config(format!("Failed to record template fingerprint: {e}"))
+            })
+    }
+
+    /// Index of the lowest layer that is missing or out of date
+    async fn first_stale_layer(
+        &self,
+        layer_names: &[String],
+        layers: &[TemplateLayer],
+    ) -> TemplateResult<Option<usize>> {
+        for (index, layer) in layers.iter().enumerate() {
+            let base_layer = index
+                .checked_sub(1)
+                .map(|below| layer_names[below].as_str());
+            if !self.template_exists(&layer_names[index]).await?
+                || self
+                    .layer_needs_rebuild(&layer_names[index], &layer.sql_files, base_layer)
+                    .await?
+            {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check if one layer needs rebuilding (always true without change detection)
+    async fn layer_needs_rebuild(
+        &self,
+        layer_name: &str,
+        sql_files: &[PathBuf],
+        base_layer: Option<&str>,
+    ) -> TemplateResult<bool> {
+        let Some(change_detector) = &self.change_detector else {
+            return Ok(true);
+        };
+
+        let current_files = self.scan_inputs(sql_files).map_err(|e| {
+            DatabaseError::Config(format!("Failed to scan files for change detection: {e}"))
+        })?;
+
+        change_detector
+            .layer_needs_rebuild(layer_name, &current_files, base_layer)
+            .await
+            .map_err(|e| {
+                DatabaseError::Config(format!("Failed to check if layer needs rebuild: {e}"))
+            })
+    }
+
     /// Smart template creation - only creates if template doesn't exist or files have changed
     ///
+    /// Rebuilds are serialized across processes with an advisory lock keyed on the
+    /// template name; a process that had to wait re-checks the template and skips
+    /// the build if another process already brought it up to date.
+    ///
     /// # Arguments
     /// * `template_name` - Name for the template database
     /// * `sql_files` - Array of SQL file paths to execute in order
@@ -276,31 +798,308 @@ impl TemplateManager {
         template_name: &str,
         sql_files: &[P],
     ) -> TemplateResult<bool> {
-        // Check if template exists and needs rebuilding
-        let template_exists = self.template_exists(template_name).await?;
+        if !self.needs_build(template_name, sql_files).await? {
+            println!("⏩ Template '{template_name}' is up to date, skipping creation");
+            return Ok(false);
+        }
+
+        // Another process may be rebuilding the same template; wait for it and
+        // check again instead of racing it
+        let lock = self.acquire_build_lock(template_name).await?;
+        if !self.needs_build(template_name, sql_files).await? {
+            println!(
+                "⏩ Template '{template_name}' was rebuilt by another process, skipping creation"
+            );
+            lock.release().await?;
+            return Ok(false);
+        }
+
+        if self.template_exists(template_name).await? {
+            println!("🔄 Template '{template_name}' needs rebuilding due to file changes");
+        }
+
+        // Build the new generation next to the current one and swap it in, so
+        // concurrent clones never see a missing template
+        let start = Instant::now();
+        let started_at = Utc::now();
+        let profiler = BuildProfiler::new(self.profile_statements);
+        self.build_and_swap(
+            template_name,
+            None,
+            sql_files,
+            &profiler,
+            self.build_deadline(),
+        )
+        .await?;
+        self.store_change_metadata(template_name, sql_files, None)
+            .await?;
+        self.report_build(template_name, &profiler, started_at, start.elapsed())
+            .await;
+        lock.release().await?;
+
+        Ok(true)
+    }
+
+    /// Check if a template is missing or out of date
+    async fn needs_build<P: AsRef<Path> + Send + Sync>(
+        &self,
+        template_name: &str,
+        sql_files: &[P],
+    ) -> TemplateResult<bool> {
+        if !self.template_exists(template_name).await? {
+            return Ok(true);
+        }
+        self.template_needs_rebuild_for_files(template_name, sql_files)
+            .await
+    }
+
+    /// Advisory lock key for building a template
+    ///
+    /// Every process building the same template on the same server derives the
+    /// same key, so builds of one template are serialized across processes.
+    #[must_use]
+    pub fn template_lock_key(template_name: &str) -> i64 {
+        let hash = xxh3_64(format!("dbfast:template:{template_name}").as_bytes());
+        i64::from_ne_bytes(hash.to_ne_bytes())
+    }
+
+    /// Take the server-side build lock for a template
+    ///
+    /// Waits up to `template_lock_timeout_secs` while another process holds it.
+    async fn acquire_build_lock(&self, template_name: &str) -> TemplateResult<AdvisoryLock> {
+        let timeout = Duration::from_secs(self.db_config.template_lock_timeout_secs);
+        let start = Instant::now();
+        let lock = self
+            .pool
+            .acquire_advisory_lock(Self::template_lock_key(template_name), timeout)
+            .await
+            .map_err(|e| {
+                DatabaseError::Config(format!(
+                    "Failed to acquire build lock for template '{template_name}': {e}"
+                ))
+            })?;
+
+        let waited = start.elapsed();
+        if waited >= Duration::from_secs(1) {
+            println!(
+                "🔒 Waited {}ms for another process building '{template_name}'",
+                waited.as_millis()
+            );
+        }
+        Ok(lock)
+    }
+
+    /// Name of the database a new template generation is built in
+    #[must_use]
+    pub fn staging_database_name(template_name: &str) -> String {
+        Self::suffixed_database_name(template_name, "__next")
+    }
+
+    /// Name the previous template generation is moved to during a swap
+    #[must_use]
+    pub fn retired_database_name(template_name: &str) -> String {
+        Self::suffixed_database_name(template_name, "__old")
+    }
+
+    /// Append a suffix while staying within `PostgreSQL`'s 63 byte identifier limit
+    ///
+    /// Longer names would be silently truncated by the server, which could make
+    /// the suffixed name collide with the original. Names that do not fit are
+    /// shortened and disambiguated with a hash of the full name instead.
+    fn suffixed_database_name(database_name: &str, suffix: &str) -> String {
+        const MAX_IDENTIFIER_LENGTH: usize = 63;
+
+        if database_name.len() + suffix.len() <= MAX_IDENTIFIER_LENGTH {
+            return format!("{database_name}{suffix}");
+        }
+
+        let hash = format!("{:08x}", xxh3_64(database_name.as_bytes()) >> 32);
+        let mut prefix_length = MAX_IDENTIFIER_LENGTH - suffix.len() - hash.len() - 1;
+        while !database_name.is_char_boundary(prefix_length) {
+            prefix_length -= 1;
+        }
+        format!("{}_{hash}{suffix}", &database_name[..prefix_length])
+    }
+
+    /// Build a database under its staging name and swap it in atomically
+    ///
+    /// The staging database is created from `template0` (or cloned from
+    /// `base_database`) and the SQL files are applied to it. Only if that
+    /// succeeds is it renamed to `database_name`; the previous generation is
+    /// kept under its retired name until the rename has committed. A failed
+    /// or timed out build leaves the existing database untouched.
+    async fn build_and_swap<P: AsRef<Path> + Send + Sync>(
+        &self,
+        database_name: &str,
+        base_database: Option<&str>,
+        sql_files: &[P],
+        profiler: &BuildProfiler,
+        deadline: Option<Instant>,
+    ) -> TemplateResult<()> {
+        let staging_name = Self::staging_database_name(database_name);
+        let retired_name = Self::retired_database_name(database_name);
 
-        if template_exists {
-            let needs_rebuild = self.template_needs_rebuild(template_name).await?;
+        // Leftovers from an interrupted build
+        self.pool.drop_database(&staging_name).await?;
 
-            if !needs_rebuild {
-                println!("⏩ Template '{template_name}' is up to date, skipping creation");
-                return Ok(false);
+        match base_database {
+            Some(base_database) => {
+                self.pool
+                    .create_database_from_template(&staging_name, base_database)
+                    .await?;
+                println!("📝 Cloned database '{staging_name}' from '{base_database}'");
+            }
+            None => {
+                self.pool.create_database(&staging_name).await?;
+                println!("📝 Created staging database: {staging_name}");
             }
+        }
 
-            println!("🔄 Template '{template_name}' needs rebuilding due to file changes");
+        if let Err(e) = self
+            .apply_sql_files(&staging_name, sql_files, profiler, deadline)
+            .await
+        {
+            // Sessions of a cancelled build may still be closing
+            let dropped = if matches!(e, DatabaseError::BuildTimeout { .. }) {
+                self.pool.force_drop_database(&staging_name).await
+            } else {
+                self.pool.drop_database(&staging_name).await
+            };
+            if let Err(drop_error) = dropped {
+                println!("⚠️  Failed to clean up staging database '{staging_name}': {drop_error}");
+            }
+            if self.template_exists(database_name).await.unwrap_or(false) {
+                println!("🛡️  Keeping previous '{database_name}' after failed build");
+            }
+            return Err(e);
+        }
+
+        self.pool.drop_database(&retired_name).await?;
+        self.swap_with_retry(database_name, &staging_name, &retired_name)
+            .await?;
+        println!("🔀 Swapped new generation into '{database_name}'");
 
-            // Drop existing template before recreating
-            self.drop_template(template_name).await?;
+        if let Err(e) = self.pool.drop_database(&retired_name).await {
+            println!("⚠️  Failed to drop previous generation '{retired_name}': {e}");
         }
 
-        // Create (or recreate) template with change tracking
-        if self.has_change_detection() {
-            self.create_template_with_change_tracking(template_name, sql_files)
-                .await?;
-        } else {
-            self.create_template(template_name, sql_files).await?;
+        Ok(())
+    }
+
+    /// Swap the staging database in, retrying while connections to it are still closing
+    async fn swap_with_retry(
+        &self,
+        database_name: &str,
+        staging_name: &str,
+        retired_name: &str,
+    ) -> TemplateResult<()> {
+        const MAX_ATTEMPTS: u32 = 20;
+        let mut attempt = 1;
+        loop {
+            match self
+                .pool
+                .replace_database(database_name, staging_name, retired_name)
+                .await
+            {
+                Ok(_) => return Ok(()),
+                Err(e)
+                    if attempt < MAX_ATTEMPTS
+                        && e.to_string().contains("is being accessed by other users") =>
+                {
+                    attempt += 1;
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+                Err(e) => return Err(e),
+            }
         }
+    }
 
-        Ok(true)
+    /// Print the slowest files (and statements) of a build and store its report
+    ///
+    /// The report goes next to the change detection metadata, when there is
+    /// any; failing to write it does not fail the build.
+    async fn report_build(
+        &self,
+        template_name: &str,
+        profiler: &BuildProfiler,
+        started_at: chrono::DateTime<Utc>,
+        duration: Duration,
+    ) {
+        let report = profiler.report(template_name, started_at, duration);
+        if report.files.is_empty() && report.skipped.is_empty() {
+            return;
+        }
+
+        if !report.files.is_empty() {
+            let slowest = report.slowest_files(SLOWEST_LISTED);
+            println!(
+                "🐢 Slowest {} of {} files:",
+                slowest.len(),
+                report.files.len()
+            );
+            for timing in slowest {
+                println!(
+                    "   {:>10.1}ms  {}",
+                    timing.duration_ms,
+                    timing.file.display()
+                );
+            }
+        }
+
+        if !report.statements.is_empty() {
+            let slowest = report.slowest_statements(SLOWEST_LISTED);
+            println!(
+                "🐢 Slowest {} of {} statements:",
+                slowest.len(),
+                report.statements.len()
+            );
+            for timing in slowest {
+                println!("   {:>10.1}ms  {}", timing.duration_ms, timing.location());
+            }
+        }
+
+        if let Some(change_detector) = &self.change_detector {
+            match change_detector.store_build_report(&report).await {
+                Ok(path) => println!("📈 Build report written to {}", path.display()),
+                Err(e) => println!("⚠️  Failed to write build report: {e}"),
+            }
+        }
+    }
+
+    /// Hashes of `sql_files`, plus the pool's variables when there are any
+    fn scan_inputs<P: AsRef<Path>>(
+        &self,
+        sql_files: &[P],
+    ) -> Result<Vec<ScannedFile>, ScannerError> {
+        let mut files = FileScanner::scan_files(sql_files)?;
+        if !self.pool.variables().is_empty() {
+            files.push(ScannedFile::variables(self.pool.variables()));
+        }
+        Ok(files)
+    }
+
+    /// Store change detection metadata for a freshly built database, if enabled
+    async fn store_change_metadata<P: AsRef<Path> + Send + Sync>(
+        &self,
+        database_name: &str,
+        sql_files: &[P],
+        base_database: Option<&str>,
+    ) -> TemplateResult<()> {
+        let Some(change_detector) = &self.change_detector else {
+            return Ok(());
+        };
+
+        let scanned_files = self.scan_inputs(sql_files).map_err(|e| {
+            DatabaseError::Config(format!("Failed to scan files for change tracking: {e}"))
+        })?;
+        change_detector
+            .store_layer_metadata(database_name, &scanned_files, base_database)
+            .await
+            .map_err(|e| {
+                DatabaseError::Config(format!("Failed to store change detection metadata: {e}"))
+            })?;
+
+        println!("📊 Change detection metadata stored for template: {database_name}");
+        Ok(())
     }
 }
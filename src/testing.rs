@@ -0,0 +1,268 @@
+//! # Test Databases
+//!
+//! [`TestDatabase`] gives integration tests a private, freshly cloned database:
+//! it loads `dbfast.toml`, makes sure the template is up to date, clones it under
+//! a unique name and drops the clone again when the value goes out of scope.
+//!
+//! ```rust,no_run
+//! use dbfast::testing::TestDatabase;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let db = TestDatabase::new().await?;
+//! let rows = db.pool().query("SELECT count(*) FROM tb_user", &[]).await?;
+//! println!("Testing against {}", db.name());
+//! db.cleanup().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Set `DBFAST_KEEP_TEST_DB=1` to keep clones around for inspection after a test.
+
+use crate::clone::{CloneConfig, CloneManager};
+use crate::commands::seed::ensure_template;
+use crate::config::Config;
+use crate::database::DatabasePool;
+use crate::error::{DbFastError, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Environment variable that keeps test databases instead of dropping them
+pub const KEEP_TEST_DB_ENV: &str = "DBFAST_KEEP_TEST_DB";
+
+/// Options for creating a [`TestDatabase`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestDatabaseOptions {
+    /// Config file to load; defaults to `dbfast.toml` in the current directory
+    pub config_path: Option<PathBuf>,
+    /// Environment whose file filters are used to build the template
+    pub environment: Option<String>,
+    /// Include seed data in the template
+    pub with_seeds: bool,
+    /// SQL fixture files applied to the clone, relative to the config file's directory
+    pub fixtures: Vec<PathBuf>,
+    /// TTL recorded in the clone registry, so `clones gc` collects leaked clones
+    pub ttl: Option<Duration>,
+    /// Labels recorded in the clone registry
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Default for TestDatabaseOptions {
+    fn default() -> Self {
+        Self {
+            config_path: None,
+            environment: None,
+            with_seeds: true,
+            fixtures: Vec::new(),
+            ttl: Some(Duration::from_secs(60 * 60)),
+            labels: BTreeMap::new(),
+        }
+    }
+}
+
+/// A uniquely named clone of the template, dropped when it goes out of scope
+pub struct TestDatabase {
+    name: String,
+    template: String,
+    url: String,
+    pool: DatabasePool,
+    clone_manager: CloneManager,
+    config: Config,
+    cleaned_up: bool,
+}
+
+impl TestDatabase {
+    /// Create a test database with the default options
+    pub async fn new() -> Result<Self> {
+        Self::with_options(&TestDatabaseOptions::default()).await
+    }
+
+    /// Create a test database
+    pub async fn with_options(options: &TestDatabaseOptions) -> Result<Self> {
+        let config_path = match &options.config_path {
+            Some(path) => path.clone(),
+            None => std::env::current_dir()?.join("dbfast.toml"),
+        };
+        let mut config =
+            Config::from_file(&config_path).map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to load config {}: {e}", config_path.display()),
+            })?;
+
+        // Resolve the repository relative to the config file, not the test's working directory
+        let config_dir = config_path.parent().unwrap_or_else(|| Path::new("."));
+        let repo_path = config_dir.join(&config.repository.path);
+        config.repository.path = repo_path.to_string_lossy().into_owned();
+
+        let admin_pool = DatabasePool::from_config_with_pool_config(
+            &config.database,
+            config.performance.pool_config(),
+        )
+        .await
+        .map_err(|e| DbFastError::ConfigCreationFailed {
+            message: format!("Failed to connect to database: {e}"),
+        })?;
+
+        let (template, _) = ensure_template(
+            &config,
+            &admin_pool,
+            &repo_path,
+            options.environment.as_deref(),
+            options.with_seeds,
+        )
+        .await?;
+
+        let clone_manager = CloneManager::new_with_config(
+            admin_pool.clone(),
+            CloneConfig {
+                ttl: options.ttl,
+                labels: options.labels.clone(),
+                ..config.performance.clone_config()
+            },
+        );
+        let name = unique_database_name();
+        clone_manager
+            .clone_database(&template, &name)
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to clone test database: {e}"),
+            })?;
+
+        let pool = admin_pool.for_database(&name).await.map_err(|e| {
+            DbFastError::ConfigCreationFailed {
+                message: format!("Failed to connect to test database '{name}': {e}"),
+            }
+        })?;
+        let url = pool.connection_url().unwrap_or_default();
+
+        for fixture in &options.fixtures {
+            let fixture_path = config_dir.join(fixture);
+            let result = pool
+                .execute_sql_files(std::slice::from_ref(&fixture_path), true)
+                .await;
+            if let Err(e) = result {
+                // Nothing else owns the clone yet, so don't leak it
+                let _ = clone_manager.force_drop_database(&name).await;
+                return Err(DbFastError::ConfigCreationFailed {
+                    message: format!("Failed to load fixture {}: {e}", fixture_path.display()),
+                });
+            }
+        }
+
+        info!("Created test database {} from {}", name, template);
+        Ok(Self {
+            name,
+            template,
+            url,
+            pool,
+            clone_manager,
+            config,
+            cleaned_up: false,
+        })
+    }
+
+    /// Name of the cloned database
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name of the template database the clone was created from
+    #[must_use]
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// `postgresql://` connection URL for the cloned database
+    ///
+    /// The URL contains the configured password in plain text: pass it to the
+    /// code under test, but do not log or print it.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Connection pool for the cloned database
+    #[must_use]
+    pub const fn pool(&self) -> &DatabasePool {
+        &self.pool
+    }
+
+    /// Drop the database now instead of when the value goes out of scope
+    ///
+    /// Prefer this over relying on `Drop` inside async tests: it reports errors
+    /// and does not block the runtime.
+    pub async fn cleanup(mut self) -> Result<()> {
+        self.cleaned_up = true;
+        if keep_requested() {
+            println!(
+                "📌 Keeping test database {} ({KEEP_TEST_DB_ENV})",
+                self.name
+            );
+            return Ok(());
+        }
+
+        self.clone_manager
+            .force_drop_database(&self.name)
+            .await
+            .map_err(|e| DbFastError::ConfigCreationFailed {
+                message: format!("Failed to drop test database '{}': {e}", self.name),
+            })
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        if self.cleaned_up {
+            return;
+        }
+        if keep_requested() {
+            println!(
+                "📌 Keeping test database {} ({KEEP_TEST_DB_ENV})",
+                self.name
+            );
+            return;
+        }
+
+        // Drop may run inside or outside a runtime, so drop the clone from a
+        // separate thread with its own runtime and connection.
+        let database_config = self.config.database.clone();
+        let name = self.name.clone();
+        let result = std::thread::spawn(move || -> std::result::Result<(), String> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| e.to_string())?;
+            runtime.block_on(async {
+                let pool = DatabasePool::from_config(&database_config)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let clone_config = CloneConfig {
+                    register_clones: true,
+                    ..CloneConfig::default()
+                };
+                CloneManager::new_with_config(pool, clone_config)
+                    .force_drop_database(&name)
+                    .await
+                    .map_err(|e| e.to_string())
+            })
+        })
+        .join();
+
+        match result {
+            Ok(Ok(())) => info!("Dropped test database {}", self.name),
+            Ok(Err(e)) => warn!("Failed to drop test database {}: {}", self.name, e),
+            Err(_) => warn!("Panicked while dropping test database {}", self.name),
+        }
+    }
+}
+
+/// Whether [`KEEP_TEST_DB_ENV`] asks to keep test databases
+fn keep_requested() -> bool {
+    std::env::var(KEEP_TEST_DB_ENV).is_ok_and(|value| !matches!(value.as_str(), "" | "0" | "false"))
+}
+
+/// Unique database name for a test clone
+fn unique_database_name() -> String {
+    format!("dbfast_test_{}", uuid::Uuid::new_v4().simple())
+}
@@ -0,0 +1,584 @@
+//! # TLS
+//!
+//! Encrypts connections with rustls, configured with libpq's settings. They
+//! are accepted on `[database]` and on every remote, and as query parameters of
+//! a remote's URL, which take precedence over the remote's own settings:
+//!
+//! | `sslmode`          | Encrypted                  | Server certificate checked           |
+//! |--------------------|----------------------------|--------------------------------------|
+//! | `disable`          | never                      | -                                    |
+//! | `prefer` (default) | if the server supports TLS | no                                   |
+//! | `require`          | always                     | only if a root certificate is found  |
+//! | `verify-ca`        | always                     | signed by a trusted root             |
+//! | `verify-full`      | always                     | signed by a trusted root, for the host |
+//!
+//! - `sslrootcert`: PEM file of the trusted roots, `~/.postgresql/root.crt`
+//!   when unset like libpq. `system` trusts the operating system's roots and
+//!   makes `verify-full` the default mode.
+//! - `sslcert` and `sslkey`: PEM client certificate and its key, presented when
+//!   both are set
+//!
+//! `psql`, `pg_dump` and `pg_restore` receive the same settings through
+//! `PGSSLMODE`, `PGSSLROOTCERT`, `PGSSLCERT` and `PGSSLKEY` in their own
+//! environment, see [`TlsConfig::apply_to`].
+//!
+//! ```rust
+//! use dbfast::tls::{SslMode, TlsConfig};
+//!
+//! let url = url::Url::parse("postgres://deploy@db.internal/app?sslmode=verify-full").unwrap();
+//! let tls = TlsConfig::default().with_url_parameters(&url).unwrap();
+//! assert_eq!(tls.mode(), SslMode::VerifyFull);
+//! assert_eq!(
+//!     TlsConfig::without_url_parameters(&url).as_str(),
+//!     "postgres://deploy@db.internal/app"
+//! );
+//! ```
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::warn;
+use url::Url;
+
+use tokio_postgres::tls::MakeTlsConnect;
+use tokio_postgres::Socket;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+/// `sslrootcert` value trusting the operating system's root certificates
+pub const SYSTEM_ROOTS: &str = "system";
+
+/// URL query parameters read by [`TlsConfig::with_url_parameters`]
+const URL_PARAMETERS: [&str; 4] = ["sslmode", "sslrootcert", "sslcert", "sslkey"];
+
+/// How strictly a connection uses TLS, as libpq's `sslmode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    /// Never use TLS
+    Disable,
+    /// Use TLS if the server supports it, without checking its certificate
+    #[default]
+    Prefer,
+    /// Always use TLS; check the server certificate only if a root certificate is found
+    Require,
+    /// Always use TLS and check the server certificate is signed by a trusted root
+    VerifyCa,
+    /// Like `verify-ca`, and check the certificate was issued for the host
+    VerifyFull,
+}
+
+impl SslMode {
+    /// Name of the mode, as written in `sslmode`
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Disable => "disable",
+            Self::Prefer => "prefer",
+            Self::Require => "require",
+            Self::VerifyCa => "verify-ca",
+            Self::VerifyFull => "verify-full",
+        }
+    }
+}
+
+impl fmt::Display for SslMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SslMode {
+    type Err = TlsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "disable" => Ok(Self::Disable),
+            "prefer" => Ok(Self::Prefer),
+            "require" => Ok(Self::Require),
+            "verify-ca" => Ok(Self::VerifyCa),
+            "verify-full" => Ok(Self::VerifyFull),
+            _ => Err(TlsError::InvalidMode(value.to_string())),
+        }
+    }
+}
+
+/// TLS settings that cannot be turned into a connector
+#[derive(Debug, Error)]
+pub enum TlsError {
+    /// `sslmode` has an unknown value
+    #[error("Invalid sslmode '{0}': expected disable, prefer, require, verify-ca or verify-full")]
+    InvalidMode(String),
+
+    /// A certificate or key file could not be read or parsed
+    #[error("Cannot read {}: {message}", path.display())]
+    File {
+        /// The file
+        path: PathBuf,
+        /// What went wrong
+        message: String,
+    },
+
+    /// A certificate file holds no certificate
+    #[error("No certificates found in {}", .0.display())]
+    NoCertificates(PathBuf),
+
+    /// A mode verifying the server has no root certificate to verify it against
+    #[error(
+        "sslmode={mode} needs root certificates, but {} does not exist; set sslrootcert to a PEM file, or to \"system\" for the system's trusted roots",
+        path.display()
+    )]
+    MissingRootCert {
+        /// The configured mode
+        mode: SslMode,
+        /// Where the root certificates were expected
+        path: PathBuf,
+    },
+
+    /// Only one of `sslcert` and `sslkey` is set
+    #[error("sslcert and sslkey must be set together")]
+    IncompleteClientCert,
+
+    /// rustls rejected the settings
+    #[error("TLS configuration error: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// TLS settings of a connection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// How strictly to use TLS, `prefer` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sslmode: Option<SslMode>,
+    /// PEM file of trusted root certificates, or `system`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sslrootcert: Option<PathBuf>,
+    /// PEM client certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sslcert: Option<PathBuf>,
+    /// PEM private key of the client certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sslkey: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Effective mode: the configured one, else `verify-full` for system roots and `prefer` otherwise
+    #[must_use]
+    pub fn mode(&self) -> SslMode {
+        self.sslmode.unwrap_or_else(|| {
+            if self.uses_system_roots() {
+                SslMode::VerifyFull
+            } else {
+                SslMode::Prefer
+            }
+        })
+    }
+
+    fn uses_system_roots(&self) -> bool {
+        self.sslrootcert.as_deref() == Some(Path::new(SYSTEM_ROOTS))
+    }
+
+    /// These settings, overridden by the `sslmode`, `sslrootcert`, `sslcert`
+    /// and `sslkey` query parameters of `url`
+    pub fn with_url_parameters(&self, url: &Url) -> Result<Self, TlsError> {
+        let mut tls = self.clone();
+        for (name, value) in url.query_pairs() {
+            match name.as_ref() {
+                "sslmode" => tls.sslmode = Some(value.parse()?),
+                "sslrootcert" => tls.sslrootcert = Some(PathBuf::from(value.as_ref())),
+                "sslcert" => tls.sslcert = Some(PathBuf::from(value.as_ref())),
+                "sslkey" => tls.sslkey = Some(PathBuf::from(value.as_ref())),
+                _ => {}
+            }
+        }
+        Ok(tls)
+    }
+
+    /// `url` without the query parameters read by [`Self::with_url_parameters`]
+    ///
+    /// The driver only understands `disable`, `prefer` and `require`, so the
+    /// parameters are stripped before it parses a URL.
+    #[must_use]
+    pub fn without_url_parameters(url: &Url) -> Url {
+        let remaining: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(name, _)| !URL_PARAMETERS.contains(&name.as_ref()))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+
+        let mut url = url.clone();
+        if remaining.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(remaining);
+        }
+        url
+    }
+
+    /// Add the configured settings to `url` as query parameters
+    pub fn append_to_url(&self, url: &mut Url) {
+        let settings = self.settings();
+        if settings.iter().any(|(_, value)| value.is_some()) {
+            let mut query = url.query_pairs_mut();
+            for (name, value) in settings {
+                if let Some(value) = value {
+                    query.append_pair(name, &value);
+                }
+            }
+        }
+    }
+
+    /// Pass the configured settings to a `psql`, `pg_dump` or `pg_restore`
+    /// `command`, and to no other process
+    pub fn apply_to(&self, command: &mut Command) {
+        for (name, value) in self.settings() {
+            if let Some(value) = value {
+                command.env(format!("PG{}", name.to_uppercase()), value);
+            }
+        }
+    }
+
+    fn settings(&self) -> [(&'static str, Option<String>); 4] {
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|p| p.display().to_string());
+        [
+            (
+                "sslmode",
+                self.sslmode.map(|mode| mode.as_str().to_string()),
+            ),
+            ("sslrootcert", path(&self.sslrootcert)),
+            ("sslcert", path(&self.sslcert)),
+            ("sslkey", path(&self.sslkey)),
+        ]
+    }
+
+    /// Set how the driver negotiates TLS on `config`
+    ///
+    /// Like libpq, connections through Unix sockets never use TLS.
+    pub fn apply_to_config(&self, config: &mut tokio_postgres::Config) {
+        #[cfg(unix)]
+        if config
+            .get_hosts()
+            .iter()
+            .all(|host| matches!(host, tokio_postgres::config::Host::Unix(_)))
+        {
+            config.ssl_mode(tokio_postgres::config::SslMode::Disable);
+            return;
+        }
+        config.ssl_mode(match self.mode() {
+            SslMode::Disable => tokio_postgres::config::SslMode::Disable,
+            SslMode::Prefer => tokio_postgres::config::SslMode::Prefer,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => {
+                tokio_postgres::config::SslMode::Require
+            }
+        });
+    }
+
+    /// Connector performing the TLS handshakes of a pool
+    ///
+    /// # Errors
+    /// Returns an error if a certificate or key cannot be loaded, or if the
+    /// mode verifies the server and no root certificates are found.
+    pub fn connector(&self) -> Result<MakeTlsConnector, TlsError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mode = self.mode();
+        let root_cert = self.sslrootcert.clone().or_else(default_root_cert);
+        let found = root_cert
+            .clone()
+            .filter(|path| path == Path::new(SYSTEM_ROOTS) || path.exists());
+
+        let verifier: Arc<dyn ServerCertVerifier> = match (mode, found) {
+            (SslMode::Disable | SslMode::Prefer, _) | (SslMode::Require, None) => {
+                Arc::new(NoVerification(Arc::clone(&provider)))
+            }
+            (SslMode::Require | SslMode::VerifyCa, Some(path)) => {
+                Arc::new(CaVerification(webpki_verifier(&path, &provider)?))
+            }
+            (SslMode::VerifyFull, Some(path)) => webpki_verifier(&path, &provider)?,
+            (SslMode::VerifyCa | SslMode::VerifyFull, None) => {
+                return Err(TlsError::MissingRootCert {
+                    mode,
+                    path: root_cert.unwrap_or_else(|| PathBuf::from("~/.postgresql/root.crt")),
+                });
+            }
+        };
+
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier);
+        let config = match (&self.sslcert, &self.sslkey) {
+            (Some(cert), Some(key)) => {
+                builder.with_client_auth_cert(read_certificates(cert)?, read_private_key(key)?)?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => return Err(TlsError::IncompleteClientCert),
+        };
+        Ok(MakeTlsConnector(MakeRustlsConnect::new(config)))
+    }
+}
+
+/// Connector performing the TLS handshakes of a pool, see [`TlsConfig::connector`]
+#[derive(Clone)]
+pub struct MakeTlsConnector(MakeRustlsConnect);
+
+impl MakeTlsConnect<Socket> for MakeTlsConnector {
+    type Stream = <MakeRustlsConnect as MakeTlsConnect<Socket>>::Stream;
+    type TlsConnect = <MakeRustlsConnect as MakeTlsConnect<Socket>>::TlsConnect;
+    type Error = <MakeRustlsConnect as MakeTlsConnect<Socket>>::Error;
+
+    fn make_tls_connect(&mut self, hostname: &str) -> Result<Self::TlsConnect, Self::Error> {
+        // The driver asks for a connector even for Unix sockets, which have no
+        // host name and never use TLS; any valid name will do for those
+        let hostname = if hostname.is_empty() {
+            "localhost"
+        } else {
+            hostname
+        };
+        MakeTlsConnect::<Socket>::make_tls_connect(&mut self.0, hostname)
+    }
+}
+
+/// libpq's default root certificate file
+fn default_root_cert() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let path = std::env::var_os("APPDATA")
+        .map(|dir| PathBuf::from(dir).join("postgresql").join("root.crt"));
+    #[cfg(not(windows))]
+    let path =
+        std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".postgresql").join("root.crt"));
+    path
+}
+
+/// Verifier checking server certificates against the roots in `path`
+fn webpki_verifier(
+    path: &Path,
+    provider: &Arc<CryptoProvider>,
+) -> Result<Arc<WebPkiServerVerifier>, TlsError> {
+    let mut roots = RootCertStore::empty();
+    if path == Path::new(SYSTEM_ROOTS) {
+        let native = rustls_native_certs::load_native_certs();
+        for error in native.errors {
+            warn!("Skipping system root certificates: {}", error);
+        }
+        roots.add_parsable_certificates(native.certs);
+    } else {
+        roots.add_parsable_certificates(read_certificates(path)?);
+    }
+    if roots.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_path_buf()));
+    }
+
+    WebPkiServerVerifier::builder_with_provider(Arc::new(roots), Arc::clone(provider))
+        .build()
+        .map_err(|e| TlsError::File {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|e| TlsError::File {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certificates = CertificateDer::pem_slice_iter(&read_pem(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::File {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_path_buf()));
+    }
+    Ok(certificates)
+}
+
+fn read_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    PrivateKeyDer::from_pem_slice(&read_pem(path)?).map_err(|e| TlsError::File {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+/// Accepts any server certificate, but still checks the handshake signatures
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Checks the server certificate chains to a trusted root, whatever host it names
+#[derive(Debug)]
+struct CaVerification(Arc<WebPkiServerVerifier>);
+
+impl ServerCertVerifier for CaVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match self
+            .0
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+        {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_parameters() {
+        let url = Url::parse(
+            "postgres://deploy@db/app?application_name=dbfast&sslmode=verify-ca&sslrootcert=/etc/ca.pem",
+        )
+        .unwrap();
+        let configured = TlsConfig {
+            sslmode: Some(SslMode::Require),
+            sslcert: Some(PathBuf::from("client.crt")),
+            ..TlsConfig::default()
+        };
+
+        let tls = configured.with_url_parameters(&url).unwrap();
+        assert_eq!(tls.mode(), SslMode::VerifyCa);
+        assert_eq!(tls.sslrootcert, Some(PathBuf::from("/etc/ca.pem")));
+        assert_eq!(tls.sslcert, Some(PathBuf::from("client.crt")));
+        assert_eq!(
+            TlsConfig::without_url_parameters(&url).as_str(),
+            "postgres://deploy@db/app?application_name=dbfast"
+        );
+
+        let mut appended = Url::parse("postgresql://deploy@db/app").unwrap();
+        tls.append_to_url(&mut appended);
+        assert_eq!(
+            appended.as_str(),
+            "postgresql://deploy@db/app?sslmode=verify-ca&sslrootcert=%2Fetc%2Fca.pem&sslcert=client.crt"
+        );
+
+        let invalid = Url::parse("postgres://deploy@db/app?sslmode=always").unwrap();
+        assert!(matches!(
+            TlsConfig::default().with_url_parameters(&invalid),
+            Err(TlsError::InvalidMode(_))
+        ));
+    }
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(TlsConfig::default().mode(), SslMode::Prefer);
+        let system = TlsConfig {
+            sslrootcert: Some(PathBuf::from(SYSTEM_ROOTS)),
+            ..TlsConfig::default()
+        };
+        assert_eq!(system.mode(), SslMode::VerifyFull);
+
+        for mode in ["disable", "prefer", "require", "verify-ca", "verify-full"] {
+            assert_eq!(mode.parse::<SslMode>().unwrap().to_string(), mode);
+        }
+
+        let missing = TlsConfig {
+            sslmode: Some(SslMode::VerifyFull),
+            sslrootcert: Some(PathBuf::from("/nonexistent/root.crt")),
+            ..TlsConfig::default()
+        };
+        assert!(matches!(
+            missing.connector(),
+            Err(TlsError::MissingRootCert { .. })
+        ));
+
+        let half_client_cert = TlsConfig {
+            sslcert: Some(PathBuf::from("client.crt")),
+            ..TlsConfig::default()
+        };
+        assert!(matches!(
+            half_client_cert.connector(),
+            Err(TlsError::IncompleteClientCert)
+        ));
+    }
+}
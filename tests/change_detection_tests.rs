@@ -273,3 +273,55 @@ async fn test_performance_change_detection_under_50ms() {
         duration.as_millis()
     );
 }
+
+#[tokio::test]
+async fn test_layer_needs_rebuild_when_base_layer_rebuilt() {
+    let temp_dir = TempDir::new().unwrap();
+    let sql_files = create_test_sql_files(temp_dir.path()).unwrap();
+
+    let change_detector = ChangeDetector::new(temp_dir.path().to_path_buf());
+    let schema_files = FileScanner::scan_files(&sql_files[..1]).unwrap();
+    let seed_files = FileScanner::scan_files(&sql_files[1..]).unwrap();
+
+    change_detector
+        .store_layer_metadata("layered__layer0", &schema_files, None)
+        .await
+        .unwrap();
+    change_detector
+        .store_layer_metadata("layered", &seed_files, Some("layered__layer0"))
+        .await
+        .unwrap();
+
+    // Both layers are up to date
+    assert!(!change_detector
+        .layer_needs_rebuild("layered__layer0", &schema_files, None)
+        .await
+        .unwrap());
+    assert!(!change_detector
+        .layer_needs_rebuild("layered", &seed_files, Some("layered__layer0"))
+        .await
+        .unwrap());
+
+    // Changing only the top layer's files leaves the base layer untouched
+    modify_test_sql_file(&sql_files[1]).unwrap();
+    let modified_seed_files = FileScanner::scan_files(&sql_files[1..]).unwrap();
+    assert!(!change_detector
+        .layer_needs_rebuild("layered__layer0", &schema_files, None)
+        .await
+        .unwrap());
+    assert!(change_detector
+        .layer_needs_rebuild("layered", &modified_seed_files, Some("layered__layer0"))
+        .await
+        .unwrap());
+
+    // Rebuilding the base layer invalidates the layer cloned from it
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    change_detector
+        .store_layer_metadata("layered__layer0", &schema_files, None)
+        .await
+        .unwrap();
+    assert!(change_detector
+        .layer_needs_rebuild("layered", &seed_files, Some("layered__layer0"))
+        .await
+        .unwrap());
+}
@@ -119,3 +119,471 @@ async fn test_database_clone_cleanup() {
         }
     }
 }
+
+/// Test that a batch of clones is created concurrently within the configured limit
+#[tokio::test]
+async fn test_batch_cloning_with_concurrency_limit() {
+    use dbfast::clone::{CloneConfig, CloneManager};
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let template_name = format!("batch_template_{suffix}");
+            let clone_manager = CloneManager::new_with_config(
+                pool.clone(),
+                CloneConfig {
+                    max_concurrent_clones: 2,
+                    ..CloneConfig::default()
+                },
+            );
+
+            if pool.create_database(&template_name).await.is_err() {
+                println!("⚠️  Could not create template for batch clone test");
+                return;
+            }
+
+            let clone_names =
+                dbfast::commands::seed::batch_clone_names(&format!("batch_clone_{suffix}"), 6);
+            let reports = clone_manager
+                .clone_databases(&template_name, &clone_names)
+                .await;
+
+            assert_eq!(reports.len(), 6);
+            for (report, clone_name) in reports.iter().zip(&clone_names) {
+                assert_eq!(&report.clone_name, clone_name);
+                assert!(
+                    report.result.is_ok(),
+                    "Clone {} failed: {:?}",
+                    report.clone_name,
+                    report.result
+                );
+                assert!(report.attempts >= 1);
+            }
+
+            for clone_name in &clone_names {
+                clone_manager.drop_database(clone_name).await.unwrap();
+            }
+            clone_manager.drop_database(&template_name).await.unwrap();
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for batch clone test");
+        }
+    }
+}
+
+/// Test that clone verification passes for a faithful clone and reports drift
+#[tokio::test]
+async fn test_clone_verification_reports_differences() {
+    use dbfast::clone::{CloneConfig, CloneError, CloneManager};
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let template_name = format!("verify_template_{suffix}");
+            let clone_name = format!("verify_clone_{suffix}");
+            let clone_manager = CloneManager::new_with_config(
+                pool.clone(),
+                CloneConfig {
+                    enable_verification: true,
+                    verify_data_integrity: true,
+                    verify_checksums: true,
+                    ..CloneConfig::default()
+                },
+            );
+
+            if pool.create_database(&template_name).await.is_err() {
+                println!("⚠️  Could not create template for verification test");
+                return;
+            }
+            {
+                let template_pool = pool.for_database(&template_name).await.unwrap();
+                template_pool
+                    .execute_sql_content(
+                        "CREATE TABLE tb_user (id SERIAL PRIMARY KEY, name TEXT);\
+                         INSERT INTO tb_user (name) VALUES ('alice'), ('bob');",
+                    )
+                    .await
+                    .unwrap();
+            }
+
+            // A faithful clone verifies cleanly
+            clone_manager
+                .clone_database(&template_name, &clone_name)
+                .await
+                .unwrap();
+
+            // Drift in the clone is reported in detail
+            {
+                let clone_pool = pool.for_database(&clone_name).await.unwrap();
+                clone_pool
+                    .execute_sql_content("DELETE FROM tb_user WHERE name = 'bob'")
+                    .await
+                    .unwrap();
+            }
+            match clone_manager
+                .verify_clone(&template_name, &clone_name)
+                .await
+            {
+                Err(CloneError::CloneVerificationFailed { reason }) => {
+                    assert!(reason.contains("public.tb_user"), "{reason}");
+                    assert!(
+                        reason.contains("2 row(s) in template, 1 in clone"),
+                        "{reason}"
+                    );
+                }
+                other => panic!("Expected verification failure, got {other:?}"),
+            }
+
+            clone_manager.drop_database(&clone_name).await.unwrap();
+            clone_manager.drop_database(&template_name).await.unwrap();
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for clone verification test");
+        }
+    }
+}
+
+/// Test that a clone failing verification is dropped and unregistered
+#[tokio::test]
+async fn test_failed_verification_drops_clone() {
+    use dbfast::clone::{CloneConfig, CloneError, CloneManager};
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let template_name = format!("unverified_template_{suffix}");
+            let clone_name = format!("unverified_clone_{suffix}");
+            let clone_manager = CloneManager::new_with_config(
+                pool.clone(),
+                CloneConfig {
+                    enable_verification: true,
+                    verify_data_integrity: true,
+                    verify_checksums: true,
+                    register_clones: true,
+                    ..CloneConfig::default()
+                },
+            );
+
+            if pool.create_database(&template_name).await.is_err() {
+                println!("⚠️  Could not create template for failed verification test");
+                return;
+            }
+            {
+                let template_pool = pool.for_database(&template_name).await.unwrap();
+                template_pool
+                    .execute_sql_content(
+                        "CREATE TABLE tb_event (at TIMESTAMPTZ);\
+                         INSERT INTO tb_event VALUES ('2024-01-01 00:00:00+00');",
+                    )
+                    .await
+                    .unwrap();
+            }
+            // Database settings are not cloned, so the clone renders timestamps
+            // differently and its checksum no longer matches the template's
+            pool.execute_non_transactional(
+                &format!("ALTER DATABASE {template_name} SET timezone = 'Pacific/Kiritimati'"),
+                &[],
+            )
+            .await
+            .unwrap();
+
+            let reports = clone_manager
+                .clone_databases(&template_name, std::slice::from_ref(&clone_name))
+                .await;
+            match &reports[0].result {
+                Err(CloneError::CloneVerificationFailed { reason }) => {
+                    assert!(reason.contains("public.tb_event"), "{reason}");
+                }
+                other => panic!("Expected verification failure, got {other:?}"),
+            }
+            assert!(!pool.database_exists(&clone_name).await.unwrap());
+            let clones = clone_manager.registry().list().await.unwrap();
+            assert!(clones.iter().all(|record| record.name != clone_name));
+
+            pool.drop_database(&template_name).await.unwrap();
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for failed verification test");
+        }
+    }
+}
+
+/// Test that verification reports a missing template before any clone starts
+#[tokio::test]
+async fn test_verification_requires_template() {
+    use dbfast::clone::{CloneConfig, CloneError, CloneManager};
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let clone_names = vec![
+                format!("orphan_clone_{suffix}_1"),
+                format!("orphan_clone_{suffix}_2"),
+            ];
+            let clone_manager = CloneManager::new_with_config(
+                pool.clone(),
+                CloneConfig {
+                    enable_verification: true,
+                    ..CloneConfig::default()
+                },
+            );
+
+            let reports = clone_manager
+                .clone_databases(&format!("missing_template_{suffix}"), &clone_names)
+                .await;
+            assert_eq!(reports.len(), 2);
+            for report in &reports {
+                assert!(matches!(
+                    report.result,
+                    Err(CloneError::TemplateNotFound { .. })
+                ));
+                assert_eq!(report.attempts, 0);
+            }
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for missing template test");
+        }
+    }
+}
+
+/// Test that a default clone manager leaves the database it connects to alone
+#[tokio::test]
+async fn test_default_manager_does_not_register_clones() {
+    use dbfast::clone::CloneManager;
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let app_name = format!("unregistered_app_{suffix}");
+            let template_name = format!("unregistered_template_{suffix}");
+            let clone_name = format!("unregistered_clone_{suffix}");
+
+            if pool.create_database(&app_name).await.is_err()
+                || pool.create_database(&template_name).await.is_err()
+            {
+                println!("⚠️  Could not create databases for registration test");
+                return;
+            }
+            // Like an application pointing the manager at its own database
+            let app_pool = pool.for_database(&app_name).await.unwrap();
+            let clone_manager = CloneManager::new(app_pool.clone());
+            clone_manager
+                .clone_database(&template_name, &clone_name)
+                .await
+                .unwrap();
+
+            let rows = app_pool
+                .query("SELECT to_regnamespace('dbfast') IS NULL", &[])
+                .await
+                .unwrap();
+            assert!(rows[0].get::<_, bool>(0), "dbfast schema was created");
+
+            clone_manager.drop_database(&clone_name).await.unwrap();
+            drop(app_pool);
+            pool.force_drop_database(&app_name).await.unwrap();
+            pool.drop_database(&template_name).await.unwrap();
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for registration test");
+        }
+    }
+}
+
+/// Test that clones are recorded in the registry and can be dropped by pattern
+#[tokio::test]
+async fn test_clone_registry_records_and_drops_clones() {
+    use dbfast::clone::{CloneConfig, CloneManager};
+    use dbfast::commands::clones::handle_clones_drop_in_dir;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let template_name = format!("registry_template_{suffix}");
+            let clone_name = format!("registry_clone_{suffix}");
+            let clone_manager = CloneManager::new_with_config(
+                pool.clone(),
+                CloneConfig {
+                    ttl: Some(Duration::from_secs(7_200)),
+                    labels: BTreeMap::from([("ci_job".to_string(), "42".to_string())]),
+                    register_clones: true,
+                    ..CloneConfig::default()
+                },
+            );
+
+            if pool.create_database(&template_name).await.is_err() {
+                println!("⚠️  Could not create template for registry test");
+                return;
+            }
+            clone_manager
+                .clone_database(&template_name, &clone_name)
+                .await
+                .unwrap();
+
+            let clones = clone_manager.registry().list().await.unwrap();
+            let record = clones
+                .iter()
+                .find(|record| record.name == clone_name)
+                .expect("clone should be registered");
+            assert_eq!(record.template, template_name);
+            assert_eq!(record.ttl, Some(Duration::from_secs(7_200)));
+            assert_eq!(record.labels.get("ci_job").map(String::as_str), Some("42"));
+            assert!(!record.is_expired(chrono::Utc::now()));
+
+            handle_clones_drop_in_dir(
+                std::path::Path::new("tests/fixtures"),
+                &format!("registry_clone_{suffix}*"),
+                false,
+                false,
+            )
+            .await
+            .unwrap();
+
+            assert!(!pool.database_exists(&clone_name).await.unwrap());
+            let clones = clone_manager.registry().list().await.unwrap();
+            assert!(clones.iter().all(|record| record.name != clone_name));
+
+            pool.drop_database(&template_name).await.unwrap();
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for clone registry test");
+        }
+    }
+}
+
+/// Test that force drop and reset work while sessions are still connected to a clone
+#[tokio::test]
+async fn test_force_drop_and_reset_with_active_connections() {
+    use dbfast::clone::{CloneConfig, CloneError, CloneManager};
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let template_name = format!("reset_template_{suffix}");
+            let clone_name = format!("reset_clone_{suffix}");
+            let clone_manager = CloneManager::new_with_config(
+                pool.clone(),
+                CloneConfig {
+                    register_clones: true,
+                    ..CloneConfig::default()
+                },
+            );
+
+            if pool.create_database(&template_name).await.is_err() {
+                println!("⚠️  Could not create template for reset test");
+                return;
+            }
+            {
+                let template_pool = pool.for_database(&template_name).await.unwrap();
+                template_pool
+                    .execute_sql_content("CREATE TABLE tb_item (id INT)")
+                    .await
+                    .unwrap();
+            }
+            clone_manager
+                .clone_database(&template_name, &clone_name)
+                .await
+                .unwrap();
+
+            // Dirty the clone and keep a session open, like a crashed test would
+            let clone_pool = pool.for_database(&clone_name).await.unwrap();
+            clone_pool
+                .execute_sql_content("INSERT INTO tb_item VALUES (1)")
+                .await
+                .unwrap();
+            let _held = clone_pool
+                .acquire_advisory_lock(1, std::time::Duration::from_secs(1))
+                .await
+                .unwrap();
+
+            assert!(clone_manager.drop_database(&clone_name).await.is_err());
+
+            let template = clone_manager.reset_database(&clone_name).await.unwrap();
+            assert_eq!(template, template_name);
+
+            let fresh_pool = pool.for_database(&clone_name).await.unwrap();
+            let rows = fresh_pool
+                .query("SELECT count(*) FROM tb_item", &[])
+                .await
+                .unwrap();
+            assert_eq!(rows[0].get::<_, i64>(0), 0);
+            let _held_again = fresh_pool
+                .acquire_advisory_lock(1, std::time::Duration::from_secs(1))
+                .await
+                .unwrap();
+
+            clone_manager
+                .force_drop_database(&clone_name)
+                .await
+                .unwrap();
+            assert!(!pool.database_exists(&clone_name).await.unwrap());
+            assert!(matches!(
+                clone_manager.reset_database(&clone_name).await,
+                Err(CloneError::CloneNotRegistered { .. })
+            ));
+
+            pool.drop_database(&template_name).await.unwrap();
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for reset test");
+        }
+    }
+}
+
+/// Test the pre-13 force drop path: terminate sessions, then a plain drop
+#[tokio::test]
+async fn test_terminate_connections_then_drop() {
+    use dbfast::clone::CloneManager;
+
+    let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+
+    match DatabasePool::from_config(&config.database).await {
+        Ok(pool) => {
+            let suffix = std::process::id();
+            let template_name = format!("terminate_template_{suffix}");
+            // Mixed case only survives when every statement quotes the name
+            let clone_name = format!("Terminate_Clone_{suffix}");
+            let clone_manager = CloneManager::new(pool.clone());
+
+            if pool.create_database(&template_name).await.is_err() {
+                println!("⚠️  Could not create template for terminate test");
+                return;
+            }
+            clone_manager
+                .clone_database(&template_name, &clone_name)
+                .await
+                .unwrap();
+
+            let clone_pool = pool.for_database(&clone_name).await.unwrap();
+            let _held = clone_pool
+                .acquire_advisory_lock(1, std::time::Duration::from_secs(1))
+                .await
+                .unwrap();
+            assert!(pool.drop_database(&clone_name).await.is_err());
+
+            assert!(pool.terminate_connections(&clone_name).await.unwrap() > 0);
+            pool.drop_database(&clone_name).await.unwrap();
+            assert!(!pool.database_exists(&clone_name).await.unwrap());
+
+            pool.drop_database(&template_name).await.unwrap();
+        }
+        Err(_) => {
+            println!("⚠️  No database connection for terminate test");
+        }
+    }
+}
@@ -1,6 +1,7 @@
 /// Shared test utilities for dbfast integration tests
-use dbfast::config::DatabaseConfig;
+use dbfast::config::{DatabaseConfig, SqlExecutor};
 use dbfast::database::DatabasePool;
+use dbfast::tls::TlsConfig;
 use uuid::Uuid;
 
 /// Test database manager that ensures unique database names and automatic cleanup
@@ -44,6 +45,7 @@ impl TestDatabase {
 
     /// Get an admin database configuration for template operations
     /// This returns a config that connects to postgres database for admin operations
+    #[allow(dead_code)]
     pub fn admin_config(&self) -> DatabaseConfig {
         create_admin_db_config()
     }
@@ -67,23 +69,41 @@ impl Drop for TestDatabase {
 /// Create a database config for admin operations (connecting to postgres database)
 fn create_admin_db_config() -> DatabaseConfig {
     DatabaseConfig {
-        host: "localhost".to_string(),
-        port: 5432,
-        user: "postgres".to_string(),
+        host: Some("localhost".to_string()),
+        port: Some(5432),
+        user: Some("postgres".to_string()),
+        url: None,
+        service: None,
+        admin_database: None,
         password_env: Some("POSTGRES_PASSWORD".to_string()),
+        password_file: None,
+        password_command: None,
         template_name: "postgres".to_string(), // Connect to postgres database for admin operations
         allow_multi_statement: true,
+        template_lock_timeout_secs: 300,
+        fingerprint_templates: false,
+        sql_executor: SqlExecutor::Native,
+        tls: TlsConfig::default(),
     }
 }
 
 /// Create a test database config with a specific database name
 fn create_test_db_config_with_name(database_name: &str) -> DatabaseConfig {
     DatabaseConfig {
-        host: "localhost".to_string(),
-        port: 5432,
-        user: "postgres".to_string(),
+        host: Some("localhost".to_string()),
+        port: Some(5432),
+        user: Some("postgres".to_string()),
+        url: None,
+        service: None,
+        admin_database: None,
         password_env: Some("POSTGRES_PASSWORD".to_string()),
+        password_file: None,
+        password_command: None,
         allow_multi_statement: true,
+        template_lock_timeout_secs: 300,
+        fingerprint_templates: false,
+        sql_executor: SqlExecutor::Native,
+        tls: TlsConfig::default(),
         template_name: database_name.to_string(),
     }
 }
@@ -1,11 +1,13 @@
+use dbfast::config::{PerformanceConfig, PerformanceOverrides};
 use dbfast::Config;
+use std::time::Duration;
 
 #[test]
 fn test_config_loading() {
     let config = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
-    assert_eq!(config.database.host, "localhost");
-    assert_eq!(config.database.port, 5432);
-    assert_eq!(config.database.user, "postgres");
+    assert_eq!(config.database.host.as_deref(), Some("localhost"));
+    assert_eq!(config.database.port, Some(5432));
+    assert_eq!(config.database.user.as_deref(), Some("postgres"));
     assert_eq!(config.repository.path, "./db");
 }
 
@@ -14,3 +16,145 @@ fn test_config_missing_file() {
     let result = Config::from_file("nonexistent.toml");
     assert!(result.is_err());
 }
+
+#[test]
+fn test_environment_variables() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("dbfast.toml");
+    let base = r#"
+[database]
+host = "localhost"
+port = 5432
+user = "postgres"
+template_name = "app_template"
+
+[repository]
+path = "./db"
+type = "structured"
+
+[environments.staging]
+include_directories = ["0_schema"]
+"#;
+
+    std::fs::write(
+        &config_path,
+        format!(
+            "{base}\n[environments.staging.variables]\napp_role = \"app_rw\"\ntenant_count = 3\nbilling = false\n"
+        ),
+    )
+    .unwrap();
+    let config = Config::from_file(&config_path).unwrap();
+    let variables = config.environment_variables(Some("staging"));
+    assert_eq!(variables["app_role"], "app_rw");
+    assert_eq!(variables["tenant_count"], "3");
+    assert_eq!(variables["billing"], "false");
+    assert!(config.environment_variables(Some("local")).is_empty());
+    assert!(config.environment_variables(None).is_empty());
+
+    std::fs::write(
+        &config_path,
+        format!("{base}\n[environments.staging.variables]\n\"app-role\" = \"x\"\n"),
+    )
+    .unwrap();
+    let error = Config::from_file(&config_path).unwrap_err();
+    assert!(
+        error.to_string().contains("invalid variable name"),
+        "{error}"
+    );
+}
+
+#[test]
+fn test_password_file_and_command() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("dbfast.toml");
+    let password_path = temp_dir.path().join("password");
+    std::fs::write(&password_path, "s3cret\n").unwrap();
+    std::fs::write(
+        &config_path,
+        format!(
+            r#"
+[database]
+host = "localhost"
+port = 5432
+user = "postgres"
+password_file = "{}"
+template_name = "app_template"
+
+[repository]
+path = "./db"
+type = "structured"
+
+[remotes.staging]
+url = "postgres://deploy@staging:5432/app"
+password_command = "echo from-command"
+environment = "staging"
+"#,
+            password_path.display()
+        ),
+    )
+    .unwrap();
+
+    let config = Config::from_file(&config_path).unwrap();
+    let target = dbfast::credentials::PgPassTarget {
+        host: "localhost",
+        port: 5432,
+        database: "app_template",
+        user: "postgres",
+    };
+    let password = config.database.credentials().resolve(&target).unwrap();
+    assert_eq!(password.as_ref().map(|p| p.expose()), Some("s3cret"));
+    assert_eq!(config.database.credentials().kind(), "password file");
+
+    let remote = &config.remotes["staging"];
+    assert_eq!(remote.credentials().kind(), "password command");
+    if cfg!(unix) {
+        let password = remote.get_password().unwrap();
+        assert_eq!(password.as_ref().map(|p| p.expose()), Some("from-command"));
+    }
+}
+
+#[test]
+fn test_performance_settings() {
+    let defaults = Config::from_file("tests/fixtures/dbfast.toml").unwrap();
+    assert_eq!(defaults.performance, PerformanceConfig::default());
+    assert_eq!(defaults.performance.build_timeout(), None);
+
+    let config: Config = toml::from_str(
+        r#"
+[database]
+template_name = "app_template"
+
+[repository]
+path = "./db"
+type = "structured"
+
+[performance]
+max_concurrent_clones = 4
+connection_pool_size = 8
+clone_timeout_ms = 5000
+build_timeout_ms = 300000
+"#,
+    )
+    .unwrap();
+    let performance = config.performance;
+    assert_eq!(performance.pool_config().max_size, 8);
+    assert_eq!(
+        performance.pool_config().connection_timeout,
+        Duration::from_secs(30)
+    );
+    assert_eq!(performance.clone_config().max_concurrent_clones, 4);
+    assert_eq!(
+        performance.clone_config().clone_timeout,
+        Duration::from_secs(5)
+    );
+    assert_eq!(performance.build_timeout(), Some(Duration::from_secs(300)));
+
+    let overridden = performance.with_overrides(&PerformanceOverrides {
+        connection_pool_size: Some(2),
+        build_timeout_ms: Some(1000),
+        ..PerformanceOverrides::default()
+    });
+    assert_eq!(overridden.connection_pool_size, 2);
+    assert_eq!(overridden.max_concurrent_clones, 4);
+    assert_eq!(overridden.build_timeout(), Some(Duration::from_secs(1)));
+}
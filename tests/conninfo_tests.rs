@@ -0,0 +1,76 @@
+//! Connections resolved from conninfo strings and Unix socket directories
+//!
+//! Socket tests are skipped when the test server has no socket in
+//! `/var/run/postgresql`.
+
+use dbfast::config::{DatabaseConfig, SqlExecutor};
+use dbfast::database::DatabasePool;
+use std::path::Path;
+
+const SOCKET_DIRECTORY: &str = "/var/run/postgresql";
+
+fn config(toml: &str) -> DatabaseConfig {
+    toml::from_str(&format!(
+        "template_name = \"postgres\"\npassword_env = \"POSTGRES_PASSWORD\"\n{toml}"
+    ))
+    .unwrap()
+}
+
+/// Whether the pool's connection goes through a Unix socket
+async fn through_socket(pool: &DatabasePool) -> bool {
+    let rows = pool
+        .query("SELECT inet_server_addr() IS NULL", &[])
+        .await
+        .unwrap();
+    rows[0].get(0)
+}
+
+#[tokio::test]
+async fn test_conninfo_and_admin_database() {
+    let pool = DatabasePool::from_config(&config(
+        "conninfo = \"host=localhost port=5432 user=postgres dbname=template1\"",
+    ))
+    .await
+    .unwrap();
+    let rows = pool.query("SELECT current_database()", &[]).await.unwrap();
+    assert_eq!(rows[0].get::<_, String>(0), "template1");
+
+    // Explicit settings take precedence over the URL
+    let pool = DatabasePool::from_config(&config(
+        "url = \"postgresql://postgres@localhost:5432/template1\"\nadmin_database = \"postgres\"",
+    ))
+    .await
+    .unwrap();
+    let rows = pool.query("SELECT current_database()", &[]).await.unwrap();
+    assert_eq!(rows[0].get::<_, String>(0), "postgres");
+    assert!(!through_socket(&pool).await);
+}
+
+#[tokio::test]
+async fn test_socket_directory() {
+    if !Path::new(SOCKET_DIRECTORY).join(".s.PGSQL.5432").exists() {
+        eprintln!("Skipping: no server socket in {SOCKET_DIRECTORY}");
+        return;
+    }
+
+    // TLS settings are ignored for sockets, as by libpq
+    let config = config(&format!(
+        "host = \"{SOCKET_DIRECTORY}\"\nuser = \"postgres\"\nsslmode = \"require\""
+    ));
+    let pool = DatabasePool::from_config(&config).await.unwrap();
+    assert!(through_socket(&pool).await);
+
+    let url = pool.connection_url().unwrap();
+    assert!(
+        url.starts_with("postgresql:///postgres?host=%2Fvar%2Frun%2Fpostgresql"),
+        "{url}"
+    );
+
+    // psql connects through the same socket
+    let sql_file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(sql_file.path(), "SELECT 1;\nSELECT 2;\n").unwrap();
+    pool.with_sql_executor(SqlExecutor::Psql)
+        .execute_sql_files(&[sql_file.path()], true)
+        .await
+        .unwrap();
+}
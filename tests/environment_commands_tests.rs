@@ -1,6 +1,9 @@
 use assert_cmd::prelude::*;
-use dbfast::config::{Config, DatabaseConfig, Environment, RepositoryConfig};
-use std::collections::HashMap;
+use dbfast::config::{
+    Config, DatabaseConfig, Environment, PerformanceConfig, RepositoryConfig, SqlExecutor,
+};
+use dbfast::tls::TlsConfig;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::process::Command;
 use tempfile::TempDir;
@@ -50,6 +53,7 @@ fn create_test_project_with_environments() -> TempDir {
                 "1_seed_local".to_string(),
             ],
             exclude_directories: vec![],
+            variables: BTreeMap::new(),
         },
     );
 
@@ -58,17 +62,27 @@ fn create_test_project_with_environments() -> TempDir {
         Environment {
             include_directories: vec!["0_schema".to_string(), "1_seed_common".to_string()],
             exclude_directories: vec!["1_seed_local".to_string()],
+            variables: BTreeMap::new(),
         },
     );
 
     let config = Config {
         database: DatabaseConfig {
-            host: "localhost".to_string(),
-            port: 5432,
-            user: "postgres".to_string(),
+            host: Some("localhost".to_string()),
+            port: Some(5432),
+            user: Some("postgres".to_string()),
+            url: None,
+            service: None,
+            admin_database: None,
             password_env: Some("POSTGRES_PASSWORD".to_string()),
+            password_file: None,
+            password_command: None,
             template_name: "blog_template".to_string(),
             allow_multi_statement: true,
+            template_lock_timeout_secs: 300,
+            fingerprint_templates: false,
+            sql_executor: SqlExecutor::Native,
+            tls: TlsConfig::default(),
         },
         repository: RepositoryConfig {
             path: temp_dir.path().display().to_string(),
@@ -76,6 +90,7 @@ fn create_test_project_with_environments() -> TempDir {
         },
         environments,
         remotes: HashMap::new(),
+        performance: PerformanceConfig::default(),
     };
 
     let config_content = toml::to_string(&config).unwrap();
@@ -119,17 +134,27 @@ fn create_unsafe_production_config() -> TempDir {
                 "1_seed_backend".to_string(), // DANGEROUS: includes test data in production
             ],
             exclude_directories: vec![],
+            variables: BTreeMap::new(),
         },
     );
 
     let config = Config {
         database: DatabaseConfig {
-            host: "localhost".to_string(),
-            port: 5432,
-            user: "postgres".to_string(),
+            host: Some("localhost".to_string()),
+            port: Some(5432),
+            user: Some("postgres".to_string()),
+            url: None,
+            service: None,
+            admin_database: None,
             password_env: Some("POSTGRES_PASSWORD".to_string()),
+            password_file: None,
+            password_command: None,
             template_name: "unsafe_template".to_string(),
             allow_multi_statement: true,
+            template_lock_timeout_secs: 300,
+            fingerprint_templates: false,
+            sql_executor: SqlExecutor::Native,
+            tls: TlsConfig::default(),
         },
         repository: RepositoryConfig {
             path: temp_dir.path().display().to_string(),
@@ -137,6 +162,7 @@ fn create_unsafe_production_config() -> TempDir {
         },
         environments,
         remotes: HashMap::new(),
+        performance: PerformanceConfig::default(),
     };
 
     let config_content = toml::to_string(&config).unwrap();
@@ -256,6 +282,91 @@ fn test_environments_command_verbose_mode() {
     assert!(stdout.len() > 200, "Verbose output should be more detailed");
 }
 
+#[test]
+fn test_environments_command_selects_files_like_seed() {
+    let temp_dir = create_test_project_with_environments();
+    let config_path = temp_dir.path().join("dbfast.toml");
+    let mut config = fs::read_to_string(&config_path).unwrap();
+    config.push_str(
+        "\n[environments.everything]\ninclude_directories = []\nexclude_directories = []\n",
+    );
+    fs::write(&config_path, config).unwrap();
+
+    let output = Command::cargo_bin("dbfast")
+        .unwrap()
+        .arg("environments")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // An empty include list places no restriction, as for `seed --env`
+    assert!(
+        stdout.contains("• everything (3 files)"),
+        "Unexpected output:\n{stdout}"
+    );
+    assert!(stdout.contains("• local (3 files)"));
+    assert!(stdout.contains("• production (2 files)"));
+    assert!(stdout.contains("Includes: (all directories)"));
+}
+
+#[test]
+fn test_unknown_environment_is_rejected() {
+    let temp_dir = create_test_project_with_environments();
+
+    for args in [
+        vec!["order", "--env", "locl"],
+        vec!["seed", "--output", "typo_db", "--env", "locl"],
+    ] {
+        let output = Command::cargo_bin("dbfast")
+            .unwrap()
+            .args(&args)
+            .current_dir(temp_dir.path())
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success(), "{args:?} should fail");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr
+                .contains("Unknown environment 'locl'; configured environments: local, production"),
+            "Unexpected error for {args:?}:\n{stderr}"
+        );
+    }
+}
+
+#[test]
+fn test_order_command_explains_dependencies() {
+    let temp_dir = create_test_project_with_environments();
+    fs::write(
+        temp_dir.path().join("0_schema").join("000_views.sql"),
+        "CREATE VIEW user_names AS SELECT name FROM users;",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dbfast").unwrap();
+    let output = cmd
+        .args(["order", "--env", "local", "--explain"])
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let users = stdout.find("0_schema/001_users.sql").unwrap();
+    let views = stdout.find("0_schema/000_views.sql").unwrap();
+    assert!(
+        users < views,
+        "Views should run after the tables they read:\n{stdout}"
+    );
+    assert!(
+        stdout.contains("after 0_schema/001_users.sql: view reads from users"),
+        "Should explain the inferred dependency:\n{stdout}"
+    );
+}
+
 #[test]
 fn test_validate_env_command_with_valid_environment() {
     let temp_dir = create_test_project_with_environments();
@@ -423,17 +534,27 @@ fn test_validate_env_command_checks_file_existence() {
         Environment {
             include_directories: vec!["nonexistent_dir".to_string()],
             exclude_directories: vec![],
+            variables: BTreeMap::new(),
         },
     );
 
     let config = Config {
         database: DatabaseConfig {
-            host: "localhost".to_string(),
-            port: 5432,
-            user: "postgres".to_string(),
+            host: Some("localhost".to_string()),
+            port: Some(5432),
+            user: Some("postgres".to_string()),
+            url: None,
+            service: None,
+            admin_database: None,
             password_env: Some("POSTGRES_PASSWORD".to_string()),
+            password_file: None,
+            password_command: None,
             template_name: "test_template".to_string(),
             allow_multi_statement: true,
+            template_lock_timeout_secs: 300,
+            fingerprint_templates: false,
+            sql_executor: SqlExecutor::Native,
+            tls: TlsConfig::default(),
         },
         repository: RepositoryConfig {
             path: temp_dir.path().display().to_string(),
@@ -441,6 +562,7 @@ fn test_validate_env_command_checks_file_existence() {
         },
         environments,
         remotes: HashMap::new(),
+        performance: PerformanceConfig::default(),
     };
 
     let config_content = toml::to_string(&config).unwrap();
@@ -0,0 +1 @@
+CREATE TABLE tb_user (id SERIAL PRIMARY KEY, name TEXT NOT NULL);
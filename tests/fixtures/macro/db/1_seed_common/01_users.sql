@@ -0,0 +1 @@
+INSERT INTO tb_user (name) VALUES ('alice'), ('bob');
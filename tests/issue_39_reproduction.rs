@@ -1,5 +1,6 @@
 /// Tests for GitHub issue #39: Support Concatenated Multi-Statement SQL Files via psql Integration
 /// This test suite validates the hybrid execution strategy implementation
+use dbfast::credentials::Password;
 use dbfast::database::DatabasePool;
 use std::io::Write;
 use std::process::Command;
@@ -267,7 +268,10 @@ async fn test_database_pool_connection_info_storage() {
             assert_eq!(conn_info.host, "testhost");
             assert_eq!(conn_info.port, 5433);
             assert_eq!(conn_info.user, "testuser");
-            assert_eq!(conn_info.password, Some("testpass".to_string()));
+            assert_eq!(
+                conn_info.password.as_ref().map(Password::expose),
+                Some("testpass")
+            );
             assert_eq!(conn_info.database, "testdb");
 
             println!("✅ DatabasePool correctly stores connection info from URL");
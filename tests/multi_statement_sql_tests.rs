@@ -9,7 +9,13 @@
 /// - Handle additional statements after function definitions
 /// - Execute statements sequentially without "cannot insert multiple commands" error
 ///
-use dbfast::database::DatabasePool;
+mod common;
+
+use common::TestDatabase;
+use dbfast::config::SqlExecutor;
+use dbfast::database::{DatabaseError, DatabasePool};
+use dbfast::execution::SegmentMode;
+use dbfast::statement_error::SourcePosition;
 use std::fs;
 use tempfile::TempDir;
 
@@ -295,3 +301,413 @@ type = "structured"
     // But we'll need to add the field and test it properly
     assert!(result.is_ok());
 }
+
+/// Multi-statement content runs natively over the pool's connection
+#[tokio::test]
+async fn test_native_execution_of_multi_statement_content() {
+    let db = TestDatabase::create_unique("native_exec")
+        .await
+        .expect("Failed to create test database");
+    assert_eq!(db.pool.sql_executor(), SqlExecutor::Native);
+
+    let sql_content = r#"
+CREATE TABLE contracts (id SERIAL PRIMARY KEY, status TEXT);
+
+CREATE FUNCTION contract_count() RETURNS BIGINT LANGUAGE plpgsql AS $$
+BEGIN
+    RETURN (SELECT count(*) FROM contracts);
+END;
+$$;
+
+COMMENT ON FUNCTION contract_count() IS 'Counts contracts; used by reports';
+DO $$ BEGIN INSERT INTO contracts (status) VALUES ('draft;pending'); END $$;
+INSERT INTO contracts (status) VALUES ('signed');
+"#;
+
+    db.pool
+        .execute_sql_content(sql_content)
+        .await
+        .expect("Native execution should succeed");
+
+    let rows = db.pool.query("SELECT contract_count()", &[]).await.unwrap();
+    assert_eq!(rows[0].get::<_, i64>(0), 2);
+}
+
+/// A failing statement is attributed and rolls back the whole script
+#[tokio::test]
+async fn test_native_execution_reports_failing_statement() {
+    let db = TestDatabase::create_unique("native_error")
+        .await
+        .expect("Failed to create test database");
+
+    let sql_content = "CREATE TABLE accounts (id INT);\n\
+                       INSERT INTO accounts VALUES (1);\n\
+                       \n\
+                       INSERT INTO missing_table VALUES (2);\n";
+
+    let error = db
+        .pool
+        .execute_sql_content(sql_content)
+        .await
+        .expect_err("Third statement should fail");
+    match &error {
+        DatabaseError::Statement(error) => {
+            assert_eq!(error.index, 3);
+            assert_eq!(error.line, 4);
+            assert_eq!(error.statement, "INSERT INTO missing_table VALUES (2)");
+            assert!(error.message.contains("missing_table"));
+            assert_eq!(
+                error.position,
+                Some(SourcePosition {
+                    line: 4,
+                    column: 13
+                })
+            );
+        }
+        other => panic!("Expected a statement error, got {other}"),
+    }
+    assert!(error.to_string().contains("line 4"));
+
+    let rows = db
+        .pool
+        .query("SELECT to_regclass('accounts') IS NULL", &[])
+        .await
+        .unwrap();
+    assert!(
+        rows[0].get::<_, bool>(0),
+        "Transaction should be rolled back"
+    );
+}
+
+/// Errors in SQL files name the file, the line in it and the server's details
+#[tokio::test]
+async fn test_native_execution_reports_file_location_and_details() {
+    let db = TestDatabase::create_unique("native_error_details")
+        .await
+        .expect("Failed to create test database");
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema_file = temp_dir.path().join("01_schema.sql");
+    let views_file = temp_dir.path().join("02_views.sql");
+    fs::write(
+        &schema_file,
+        "CREATE TABLE accounts (id INT PRIMARY KEY);\n",
+    )
+    .unwrap();
+    fs::write(
+        &views_file,
+        "-- Views\n\nCREATE VIEW account_names AS\nSELECT id,\n       lower(id) AS name\nFROM accounts;\n",
+    )
+    .unwrap();
+
+    let error = db
+        .pool
+        .execute_sql_files(&[&schema_file, &views_file], true)
+        .await
+        .expect_err("The view should fail");
+    let DatabaseError::Statement(statement_error) = &error else {
+        panic!("Expected a statement error, got {error}");
+    };
+    assert_eq!(statement_error.index, 2);
+    assert_eq!(statement_error.file.as_deref(), Some(views_file.as_path()));
+    assert_eq!(statement_error.line, 3);
+    assert!(statement_error
+        .statement
+        .starts_with("CREATE VIEW account_names"));
+    assert!(statement_error.hint.is_some());
+    assert_eq!(
+        statement_error.position,
+        Some(SourcePosition { line: 5, column: 8 })
+    );
+
+    let rendered = error.to_string();
+    assert!(rendered.contains(&format!("{}:3", views_file.display())));
+    assert!(rendered.contains(&format!("--> {}:5:8", views_file.display())));
+    assert!(rendered.contains(" 5 |        lower(id) AS name\n   |        ^"));
+    assert!(rendered.contains("HINT: "));
+
+    let error = db
+        .pool
+        .execute_sql_content("CREATE TABLE t (id INT PRIMARY KEY);\nINSERT INTO t VALUES (1), (1);")
+        .await
+        .expect_err("The duplicate key should fail");
+    let DatabaseError::Statement(statement_error) = &error else {
+        panic!("Expected a statement error, got {error}");
+    };
+    assert_eq!(
+        statement_error.detail.as_deref(),
+        Some("Key (id)=(1) already exists.")
+    );
+    assert!(error
+        .to_string()
+        .contains("DETAIL: Key (id)=(1) already exists."));
+}
+
+/// Statements that cannot run in a transaction split the run into segments
+#[tokio::test]
+async fn test_native_execution_of_non_transactional_statements() {
+    let db = TestDatabase::create_unique("native_segments")
+        .await
+        .expect("Failed to create test database");
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema_file = temp_dir.path().join("01_schema.sql");
+    let maintenance_file = temp_dir.path().join("02_maintenance.sql");
+    fs::write(
+        &schema_file,
+        "CREATE TABLE accounts (id INT, email TEXT);\n\
+         INSERT INTO accounts VALUES (1, 'a@example.com');\n\
+         CREATE INDEX CONCURRENTLY accounts_email_idx ON accounts (email);\n\
+         CREATE TABLE audit (id INT);\n",
+    )
+    .unwrap();
+    fs::write(
+        &maintenance_file,
+        "-- dbfast: transaction=none\n\
+         INSERT INTO audit VALUES (1);\n\
+         VACUUM ANALYZE accounts;\n",
+    )
+    .unwrap();
+
+    let report = db
+        .pool
+        .execute_sql_files(&[&schema_file, &maintenance_file], true)
+        .await
+        .expect("Non-transactional statements should run outside the transaction");
+
+    let summary: Vec<(SegmentMode, usize, usize)> = report
+        .segments
+        .iter()
+        .map(|segment| {
+            (
+                segment.mode,
+                *segment.statements.start(),
+                *segment.statements.end(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (SegmentMode::Transaction, 1, 2),
+            (SegmentMode::Autocommit, 3, 3),
+            (SegmentMode::Transaction, 4, 4),
+            (SegmentMode::Autocommit, 5, 6),
+        ]
+    );
+    assert_eq!(
+        report.segments[1].first.file.as_deref(),
+        Some(schema_file.as_path())
+    );
+    assert_eq!(report.segments[1].first.line, 3);
+    assert_eq!(
+        report.segments[3].last.file.as_deref(),
+        Some(maintenance_file.as_path())
+    );
+    assert!(!report.is_single_transaction());
+
+    let rows = db
+        .pool
+        .query(
+            "SELECT to_regclass('accounts_email_idx') IS NOT NULL, (SELECT count(*) FROM audit)",
+            &[],
+        )
+        .await
+        .unwrap();
+    assert!(rows[0].get::<_, bool>(0));
+    assert_eq!(rows[0].get::<_, i64>(1), 1);
+}
+
+/// psql meta-commands and inline COPY data run natively from files
+#[tokio::test]
+async fn test_native_execution_of_psql_meta_commands() {
+    let db = TestDatabase::create_unique("native_meta")
+        .await
+        .expect("Failed to create test database");
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("lib")).unwrap();
+    fs::write(
+        temp_dir.path().join("lib/tables.sql"),
+        "CREATE TABLE :\"table_name\" (id INT, name TEXT);\n",
+    )
+    .unwrap();
+    let main_file = temp_dir.path().join("main.sql");
+    fs::write(
+        &main_file,
+        "\\set table_name people\n\
+         \\set default_name 'O''Brien'\n\
+         \\ir lib/tables.sql\n\
+         COPY people (id, name) FROM stdin;\n\
+         1\tAda\n\
+         2\t\\N\n\
+         \\.\n\
+         UPDATE people SET name = :'default_name' WHERE name IS NULL;\n",
+    )
+    .unwrap();
+
+    db.pool
+        .execute_sql_files(&[&main_file], true)
+        .await
+        .expect("Meta-commands should run natively");
+
+    let rows = db
+        .pool
+        .query("SELECT name FROM people ORDER BY id", &[])
+        .await
+        .unwrap();
+    let names: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    assert_eq!(names, vec!["Ada", "O'Brien"]);
+
+    fs::write(temp_dir.path().join("bad.sql"), "SELECT 1;\n\\gexec\n").unwrap();
+    let error = db
+        .pool
+        .execute_sql_files(&[temp_dir.path().join("bad.sql")], true)
+        .await
+        .expect_err("Unsupported meta-commands should fail");
+    assert!(matches!(error, DatabaseError::Script(_)));
+    assert!(error
+        .to_string()
+        .contains("Unsupported psql meta-command \\gexec"));
+    assert!(error.to_string().contains("bad.sql:2"));
+}
+
+/// Legacy semicolon splitting refuses to run with unsubstituted variables
+#[tokio::test]
+async fn test_legacy_splitting_rejects_variables() {
+    let db = TestDatabase::create_unique("legacy_variables")
+        .await
+        .expect("Failed to create test database");
+    let pool = db.pool.clone().with_variables(
+        [("app_role".to_string(), "app_rw".to_string())]
+            .into_iter()
+            .collect(),
+    );
+
+    let error = pool
+        .execute_sql_content_with_config("SELECT :'app_role';", false)
+        .await
+        .expect_err("Variables should be rejected without multi-statement parsing");
+    assert!(matches!(error, DatabaseError::Config(_)));
+    assert!(error.to_string().contains("app_role"));
+    assert!(error.to_string().contains("allow_multi_statement = true"));
+
+    pool.execute_sql_content_with_config("SELECT :'app_role';", true)
+        .await
+        .expect("Variables should be substituted with multi-statement parsing");
+}
+
+/// `sql_executor` selects psql as an opt-in compatibility mode
+#[test]
+fn test_sql_executor_config() {
+    let config_content = r#"
+[database]
+host = "localhost"
+port = 5432
+user = "postgres"
+template_name = "test_template"
+sql_executor = "psql"
+
+[repository]
+path = "./sql"
+type = "structured"
+"#;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("dbfast.toml");
+    fs::write(&config_path, config_content).unwrap();
+    let config = dbfast::config::Config::from_file(&config_path).unwrap();
+    assert_eq!(config.database.sql_executor, SqlExecutor::Psql);
+
+    fs::write(
+        &config_path,
+        config_content.replace("sql_executor = \"psql\"\n", ""),
+    )
+    .unwrap();
+    let config = dbfast::config::Config::from_file(&config_path).unwrap();
+    assert_eq!(config.database.sql_executor, SqlExecutor::Native);
+}
+
+/// `skip-if-exists=` and `once` directives skip files on rebuilds
+#[tokio::test]
+async fn test_native_execution_honors_skip_directives() {
+    let db = TestDatabase::create_unique("native_directives")
+        .await
+        .expect("Failed to create test database");
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema_file = temp_dir.path().join("01_schema.sql");
+    let seed_file = temp_dir.path().join("02_seed.sql");
+    fs::write(
+        &schema_file,
+        "-- dbfast: skip-if-exists=accounts\n\
+         CREATE TABLE accounts (id INT);\n",
+    )
+    .unwrap();
+    fs::write(
+        &seed_file,
+        "-- dbfast: once\n\
+         INSERT INTO accounts VALUES (1);\n\
+         INSERT INTO accounts VALUES (2);\n",
+    )
+    .unwrap();
+
+    // Like a template build: `once` is not tracked and leaves no table behind
+    let report = db
+        .pool
+        .execute_sql_files(&[&schema_file, &seed_file], true)
+        .await
+        .expect("Untracked once file should run");
+    assert!(report.skipped.is_empty());
+    let rows = db
+        .pool
+        .query("SELECT to_regclass('dbfast_applied_files') IS NULL", &[])
+        .await
+        .unwrap();
+    assert!(rows[0].get::<_, bool>(0));
+    db.pool
+        .execute_sql_content("DROP TABLE accounts")
+        .await
+        .unwrap();
+
+    let pool = db
+        .pool
+        .clone()
+        .with_applied_files_tracking(temp_dir.path().to_path_buf());
+    let report = pool
+        .execute_sql_files(&[&schema_file, &seed_file], true)
+        .await
+        .expect("First run should apply both files");
+    assert!(report.skipped.is_empty());
+    let rows = pool
+        .query("SELECT path FROM dbfast_applied_files", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get::<_, String>(0), "02_seed.sql");
+
+    // The same file passed through another path is still recognized
+    let relative_seed_file = temp_dir.path().join(".").join("02_seed.sql");
+    let report = pool
+        .execute_sql_files(&[&schema_file, &relative_seed_file], true)
+        .await
+        .expect("Second run should skip both files");
+    let skipped: Vec<_> = report
+        .skipped
+        .iter()
+        .map(|skipped| (skipped.file.clone().unwrap(), skipped.reason.as_str()))
+        .collect();
+    assert_eq!(
+        skipped,
+        vec![
+            (schema_file.clone(), "accounts already exists"),
+            (relative_seed_file.clone(), "already applied (once)"),
+        ]
+    );
+
+    let rows = db
+        .pool
+        .query("SELECT count(*) FROM accounts", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get::<_, i64>(0), 2);
+}
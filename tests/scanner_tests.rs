@@ -62,3 +62,29 @@ fn test_file_scanner_detects_changes() {
     // Hash should be different
     assert_ne!(original_hash, new_hash);
 }
+
+#[test]
+fn test_file_scanner_hashes_included_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let temp_path = temp_dir.path();
+    fs::create_dir_all(temp_path.join("0_schema")).unwrap();
+    fs::create_dir_all(temp_path.join("shared")).unwrap();
+
+    let main_file = temp_path.join("0_schema/01_main.sql");
+    let shared_file = temp_path.join("shared/types.psql");
+    fs::write(
+        &main_file,
+        "\\ir ../shared/types.psql\nCREATE TABLE t (id INT);",
+    )
+    .unwrap();
+    fs::write(&shared_file, "CREATE TYPE mood AS ENUM ('ok');").unwrap();
+
+    let before = FileScanner::scan_files(&[&main_file]).unwrap();
+    fs::write(&shared_file, "CREATE TYPE mood AS ENUM ('ok', 'great');").unwrap();
+    let after = FileScanner::scan_files(&[&main_file]).unwrap();
+
+    assert_ne!(
+        before[0].hash, after[0].hash,
+        "Changing an included file should change the including file's hash"
+    );
+}
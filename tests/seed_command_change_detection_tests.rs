@@ -1,5 +1,6 @@
 use dbfast::commands::seed::handle_seed_async;
-use dbfast::config::{Config, DatabaseConfig, RepositoryConfig};
+use dbfast::config::{Config, DatabaseConfig, PerformanceConfig, RepositoryConfig, SqlExecutor};
+use dbfast::tls::TlsConfig;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
@@ -29,12 +30,21 @@ fn create_test_sql_files(base_dir: &std::path::Path) -> std::io::Result<()> {
 fn create_test_config(temp_dir: &std::path::Path, template_name: &str) -> std::io::Result<PathBuf> {
     let config = Config {
         database: DatabaseConfig {
-            host: "localhost".to_string(),
-            port: 5432,
-            user: "postgres".to_string(),
+            host: Some("localhost".to_string()),
+            port: Some(5432),
+            user: Some("postgres".to_string()),
+            url: None,
+            service: None,
+            admin_database: None,
             password_env: Some("POSTGRES_PASSWORD".to_string()),
+            password_file: None,
+            password_command: None,
             template_name: template_name.to_string(),
             allow_multi_statement: true,
+            template_lock_timeout_secs: 300,
+            fingerprint_templates: false,
+            sql_executor: SqlExecutor::Native,
+            tls: TlsConfig::default(),
         },
         repository: RepositoryConfig {
             path: temp_dir.display().to_string(),
@@ -42,6 +52,7 @@ fn create_test_config(temp_dir: &std::path::Path, template_name: &str) -> std::i
         },
         environments: HashMap::new(),
         remotes: HashMap::new(),
+        performance: PerformanceConfig::default(),
     };
 
     let config_content = toml::to_string(&config).unwrap();
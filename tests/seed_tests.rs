@@ -76,3 +76,12 @@ fn test_seed_command_with_seeds_flag() {
         stderr
     );
 }
+
+#[test]
+fn test_batch_clone_names() {
+    assert_eq!(
+        seed::batch_clone_names("test_db", 3),
+        ["test_db_1", "test_db_2", "test_db_3"]
+    );
+    assert!(seed::batch_clone_names("test_db", 0).is_empty());
+}
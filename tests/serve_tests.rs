@@ -0,0 +1,205 @@
+use dbfast::server::{LeaseServer, Request, Response, ServeOptions};
+use dbfast::{Config, DatabasePool};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+/// Start a lease server for the fixture project, or `None` without PostgreSQL
+async fn start_server(options: ServeOptions) -> Option<LeaseServer> {
+    let config = Config::from_file("tests/fixtures/macro/dbfast.toml").unwrap();
+    let repo_path = PathBuf::from("tests/fixtures/macro/db");
+    match LeaseServer::new(config, repo_path, options).await {
+        Ok(server) => Some(server),
+        Err(e) => {
+            println!("⚠️  No database connection for serve test: {e}");
+            None
+        }
+    }
+}
+
+/// Wait until the pool for the seeded template has `warm` databases ready
+async fn wait_for_warm(server: &LeaseServer, warm: usize) {
+    let start = Instant::now();
+    loop {
+        if let Response::Status { pools, .. } = server.handle(Request::Status).await {
+            if pools
+                .iter()
+                .any(|pool| pool.with_seeds && pool.warm == warm)
+            {
+                return;
+            }
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(20),
+            "pool never reached {warm} warm databases"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Test leasing, heartbeats, release, expiry and warm pool replenishment over a Unix socket
+#[tokio::test]
+async fn test_serve_leases_over_unix_socket() {
+    let Some(server) = start_server(ServeOptions {
+        warm_per_template: 2,
+        lease_ttl: Duration::from_secs(1),
+        ..ServeOptions::default()
+    })
+    .await
+    else {
+        return;
+    };
+    let maintenance = server.spawn_maintenance();
+
+    let socket_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = socket_dir.path().join("dbfast.sock");
+    let listener = {
+        let server = server.clone();
+        let socket_path = socket_path.clone();
+        tokio::spawn(async move { server.serve_unix(&socket_path).await })
+    };
+    while !socket_path.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let stream = tokio::net::UnixStream::connect(&socket_path).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    // The whole exchange happens on one connection
+    writer.write_all(b"{\"op\":\"acquire\"}\n").await.unwrap();
+    let response: Response =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    let Response::Leased {
+        lease_id,
+        database,
+        url,
+        ..
+    } = response
+    else {
+        panic!("Expected a lease, got {response:?}");
+    };
+
+    let pool = DatabasePool::new(&url).await.unwrap();
+    let rows = pool
+        .query("SELECT count(*) FROM tb_user", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get::<_, i64>(0), 2);
+    drop(pool);
+
+    // The pool is refilled in the background
+    wait_for_warm(&server, 2).await;
+
+    let heartbeat = format!("{{\"op\":\"heartbeat\",\"lease_id\":\"{lease_id}\"}}\n");
+    writer.write_all(heartbeat.as_bytes()).await.unwrap();
+    let response: Response =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert!(matches!(response, Response::Renewed { .. }), "{response:?}");
+
+    let release = format!("{{\"op\":\"release\",\"lease_id\":\"{lease_id}\"}}\n");
+    writer.write_all(release.as_bytes()).await.unwrap();
+    let response: Response =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert!(
+        matches!(response, Response::Released { .. }),
+        "{response:?}"
+    );
+
+    let config = Config::from_file("tests/fixtures/macro/dbfast.toml").unwrap();
+    let admin_pool = DatabasePool::from_config(&config.database).await.unwrap();
+    assert!(!admin_pool.database_exists(&database).await.unwrap());
+
+    // Releasing twice is an error
+    writer.write_all(release.as_bytes()).await.unwrap();
+    let response: Response =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert!(matches!(response, Response::Error { .. }), "{response:?}");
+
+    // A lease without heartbeats expires and its database is dropped
+    let Response::Leased { database, .. } = server
+        .handle(Request::Acquire {
+            env: None,
+            with_seeds: true,
+        })
+        .await
+    else {
+        panic!("Expected a lease");
+    };
+    let start = Instant::now();
+    while admin_pool.database_exists(&database).await.unwrap() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "expired lease was not dropped"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    wait_for_warm(&server, 2).await;
+    listener.abort();
+    maintenance.abort();
+    assert_eq!(server.shutdown().await, 2);
+}
+
+/// Test that the same protocol is served over localhost HTTP
+#[tokio::test]
+async fn test_serve_leases_over_http() {
+    let Some(server) = start_server(ServeOptions {
+        warm_per_template: 0,
+        ..ServeOptions::default()
+    })
+    .await
+    else {
+        return;
+    };
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let listener = {
+        let server = server.clone();
+        tokio::spawn(async move { server.serve_http(addr).await })
+    };
+
+    let body = r#"{"op":"acquire","with_seeds":false}"#;
+    let request = format!(
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let mut stream = loop {
+        match tokio::net::TcpStream::connect(addr).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).await.unwrap();
+
+    assert!(raw.starts_with("HTTP/1.1 200 OK"), "{raw}");
+    let (_, json) = raw.split_once("\r\n\r\n").unwrap();
+    let response: Response = serde_json::from_str(json).unwrap();
+    let Response::Leased { url, .. } = response else {
+        panic!("Expected a lease, got {response:?}");
+    };
+
+    let pool = DatabasePool::new(&url).await.unwrap();
+    let rows = pool
+        .query("SELECT count(*) FROM tb_user", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get::<_, i64>(0), 0);
+    drop(pool);
+
+    // Non-loopback addresses are refused
+    assert!(server
+        .serve_http(std::net::SocketAddr::from(([0, 0, 0, 0], port)))
+        .await
+        .is_err());
+
+    listener.abort();
+    assert_eq!(server.shutdown().await, 1);
+}
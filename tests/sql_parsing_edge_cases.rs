@@ -227,3 +227,34 @@ GRANT EXECUTE ON FUNCTION app.update_contract TO app_role;
     assert!(statements[1].contains("COMMENT ON FUNCTION"));
     assert!(statements[2].contains("GRANT EXECUTE"));
 }
+
+/// Test edge case: Semicolons in strings, identifiers and comments
+/// A single statement must not be sent down the multi-statement path
+#[tokio::test]
+async fn test_semicolons_in_literals_are_single_statement() {
+    let sql_content = r#"
+/* Setup; /* nested; */ still a comment; */
+INSERT INTO "audit;log" (message, pattern)
+VALUES ('started; ok', E'it\'s; escaped') -- trailing; comment
+;
+"#;
+
+    let statements = DatabasePool::parse_sql_statements(sql_content);
+
+    assert_eq!(statements.len(), 1, "Should parse 1 statement");
+    assert!(statements[0].starts_with("INSERT INTO \"audit;log\""));
+    assert!(!DatabasePool::should_use_psql_fallback(sql_content));
+}
+
+/// Test that statement spans point back into the original script
+#[tokio::test]
+async fn test_statement_spans() {
+    let sql_content = "CREATE TABLE t (id INTEGER);\n\n-- seed\nINSERT INTO t\nVALUES (1);";
+
+    let statements = dbfast::sql_lexer::split_statements(sql_content);
+
+    assert_eq!(statements.len(), 2);
+    assert_eq!(statements[1].text, "INSERT INTO t\nVALUES (1)");
+    assert_eq!(&sql_content[statements[1].span.clone()], statements[1].text);
+    assert_eq!((statements[1].start_line, statements[1].end_line), (4, 5));
+}
@@ -127,6 +127,76 @@ async fn test_environment_filtering() {
     assert!(prod_files[0].to_string_lossy().contains("prod_data.sql"));
 }
 
+/// Test discovery driven by an environment from dbfast.toml, including nested directories
+#[tokio::test]
+async fn test_environment_config_discovery() {
+    use dbfast::environment::EnvironmentConfig;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let nested_schema_dir = temp_dir.path().join("0_schema/01_write_side/0101_user");
+    let seed_common_dir = temp_dir.path().join("1_seed_common");
+    let seed_backend_dir = temp_dir.path().join("2_seed_backend");
+    let migration_dir = temp_dir.path().join("6_migration");
+
+    for dir in [
+        &nested_schema_dir,
+        &seed_common_dir,
+        &seed_backend_dir,
+        &migration_dir,
+    ] {
+        fs::create_dir_all(dir).unwrap();
+    }
+
+    fs::write(
+        nested_schema_dir.join("010111_tb_user.sql"),
+        "CREATE TABLE tb_user (id SERIAL PRIMARY KEY);",
+    )
+    .unwrap();
+    fs::write(
+        seed_common_dir.join("0101_admin.sql"),
+        "INSERT INTO tb_user DEFAULT VALUES;",
+    )
+    .unwrap();
+    fs::write(
+        seed_backend_dir.join("0201_posts.sql"),
+        "INSERT INTO tb_user DEFAULT VALUES;",
+    )
+    .unwrap();
+    fs::write(
+        migration_dir.join("001_add_column.sql"),
+        "ALTER TABLE tb_user ADD COLUMN name TEXT;",
+    )
+    .unwrap();
+
+    let repo = SqlRepository::new(temp_dir.path()).unwrap();
+
+    let local = EnvironmentConfig {
+        name: "local".to_string(),
+        include_directories: Some(vec![
+            "0_schema".to_string(),
+            "1_seed_common".to_string(),
+            "2_seed_backend".to_string(),
+        ]),
+        ..Default::default()
+    };
+
+    let files = repo
+        .discover_sql_files_for_environment(&local)
+        .await
+        .unwrap();
+
+    assert_eq!(files.len(), 3);
+    assert!(files[0].to_string_lossy().contains("010111_tb_user.sql"));
+    assert!(files[1].to_string_lossy().contains("1_seed_common"));
+    assert!(files[2].to_string_lossy().contains("2_seed_backend"));
+
+    // Seed detection is based on the top-level directory
+    assert!(!repo.is_seed_file(&files[0]));
+    assert!(repo.is_seed_file(&files[1]));
+    assert!(repo.is_seed_file(&files[2]));
+}
+
 /// Test SQL content loading
 /// RED PHASE: Should FAIL because SqlRepository doesn't exist
 #[tokio::test]
@@ -143,3 +213,53 @@ async fn test_sql_content_loading() {
 
     assert_eq!(loaded_content.trim(), sql_content);
 }
+
+/// Test that `env=` and `depends-on=` directives filter and order files
+#[tokio::test]
+async fn test_directives_filter_and_order_files() {
+    let temp_dir = TempDir::new().unwrap();
+
+    fs::write(
+        temp_dir.path().join("001_views.sql"),
+        "-- dbfast: depends-on=002_tables.sql\nCREATE VIEW v AS SELECT * FROM t;",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("002_tables.sql"),
+        "CREATE TABLE t (id INT);",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("003_staging_data.sql"),
+        "-- dbfast: env=staging\nINSERT INTO t VALUES (1);",
+    )
+    .unwrap();
+
+    let repo = SqlRepository::new(temp_dir.path()).unwrap();
+
+    let files = repo.discover_sql_files(&["local"]).await.unwrap();
+    let names: Vec<_> = files
+        .iter()
+        .map(|file| file.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, vec!["002_tables.sql", "001_views.sql"]);
+
+    // Without a selected environment, environment-specific files are skipped
+    let files = repo.discover_sql_files(&[]).await.unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(!files
+        .iter()
+        .any(|file| file.ends_with("003_staging_data.sql")));
+
+    let files = repo.discover_sql_files(&["staging"]).await.unwrap();
+    assert_eq!(files.len(), 3);
+    assert!(files[2].ends_with("003_staging_data.sql"));
+
+    fs::write(
+        temp_dir.path().join("002_tables.sql"),
+        "-- dbfast: depends-on=001_views.sql\nCREATE TABLE t (id INT);",
+    )
+    .unwrap();
+    let error = repo.discover_sql_files(&["local"]).await.unwrap_err();
+    assert!(error.to_string().contains("cycle"), "{error}");
+}
@@ -2,10 +2,16 @@ mod common;
 
 use common::TestDatabase;
 use dbfast::change_detector::ChangeDetector;
+use dbfast::config::{DatabaseConfig, SqlExecutor};
+use dbfast::database::DatabaseError;
+use dbfast::execution::SegmentMode;
+use dbfast::profile::BuildReport;
 use dbfast::scanner::FileScanner;
 use dbfast::template::TemplateManager;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tempfile::TempDir;
 
 /// Test helper to create a temporary directory with test SQL files
@@ -201,3 +207,497 @@ async fn test_smart_template_creation() {
         "Should return true when template was rebuilt due to changes"
     );
 }
+
+#[tokio::test]
+async fn test_layered_template_partial_rebuild() {
+    use dbfast::sql_repository::SqlRepository;
+    use dbfast::template::TemplateLayer;
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema_files = create_test_sql_files(temp_dir.path()).unwrap();
+
+    let seed_dir = temp_dir.path().join("1_seed_common");
+    fs::create_dir_all(&seed_dir).unwrap();
+    let seed_file = seed_dir.join("0101_users.sql");
+    fs::write(&seed_file, "INSERT INTO tb_user (name) VALUES ('alice');").unwrap();
+
+    let test_db = TestDatabase::create_unique("layered").await.unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+
+    let template_manager = TemplateManager::new_with_change_detection(
+        test_db.admin_pool.clone(),
+        test_db.admin_config(),
+        temp_dir.path().to_path_buf(),
+    );
+
+    let repo = SqlRepository::new(temp_dir.path()).unwrap();
+    let mut sql_files = schema_files.clone();
+    sql_files.push(seed_file.clone());
+    let layers = TemplateLayer::group_files(&repo, &sql_files);
+    assert_eq!(layers.len(), 2);
+
+    let base_layer = TemplateManager::layer_database_name(&template_name, 0, layers.len());
+    let base_metadata = temp_dir.path().join(format!(".dbfast/{base_layer}.json"));
+
+    // First build creates both layers
+    assert!(template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .unwrap());
+    let base_built_at = fs::read_to_string(&base_metadata).unwrap();
+
+    // Nothing changed - nothing is rebuilt
+    assert!(!template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .unwrap());
+
+    // Changing the seed layer rebuilds only the seed layer
+    fs::write(
+        &seed_file,
+        "INSERT INTO tb_user (name) VALUES ('alice'), ('bob');",
+    )
+    .unwrap();
+    assert!(template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .unwrap());
+    assert_eq!(
+        fs::read_to_string(&base_metadata).unwrap(),
+        base_built_at,
+        "Schema layer should not be rebuilt when only seed data changed"
+    );
+
+    let template_pool =
+        dbfast::DatabasePool::new_for_database(&test_db.admin_config(), &template_name)
+            .await
+            .unwrap();
+    let rows = template_pool
+        .query("SELECT COUNT(*) FROM tb_user", &[])
+        .await
+        .unwrap();
+    let count: i64 = rows[0].get(0);
+    assert_eq!(count, 2);
+    drop(template_pool);
+
+    template_manager
+        .drop_template(&template_name)
+        .await
+        .unwrap();
+    template_manager.drop_template(&base_layer).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_failed_rebuild_keeps_previous_template() {
+    let temp_dir = TempDir::new().unwrap();
+    let sql_files = create_test_sql_files(temp_dir.path()).unwrap();
+
+    let test_db = TestDatabase::create_unique("atomic_swap").await.unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+
+    let template_manager = TemplateManager::new_with_change_detection(
+        test_db.admin_pool.clone(),
+        test_db.admin_config(),
+        temp_dir.path().to_path_buf(),
+    );
+
+    assert!(template_manager
+        .smart_create_template(&template_name, &sql_files)
+        .await
+        .unwrap());
+
+    // A broken change must not take the existing template down with it
+    fs::write(&sql_files[1], "CREATE INDEX broken ON missing_table(name);").unwrap();
+    let result = template_manager
+        .smart_create_template(&template_name, &sql_files)
+        .await;
+    assert!(result.is_err(), "Rebuild with invalid SQL should fail");
+
+    assert!(template_manager
+        .template_exists(&template_name)
+        .await
+        .unwrap());
+    let staging_name = TemplateManager::staging_database_name(&template_name);
+    assert!(!template_manager
+        .template_exists(&staging_name)
+        .await
+        .unwrap());
+
+    // Still stale, so fixing the file swaps in a new generation
+    fs::write(
+        &sql_files[1],
+        "CREATE INDEX IF NOT EXISTS idx_user_name_v2 ON tb_user(name);",
+    )
+    .unwrap();
+    assert!(template_manager
+        .smart_create_template(&template_name, &sql_files)
+        .await
+        .unwrap());
+    let retired_name = TemplateManager::retired_database_name(&template_name);
+    assert!(!template_manager
+        .template_exists(&retired_name)
+        .await
+        .unwrap());
+
+    template_manager
+        .drop_template(&template_name)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_build_report_records_transaction_boundaries() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema_dir = temp_dir.path().join("0_schema");
+    fs::create_dir_all(&schema_dir).unwrap();
+    let schema_file = schema_dir.join("01_accounts.sql");
+    let skipped_file = schema_dir.join("02_accounts_again.sql");
+    fs::write(
+        &schema_file,
+        "CREATE TABLE accounts (id INT, email TEXT);\n\
+         CREATE INDEX CONCURRENTLY accounts_email_idx ON accounts (email);\n\
+         CREATE TABLE audit (id INT);\n",
+    )
+    .unwrap();
+    fs::write(
+        &skipped_file,
+        "-- dbfast: skip-if-exists=accounts\nCREATE TABLE accounts (id INT);\n",
+    )
+    .unwrap();
+
+    let test_db = TestDatabase::create_unique("build_boundaries")
+        .await
+        .unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+    let template_manager = TemplateManager::new_with_change_detection(
+        test_db.admin_pool.clone(),
+        test_db.admin_config(),
+        temp_dir.path().to_path_buf(),
+    );
+    assert!(template_manager
+        .smart_create_template(&template_name, &[&schema_file, &skipped_file])
+        .await
+        .unwrap());
+
+    let reports: Vec<PathBuf> = fs::read_dir(temp_dir.path().join(".dbfast/builds"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    let report: BuildReport =
+        serde_json::from_str(&fs::read_to_string(&reports[0]).unwrap()).unwrap();
+    let segments: Vec<(SegmentMode, usize, usize)> = report
+        .segments
+        .iter()
+        .map(|segment| {
+            (
+                segment.mode,
+                *segment.statements.start(),
+                *segment.statements.end(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        segments,
+        vec![
+            (SegmentMode::Transaction, 1, 1),
+            (SegmentMode::Autocommit, 2, 2),
+            (SegmentMode::Transaction, 3, 4),
+        ]
+    );
+    assert_eq!(report.segments[1].first.line, 2);
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].file.as_ref(), Some(&skipped_file));
+    assert_eq!(report.skipped[0].reason, "accounts already exists");
+
+    template_manager
+        .drop_template(&template_name)
+        .await
+        .unwrap();
+}
+
+// A current-thread runtime, like `#[dbfast::test]`, still runs the watchdog
+#[tokio::test]
+async fn test_build_timeout_cancels_runaway_build() {
+    let temp_dir = TempDir::new().unwrap();
+    let sql_file = temp_dir.path().join("0_schema/slow.sql");
+    fs::create_dir_all(sql_file.parent().unwrap()).unwrap();
+    fs::write(
+        &sql_file,
+        "CREATE TABLE t (id INT);\nSELECT pg_sleep(60);\n",
+    )
+    .unwrap();
+
+    let test_db = TestDatabase::create_unique("build_timeout").await.unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+
+    for sql_executor in [SqlExecutor::Native, SqlExecutor::Psql] {
+        let db_config = DatabaseConfig {
+            sql_executor,
+            ..test_db.admin_config()
+        };
+        let template_manager = TemplateManager::new_with_change_detection(
+            test_db.admin_pool.clone().with_sql_executor(sql_executor),
+            db_config,
+            temp_dir.path().to_path_buf(),
+        )
+        .with_build_timeout(Some(Duration::from_millis(500)));
+
+        let start = Instant::now();
+        let error = template_manager
+            .smart_create_template(&template_name, &[&sql_file])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                DatabaseError::BuildTimeout {
+                    timeout_ms: 500,
+                    ..
+                }
+            ),
+            "{sql_executor:?}: {error}"
+        );
+        assert!(
+            start.elapsed() < Duration::from_secs(20),
+            "{sql_executor:?}"
+        );
+
+        let staging_name = TemplateManager::staging_database_name(&template_name);
+        assert!(!template_manager
+            .template_exists(&staging_name)
+            .await
+            .unwrap());
+        assert!(!template_manager
+            .template_exists(&template_name)
+            .await
+            .unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_smart_create_builds_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let sql_files = create_test_sql_files(temp_dir.path()).unwrap();
+
+    let test_db = TestDatabase::create_unique("build_lock").await.unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+
+    let first = TemplateManager::new_with_change_detection(
+        test_db.admin_pool.clone(),
+        test_db.admin_config(),
+        temp_dir.path().to_path_buf(),
+    );
+    let second = first.clone();
+
+    // Both see a missing template; the advisory lock must let only one build it
+    let (first_result, second_result) = tokio::join!(
+        first.smart_create_template(&template_name, &sql_files),
+        second.smart_create_template(&template_name, &sql_files),
+    );
+    let built = [first_result.unwrap(), second_result.unwrap()];
+    assert_eq!(
+        built.iter().filter(|&&rebuilt| rebuilt).count(),
+        1,
+        "Exactly one caller should build the template, got {built:?}"
+    );
+
+    first.drop_template(&template_name).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_fingerprinted_templates_survive_branch_switch() {
+    use dbfast::sql_repository::SqlRepository;
+    use dbfast::template::TemplateLayer;
+
+    let temp_dir = TempDir::new().unwrap();
+    let sql_files = create_test_sql_files(temp_dir.path()).unwrap();
+
+    let test_db = TestDatabase::create_unique("fingerprint").await.unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+
+    let mut db_config = test_db.admin_config();
+    db_config.fingerprint_templates = true;
+    let template_manager = TemplateManager::new_with_change_detection(
+        test_db.admin_pool.clone(),
+        db_config,
+        temp_dir.path().to_path_buf(),
+    );
+
+    let repo = SqlRepository::new(temp_dir.path()).unwrap();
+    let layers = TemplateLayer::group_files(&repo, &sql_files);
+    let original_content = fs::read_to_string(&sql_files[1]).unwrap();
+
+    // "main" branch
+    assert!(template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .unwrap());
+    let main_template = template_manager
+        .layer_database_names(&template_name, &layers)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_ne!(main_template, template_name);
+    assert!(main_template.starts_with(&format!("{template_name}_")));
+
+    // "feature" branch gets its own template
+    fs::write(
+        &sql_files[1],
+        "CREATE INDEX IF NOT EXISTS idx_user_name_feature ON tb_user(name);",
+    )
+    .unwrap();
+    assert!(template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .unwrap());
+    let feature_template = template_manager
+        .layer_database_names(&template_name, &layers)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_ne!(feature_template, main_template);
+
+    // Switching back reuses the "main" template without rebuilding
+    fs::write(&sql_files[1], original_content).unwrap();
+    assert!(!template_manager
+        .smart_create_layered_template(&template_name, &layers)
+        .await
+        .unwrap());
+    assert!(template_manager
+        .template_exists(&feature_template)
+        .await
+        .unwrap());
+
+    // Keeping one fingerprint drops the least recently used one
+    let dropped = template_manager
+        .gc_fingerprinted_templates(None, Some(1), false)
+        .await
+        .unwrap();
+    assert_eq!(dropped, vec![feature_template.clone()]);
+    assert!(!template_manager
+        .template_exists(&feature_template)
+        .await
+        .unwrap());
+    assert!(template_manager
+        .template_exists(&main_template)
+        .await
+        .unwrap());
+
+    template_manager
+        .drop_template(&main_template)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_changed_variable_triggers_rebuild() {
+    let temp_dir = TempDir::new().unwrap();
+    let sql_file = temp_dir.path().join("001_settings.sql");
+    fs::write(
+        &sql_file,
+        "CREATE TABLE settings (tenants INT);\nINSERT INTO settings VALUES (:'tenant_count');",
+    )
+    .unwrap();
+    let sql_files = vec![sql_file];
+
+    let test_db = TestDatabase::create_unique("template_variables")
+        .await
+        .unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+    let manager_with = |tenant_count: &str| {
+        let variables = BTreeMap::from([("tenant_count".to_string(), tenant_count.to_string())]);
+        TemplateManager::new_with_change_detection(
+            test_db.admin_pool.clone().with_variables(variables),
+            test_db.admin_config(),
+            temp_dir.path().to_path_buf(),
+        )
+    };
+
+    assert!(manager_with("3")
+        .smart_create_template(&template_name, &sql_files)
+        .await
+        .unwrap());
+    assert!(!manager_with("3")
+        .smart_create_template(&template_name, &sql_files)
+        .await
+        .unwrap());
+    assert!(
+        manager_with("5")
+            .smart_create_template(&template_name, &sql_files)
+            .await
+            .unwrap(),
+        "Changing a variable should rebuild the template"
+    );
+
+    let template_pool = test_db
+        .admin_pool
+        .for_database(&template_name)
+        .await
+        .unwrap();
+    let rows = template_pool
+        .query("SELECT tenants FROM settings", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get::<_, i32>(0), 5);
+    drop(template_pool);
+
+    let error = TemplateManager::new_with_change_detection(
+        test_db.admin_pool.clone(),
+        test_db.admin_config(),
+        temp_dir.path().to_path_buf(),
+    )
+    .smart_create_template(&template_name, &sql_files)
+    .await
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("Undefined variable :tenant_count"),
+        "{error}"
+    );
+
+    manager_with("5")
+        .drop_template(&template_name)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_build_writes_profile_report() {
+    let temp_dir = TempDir::new().unwrap();
+    let sql_files = create_test_sql_files(temp_dir.path()).unwrap();
+
+    let test_db = TestDatabase::create_unique("build_profile").await.unwrap();
+    let template_name = format!("tmpl_{}", test_db.name);
+    let template_manager = TemplateManager::new_with_change_detection(
+        test_db.admin_pool.clone(),
+        test_db.admin_config(),
+        temp_dir.path().to_path_buf(),
+    )
+    .with_statement_profiling(true);
+
+    assert!(template_manager
+        .smart_create_template(&template_name, &sql_files)
+        .await
+        .unwrap());
+
+    let reports: Vec<PathBuf> = fs::read_dir(temp_dir.path().join(".dbfast/builds"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(reports.len(), 1, "One report per build");
+
+    let report: BuildReport =
+        serde_json::from_str(&fs::read_to_string(&reports[0]).unwrap()).unwrap();
+    assert_eq!(report.template, template_name);
+    let files: Vec<&PathBuf> = report.files.iter().map(|timing| &timing.file).collect();
+    assert_eq!(files, sql_files.iter().collect::<Vec<_>>());
+    assert_eq!(report.statements.len(), 2);
+    assert_eq!(report.statements[0].line, 1);
+    assert!(report.duration_ms >= report.files[0].duration_ms);
+
+    template_manager
+        .drop_template(&template_name)
+        .await
+        .unwrap();
+}
@@ -1,15 +1,25 @@
-use dbfast::config::DatabaseConfig;
+use dbfast::config::{DatabaseConfig, SqlExecutor};
+use dbfast::tls::TlsConfig;
 use tempfile::TempDir;
 
 /// Test helper to create a test database config
 fn create_test_db_config() -> DatabaseConfig {
     DatabaseConfig {
-        host: "localhost".to_string(),
-        port: 5432,
-        user: "postgres".to_string(),
+        host: Some("localhost".to_string()),
+        port: Some(5432),
+        user: Some("postgres".to_string()),
+        url: None,
+        service: None,
+        admin_database: None,
         password_env: Some("POSTGRES_PASSWORD".to_string()),
+        password_file: None,
+        password_command: None,
         template_name: "test_template".to_string(),
         allow_multi_statement: true,
+        template_lock_timeout_secs: 300,
+        fingerprint_templates: false,
+        sql_executor: SqlExecutor::Native,
+        tls: TlsConfig::default(),
     }
 }
 
@@ -42,3 +52,113 @@ async fn test_template_manager_with_change_detection_structure() {
     // These tests verify the API design is correct
     // Full integration tests with real database would be in a separate test that requires PostgreSQL
 }
+
+#[test]
+fn test_variant_template_name() {
+    use dbfast::template::TemplateManager;
+
+    assert_eq!(
+        TemplateManager::variant_template_name("myapp_template", None, false).unwrap(),
+        "myapp_template"
+    );
+    assert_eq!(
+        TemplateManager::variant_template_name("myapp_template", None, true).unwrap(),
+        "myapp_template__seeds"
+    );
+    assert_eq!(
+        TemplateManager::variant_template_name("myapp_template", Some("local"), true).unwrap(),
+        "myapp_template__local__seeds"
+    );
+    assert_eq!(
+        TemplateManager::variant_template_name("myapp_template", Some("ci"), false).unwrap(),
+        "myapp_template__ci"
+    );
+
+    // Environment names end up in database names as they are
+    for environment in ["ci-fast", "Local", ""] {
+        let error =
+            TemplateManager::variant_template_name("myapp_template", Some(environment), false)
+                .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains(&format!("Invalid environment name '{environment}'")),
+            "{error}"
+        );
+    }
+}
+
+#[test]
+fn test_template_layer_grouping() {
+    use dbfast::sql_repository::SqlRepository;
+    use dbfast::template::{TemplateLayer, TemplateManager};
+    use std::path::PathBuf;
+
+    let temp_dir = TempDir::new().unwrap();
+    let repo = SqlRepository::new(temp_dir.path()).unwrap();
+    let root = temp_dir.path();
+
+    let files: Vec<PathBuf> = [
+        "0_schema/01_tables/tb_user.sql",
+        "0_schema/02_views/v_user.sql",
+        "1_seed_common/0101_admin.sql",
+        "2_seed_backend/0201_posts.sql",
+        "2_seed_backend/0202_comments.sql",
+    ]
+    .iter()
+    .map(|file| root.join(file))
+    .collect();
+
+    let layers = TemplateLayer::group_files(&repo, &files);
+
+    let names: Vec<&str> = layers.iter().map(|layer| layer.name.as_str()).collect();
+    assert_eq!(names, ["0_schema", "1_seed_common", "2_seed_backend"]);
+    assert_eq!(layers[0].sql_files.len(), 2);
+    assert_eq!(layers[1].sql_files.len(), 1);
+    assert_eq!(layers[2].sql_files.len(), 2);
+
+    // Only the top layer uses the template name itself
+    assert_eq!(
+        TemplateManager::layer_database_name("app", 0, 3),
+        "app__layer0"
+    );
+    assert_eq!(
+        TemplateManager::layer_database_name("app", 1, 3),
+        "app__layer1"
+    );
+    assert_eq!(TemplateManager::layer_database_name("app", 2, 3), "app");
+}
+
+#[test]
+fn test_template_lock_key() {
+    use dbfast::template::TemplateManager;
+
+    assert_eq!(
+        TemplateManager::template_lock_key("app_template"),
+        TemplateManager::template_lock_key("app_template")
+    );
+    assert_ne!(
+        TemplateManager::template_lock_key("app_template"),
+        TemplateManager::template_lock_key("app_template__seeds")
+    );
+}
+
+#[test]
+fn test_staging_names_fit_identifier_limit() {
+    use dbfast::template::TemplateManager;
+
+    assert_eq!(
+        TemplateManager::staging_database_name("app_template"),
+        "app_template__next"
+    );
+    assert_eq!(
+        TemplateManager::retired_database_name("app_template"),
+        "app_template__old"
+    );
+
+    let long_name = "t".repeat(60);
+    let staging = TemplateManager::staging_database_name(&long_name);
+    let retired = TemplateManager::retired_database_name(&long_name);
+    assert!(staging.len() <= 63 && retired.len() <= 63);
+    assert!(staging.ends_with("__next") && retired.ends_with("__old"));
+}
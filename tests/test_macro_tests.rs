@@ -0,0 +1,41 @@
+use dbfast::testing::TestDatabase;
+use dbfast::DatabasePool;
+
+/// Count the users in the test database
+async fn user_count(pool: &DatabasePool) -> i64 {
+    let rows = pool
+        .query("SELECT count(*) FROM tb_user", &[])
+        .await
+        .unwrap();
+    rows[0].get(0)
+}
+
+/// Test that the macro injects a pool for a database cloned with seeds
+#[dbfast::test(config = "tests/fixtures/macro/dbfast.toml")]
+async fn test_macro_injects_seeded_pool(pool: DatabasePool) {
+    assert_eq!(user_count(&pool).await, 2);
+
+    // Changes stay private to this test's database
+    pool.query("DELETE FROM tb_user", &[]).await.unwrap();
+    assert_eq!(user_count(&pool).await, 0);
+}
+
+/// Test that the environment and fixture attributes shape the database
+#[dbfast::test(
+    config = "tests/fixtures/macro/dbfast.toml",
+    env = "schema",
+    fixtures("fixtures/carol.sql")
+)]
+async fn test_macro_environment_and_fixtures(db: &TestDatabase) {
+    assert!(db.name().starts_with("dbfast_test_"));
+    assert!(db.template().contains("schema"));
+    assert_eq!(user_count(db.pool()).await, 1);
+}
+
+/// Test that the connection URL can be injected and a `Result` returned
+#[dbfast::test(config = "tests/fixtures/macro/dbfast.toml", seeds = false)]
+async fn test_macro_injects_url(url: String) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = DatabasePool::new(&url).await?;
+    assert_eq!(user_count(&pool).await, 0);
+    Ok(())
+}
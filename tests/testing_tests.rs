@@ -0,0 +1,103 @@
+use dbfast::testing::{TestDatabase, TestDatabaseOptions};
+use dbfast::DatabasePool;
+use std::fs;
+use tempfile::TempDir;
+
+/// Write a small project with a schema and a seed file into `dir`
+fn write_project(dir: &TempDir, template_name: &str) {
+    fs::write(
+        dir.path().join("dbfast.toml"),
+        format!(
+            r#"[database]
+host = "localhost"
+port = 5432
+user = "postgres"
+password_env = "POSTGRES_PASSWORD"
+template_name = "{template_name}"
+
+[repository]
+path = "./db"
+type = "structured"
+"#
+        ),
+    )
+    .unwrap();
+
+    let schema_dir = dir.path().join("db/0_schema");
+    let seed_dir = dir.path().join("db/1_seed_common");
+    fs::create_dir_all(&schema_dir).unwrap();
+    fs::create_dir_all(&seed_dir).unwrap();
+    fs::write(
+        schema_dir.join("01_user.sql"),
+        "CREATE TABLE tb_user (id SERIAL PRIMARY KEY, name TEXT NOT NULL);",
+    )
+    .unwrap();
+    fs::write(
+        seed_dir.join("01_users.sql"),
+        "INSERT INTO tb_user (name) VALUES ('alice'), ('bob');",
+    )
+    .unwrap();
+}
+
+/// Test that a test database is cloned from a fresh template and dropped afterwards
+#[tokio::test]
+async fn test_test_database_lifecycle() {
+    let project = TempDir::new().unwrap();
+    let template_name = format!("testing_template_{}", std::process::id());
+    write_project(&project, &template_name);
+
+    let options = TestDatabaseOptions {
+        config_path: Some(project.path().join("dbfast.toml")),
+        ..TestDatabaseOptions::default()
+    };
+
+    let db = match TestDatabase::with_options(&options).await {
+        Ok(db) => db,
+        Err(e) => {
+            println!("⚠️  Could not create test database (expected without PostgreSQL): {e}");
+            return;
+        }
+    };
+
+    assert!(db.name().starts_with("dbfast_test_"));
+    assert!(db.url().starts_with("postgresql://"));
+    assert!(db.url().ends_with(&format!("/{}", db.name())));
+
+    let rows = db
+        .pool()
+        .query("SELECT count(*) FROM tb_user", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get::<_, i64>(0), 2);
+
+    // Explicit cleanup
+    let admin_pool = DatabasePool::new(&db.url().replace(db.name(), "postgres"))
+        .await
+        .unwrap();
+    let first_name = db.name().to_string();
+    db.cleanup().await.unwrap();
+    assert!(!admin_pool.database_exists(&first_name).await.unwrap());
+
+    // Cleanup on drop, even with a connection still open
+    let db = TestDatabase::with_options(&options).await.unwrap();
+    let second_name = db.name().to_string();
+    assert_ne!(first_name, second_name);
+    db.pool().query("SELECT 1", &[]).await.unwrap();
+    drop(db);
+    assert!(!admin_pool.database_exists(&second_name).await.unwrap());
+
+    // Drop the template and its layers
+    let rows = admin_pool
+        .query(
+            "SELECT datname FROM pg_database WHERE datname LIKE $1",
+            &[&format!("{template_name}%")],
+        )
+        .await
+        .unwrap();
+    for row in rows {
+        admin_pool
+            .drop_database(&row.get::<_, String>(0))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+//! TLS connections against the test server
+//!
+//! Tests needing an encrypting server are skipped when its `ssl` setting is
+//! off. Set `DBFAST_TEST_SSLROOTCERT` to the CA that signed the server
+//! certificate (issued for `localhost`) to also test `verify-ca` and
+//! `verify-full` connections that succeed.
+
+use dbfast::config::{DatabaseConfig, SqlExecutor};
+use dbfast::database::{DatabaseError, DatabasePool};
+use dbfast::execution::ExecutionReport;
+use dbfast::remote::RemoteConfig;
+use dbfast::tls::{SslMode, TlsConfig, TlsError};
+use std::path::{Path, PathBuf};
+
+const UNTRUSTED_CA: &str = "tests/fixtures/tls/untrusted-ca.crt";
+
+fn config(host: &str, tls: TlsConfig) -> DatabaseConfig {
+    DatabaseConfig {
+        host: Some(host.to_string()),
+        port: Some(5432),
+        user: Some("postgres".to_string()),
+        url: None,
+        service: None,
+        admin_database: None,
+        password_env: Some("POSTGRES_PASSWORD".to_string()),
+        password_file: None,
+        password_command: None,
+        template_name: "postgres".to_string(),
+        allow_multi_statement: true,
+        template_lock_timeout_secs: 300,
+        fingerprint_templates: false,
+        sql_executor: SqlExecutor::Native,
+        tls,
+    }
+}
+
+fn mode(sslmode: SslMode, sslrootcert: Option<&str>) -> TlsConfig {
+    TlsConfig {
+        sslmode: Some(sslmode),
+        sslrootcert: sslrootcert.map(PathBuf::from),
+        ..TlsConfig::default()
+    }
+}
+
+/// Whether the pool's connection is encrypted
+async fn encrypted(pool: &DatabasePool) -> bool {
+    let rows = pool
+        .query(
+            "SELECT ssl FROM pg_stat_ssl WHERE pid = pg_backend_pid()",
+            &[],
+        )
+        .await
+        .unwrap();
+    rows[0].get(0)
+}
+
+async fn run_psql(tls: TlsConfig, sql_file: &Path) -> Result<ExecutionReport, DatabaseError> {
+    DatabasePool::from_config(&config("localhost", tls))
+        .await?
+        .with_sql_executor(SqlExecutor::Psql)
+        .execute_sql_files(&[sql_file], true)
+        .await
+}
+
+async fn server_has_ssl() -> bool {
+    let pool = DatabasePool::from_config(&config("localhost", mode(SslMode::Disable, None)))
+        .await
+        .unwrap();
+    let rows = pool.query("SHOW ssl", &[]).await.unwrap();
+    rows[0].get::<_, String>(0) == "on"
+}
+
+#[tokio::test]
+async fn test_disable_and_prefer() {
+    let ssl = server_has_ssl().await;
+
+    let plain = DatabasePool::from_config(&config("localhost", mode(SslMode::Disable, None)))
+        .await
+        .unwrap();
+    assert!(!encrypted(&plain).await);
+
+    let preferred = DatabasePool::from_config(&config("localhost", TlsConfig::default()))
+        .await
+        .unwrap();
+    assert_eq!(encrypted(&preferred).await, ssl);
+}
+
+#[tokio::test]
+async fn test_require_and_untrusted_root() {
+    if !server_has_ssl().await {
+        eprintln!("Skipping: the test server has ssl = off");
+        return;
+    }
+
+    let required = DatabasePool::from_config(&config("localhost", mode(SslMode::Require, None)))
+        .await
+        .unwrap();
+    assert!(encrypted(&required).await);
+    let url = required.connection_url().unwrap();
+    assert!(url.ends_with("/postgres?sslmode=require"), "{url}");
+
+    // A direct connection reports the failed handshake instead of timing out
+    let mut remote = RemoteConfig::new(
+        "local".to_string(),
+        format!("postgres://postgres@localhost:5432/postgres?sslmode=verify-full&sslrootcert={UNTRUSTED_CA}"),
+        "local".to_string(),
+    );
+    remote.password_env = Some("POSTGRES_PASSWORD".to_string());
+    let pool = DatabasePool::from_remote(&remote).await.unwrap();
+    let error = pool.query_direct("SELECT 1", &[]).await.unwrap_err();
+    assert!(error.to_string().contains("UnknownIssuer"), "{error}");
+
+    // psql receives the same settings and rejects the server as well
+    let sql_file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(sql_file.path(), "SELECT 1;\n").unwrap();
+    run_psql(mode(SslMode::Require, None), sql_file.path())
+        .await
+        .unwrap();
+    let error = run_psql(
+        mode(SslMode::VerifyFull, Some(UNTRUSTED_CA)),
+        sql_file.path(),
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("certificate"), "{error}");
+}
+
+#[tokio::test]
+async fn test_verified_connections() {
+    let Ok(root_cert) = std::env::var("DBFAST_TEST_SSLROOTCERT") else {
+        eprintln!("Skipping: DBFAST_TEST_SSLROOTCERT is not set");
+        return;
+    };
+
+    let full = DatabasePool::from_config(&config(
+        "localhost",
+        mode(SslMode::VerifyFull, Some(&root_cert)),
+    ))
+    .await
+    .unwrap();
+    assert!(encrypted(&full).await);
+
+    // verify-ca accepts a certificate issued for another host name
+    let ca = DatabasePool::from_config(&config(
+        "127.0.0.1",
+        mode(SslMode::VerifyCa, Some(&root_cert)),
+    ))
+    .await
+    .unwrap();
+    assert!(encrypted(&ca).await);
+
+    // A remote reads its TLS settings from the URL
+    let mut remote = RemoteConfig::new(
+        "local".to_string(),
+        format!("postgres://postgres@localhost:5432/postgres?sslmode=verify-full&sslrootcert={root_cert}"),
+        "local".to_string(),
+    );
+    remote.password_env = Some("POSTGRES_PASSWORD".to_string());
+    let pool = DatabasePool::from_remote(&remote).await.unwrap();
+    assert!(encrypted(&pool).await);
+}
+
+#[test]
+fn test_client_certificate() {
+    let client = TlsConfig {
+        sslcert: Some(PathBuf::from("tests/fixtures/tls/client.crt")),
+        sslkey: Some(PathBuf::from("tests/fixtures/tls/client.key")),
+        ..TlsConfig::default()
+    };
+    assert!(client.connector().is_ok());
+
+    let swapped = TlsConfig {
+        sslcert: client.sslkey.clone(),
+        sslkey: client.sslcert.clone(),
+        ..TlsConfig::default()
+    };
+    assert!(matches!(
+        swapped.connector(),
+        Err(TlsError::NoCertificates(_))
+    ));
+
+    let ca = mode(SslMode::VerifyCa, Some(UNTRUSTED_CA));
+    assert!(ca.connector().is_ok());
+}